@@ -0,0 +1,1234 @@
+//! Writes the Syzygy tables the tests in `src/syzygy.rs` probe: KQvK, KRvK,
+//! KPvK, KBvK, KNNvK and KBNvK, each as a WDL and a DTZ file, and KNvK,
+//! which taking a knight from KNNvK leads to.
+//!
+//! The official files can't be fetched where the tests run, so these are
+//! solved here by retrograde analysis and written in the official format:
+//! the positions are indexed as the original probing code indexes them, and
+//! the values are compressed by recursive pairing and a canonical Huffman
+//! code, in blocks found through a sparse index. The bytes needn't match
+//! the official files, as the format leaves some choices to the writer,
+//! such as the order the pieces are numbered in and what is stored for
+//! illegal positions, but every legal position probes to the same value.
+//!
+//! It shares no code with the engine, so that the tests check one reading of
+//! the format against another; the tests also check a few positions against
+//! values published for the official tables. Regenerate the fixtures with
+//!
+//! ```text
+//! cargo run --release --example syzygy_fixtures
+//! ```
+//!
+//! which takes a few minutes, or pass another directory to write to.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    fs,
+};
+
+const PAWN: u8 = 1;
+const KNIGHT: u8 = 2;
+const BISHOP: u8 = 3;
+const ROOK: u8 = 4;
+const QUEEN: u8 = 5;
+const KING: u8 = 6;
+const BLACK: u8 = 8;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// Flags of each side's (or file's) compressed values.
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+/// Results, for the side to move.
+const WIN: i8 = 2;
+const DRAW: i8 = 0;
+const LOSS: i8 = -2;
+const ILLEGAL: i8 = i8::MIN;
+const UNKNOWN: i8 = i8::MAX;
+
+/// Bytes per block of compressed values, and values per sparse index entry.
+const BLOCK_LOG2: u8 = 5;
+const SPAN_LOG2: u8 = 9;
+
+fn main() {
+    let out = std::env::args().nth(1).unwrap_or_else(|| {
+        String::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/syzygy"
+        ))
+    });
+
+    let mut solved = HashMap::new();
+    for white in [
+        vec![QUEEN],
+        vec![ROOK],
+        vec![BISHOP],
+        vec![KNIGHT],
+        vec![PAWN],
+        vec![KNIGHT, KNIGHT],
+        vec![BISHOP, KNIGHT],
+    ] {
+        let table = Solver::new(&white, &solved).solve();
+        solved.insert(white, table);
+    }
+
+    let k = KING | BLACK;
+    let specs = [
+        Spec {
+            name: "KQvK",
+            white: vec![QUEEN],
+            sides: [vec![KING, QUEEN, k], vec![k, KING, QUEEN]],
+            dtz_flags: 0,
+        },
+        Spec {
+            name: "KRvK",
+            white: vec![ROOK],
+            sides: [vec![ROOK, KING, k], vec![k, ROOK, KING]],
+            dtz_flags: STM | MAPPED | LOSS_PLIES,
+        },
+        Spec {
+            name: "KPvK",
+            white: vec![PAWN],
+            sides: [vec![PAWN, KING, k], vec![PAWN, k, KING]],
+            dtz_flags: MAPPED,
+        },
+        Spec {
+            name: "KBvK",
+            white: vec![BISHOP],
+            sides: [vec![BISHOP, KING, k], vec![k, BISHOP, KING]],
+            dtz_flags: 0,
+        },
+        Spec {
+            name: "KNvK",
+            white: vec![KNIGHT],
+            sides: [vec![KNIGHT, KING, k], vec![k, KNIGHT, KING]],
+            dtz_flags: 0,
+        },
+        Spec {
+            name: "KNNvK",
+            white: vec![KNIGHT, KNIGHT],
+            sides: [vec![KING, k, KNIGHT, KNIGHT], vec![k, KING, KNIGHT, KNIGHT]],
+            dtz_flags: STM | MAPPED | WIDE | LOSS_PLIES,
+        },
+        Spec {
+            name: "KBNvK",
+            white: vec![BISHOP, KNIGHT],
+            sides: [vec![KING, BISHOP, KNIGHT, k], vec![k, KING, BISHOP, KNIGHT]],
+            dtz_flags: STM | MAPPED,
+        },
+    ];
+    let encoding = Encoding::new();
+    for spec in &specs {
+        let table = &solved[&spec.white];
+        let wdl = spec.write(&encoding, table, false);
+        let dtz = spec.write(&encoding, table, true);
+        fs::write(format!("{out}/{}.rtbw", spec.name), &wdl).unwrap();
+        fs::write(format!("{out}/{}.rtbz", spec.name), &dtz).unwrap();
+        println!(
+            "{}: {} and {} bytes, longest win {} plies, longest loss {} plies",
+            spec.name,
+            wdl.len(),
+            dtz.len(),
+            table.longest(0, WIN),
+            table.longest(1, LOSS),
+        );
+    }
+}
+
+// Squares are numbered from a1 = 0 to h8 = 63, along each rank.
+
+fn file(s: usize) -> usize {
+    s % 8
+}
+
+fn rank(s: usize) -> usize {
+    s / 8
+}
+
+fn distance(a: usize, b: usize) -> usize {
+    rank(a).abs_diff(rank(b)).max(file(a).abs_diff(file(b)))
+}
+
+fn step(s: usize, df: i32, dr: i32) -> Option<usize> {
+    let f = file(s) as i32 + df;
+    let r = rank(s) as i32 + dr;
+    ((0..8).contains(&f) && (0..8).contains(&r)).then(|| (r * 8 + f) as usize)
+}
+
+const KING_STEPS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+const KNIGHT_STEPS: [(i32, i32); 8] = [
+    (-2, -1),
+    (-1, -2),
+    (1, -2),
+    (2, -1),
+    (-2, 1),
+    (-1, 2),
+    (1, 2),
+    (2, 1),
+];
+
+fn bit(s: usize) -> u64 {
+    1 << s
+}
+
+/// The squares a white `piece` on `s` attacks, with `occupied` blocking its
+/// slides.
+fn attacks(piece: u8, s: usize, occupied: u64) -> u64 {
+    let steps = |steps: &[(i32, i32)]| {
+        steps
+            .iter()
+            .filter_map(|&(df, dr)| step(s, df, dr))
+            .fold(0, |bits, t| bits | bit(t))
+    };
+    let slides = |dirs: &[(i32, i32)]| {
+        let mut bits = 0;
+        for &(df, dr) in dirs {
+            let mut t = s;
+            while let Some(next) = step(t, df, dr) {
+                bits |= bit(next);
+                if occupied & bit(next) != 0 {
+                    break;
+                }
+                t = next;
+            }
+        }
+        bits
+    };
+    match piece {
+        PAWN => steps(&[(-1, 1), (1, 1)]),
+        KNIGHT => steps(&KNIGHT_STEPS),
+        KING => steps(&KING_STEPS),
+        BISHOP => slides(&[(-1, -1), (1, -1), (-1, 1), (1, 1)]),
+        ROOK => slides(&[(0, -1), (-1, 0), (1, 0), (0, 1)]),
+        QUEEN => slides(&KING_STEPS),
+        _ => unreachable!(),
+    }
+}
+
+/// A position: the white king, the black king, then white's other pieces.
+type Squares = [usize; 4];
+
+/// Where a white move leads.
+enum WhiteChild {
+    Same(Squares),
+    /// A promotion, leading to another table; black's result there.
+    Other(i8),
+}
+
+/// The results and DTZ of every position of one material signature, white
+/// having a king and `pieces` against a bare king.
+struct Solved {
+    pieces: Vec<u8>,
+    wdl: Vec<i8>,
+    dtz: Vec<u8>,
+}
+
+impl Solved {
+    fn index(&self, stm: usize, sq: &Squares) -> usize {
+        index(stm, sq, self.pieces.len() + 2)
+    }
+
+    /// The longest DTZ of a `result` with `stm` to move.
+    fn longest(&self, stm: usize, result: i8) -> u8 {
+        (stm..self.wdl.len())
+            .step_by(2)
+            .filter(|&i| self.wdl[i] == result)
+            .map(|i| self.dtz[i])
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+fn index(stm: usize, sq: &Squares, n: usize) -> usize {
+    sq[..n].iter().rev().fold(0, |i, &s| i * 64 + s) * 2 + stm
+}
+
+fn squares(idx: usize, n: usize) -> (usize, Squares) {
+    let mut sq = [0; 4];
+    let mut rest = idx / 2;
+    for s in sq.iter_mut().take(n) {
+        *s = rest % 64;
+        rest /= 64;
+    }
+    (idx % 2, sq)
+}
+
+struct Solver<'a> {
+    pieces: Vec<u8>,
+    n: usize,
+    solved: &'a HashMap<Vec<u8>, Solved>,
+}
+
+impl<'a> Solver<'a> {
+    fn new(pieces: &[u8], solved: &'a HashMap<Vec<u8>, Solved>) -> Self {
+        Solver {
+            pieces: pieces.to_vec(),
+            n: pieces.len() + 2,
+            solved,
+        }
+    }
+
+    fn occupied(&self, sq: &Squares) -> u64 {
+        sq[..self.n].iter().fold(0, |bits, &s| bits | bit(s))
+    }
+
+    /// The squares white attacks, leaving out the piece at `skip`.
+    fn white_attacks(&self, sq: &Squares, occupied: u64, skip: usize) -> u64 {
+        let mut bits = attacks(KING, sq[0], occupied);
+        for (i, (&piece, &s)) in self.pieces.iter().zip(&sq[2..]).enumerate() {
+            if i + 2 != skip {
+                bits |= attacks(piece, s, occupied);
+            }
+        }
+        bits
+    }
+
+    fn black_in_check(&self, sq: &Squares) -> bool {
+        self.white_attacks(sq, self.occupied(sq), 0) & bit(sq[1]) != 0
+    }
+
+    fn legal(&self, stm: usize, sq: &Squares) -> bool {
+        let occupied = self.occupied(sq);
+        occupied.count_ones() as usize == self.n
+            && distance(sq[0], sq[1]) > 1
+            && (2..self.n).all(|i| self.pieces[i - 2] != PAWN || (1..7).contains(&rank(sq[i])))
+            && (stm == 1 || !self.black_in_check(sq))
+    }
+
+    /// Black's result after taking the piece at `i` by moving to `to`.
+    fn capture(&self, sq: &Squares, i: usize, to: usize) -> i8 {
+        let mut rest: Vec<(u8, usize)> = (2..self.n)
+            .filter(|&j| j != i)
+            .map(|j| (self.pieces[j - 2], sq[j]))
+            .collect();
+        if rest.is_empty() {
+            return DRAW;
+        }
+        rest.sort_by_key(|&(piece, _)| Reverse(piece));
+        let table = &self.solved[&rest.iter().map(|&(piece, _)| piece).collect::<Vec<_>>()];
+        let mut child = [sq[0], to, 0, 0];
+        for (j, &(_, s)) in rest.iter().enumerate() {
+            child[j + 2] = s;
+        }
+        -table.wdl[table.index(0, &child)]
+    }
+
+    /// Black's result after a pawn on `from` promotes to `piece` on `to`.
+    fn promotion(&self, sq: &Squares, from: usize, to: usize, piece: u8) -> i8 {
+        let mut all: Vec<(u8, usize)> = (2..self.n)
+            .filter(|&j| sq[j] != from)
+            .map(|j| (self.pieces[j - 2], sq[j]))
+            .collect();
+        all.push((piece, to));
+        all.sort_by_key(|&(piece, _)| Reverse(piece));
+        let table = &self.solved[&all.iter().map(|&(piece, _)| piece).collect::<Vec<_>>()];
+        let mut child = [sq[0], sq[1], 0, 0];
+        for (j, &(_, s)) in all.iter().enumerate() {
+            child[j + 2] = s;
+        }
+        table.wdl[table.index(1, &child)]
+    }
+
+    /// Black's legal moves: the positions they lead to, and the results of
+    /// the captures.
+    fn black_moves(&self, sq: &Squares) -> (Vec<Squares>, Vec<i8>) {
+        let occupied = self.occupied(sq);
+        let (mut moves, mut captures) = (Vec::new(), Vec::new());
+        for (df, dr) in KING_STEPS {
+            let Some(to) = step(sq[1], df, dr) else {
+                continue;
+            };
+            if distance(to, sq[0]) <= 1 {
+                continue;
+            }
+            let without_king = occupied & !bit(sq[1]);
+            if let Some(i) = (2..self.n).find(|&i| sq[i] == to) {
+                if self.white_attacks(sq, without_king & !bit(to), i) & bit(to) == 0 {
+                    captures.push(self.capture(sq, i, to));
+                }
+            } else if self.white_attacks(sq, without_king, 0) & bit(to) == 0 {
+                let mut child = *sq;
+                child[1] = to;
+                moves.push(child);
+            }
+        }
+        (moves, captures)
+    }
+
+    /// White's legal moves, and whether each is a pawn move.
+    fn white_moves(&self, sq: &Squares) -> Vec<(WhiteChild, bool)> {
+        let occupied = self.occupied(sq);
+        let mut moves = Vec::new();
+        let to_same = |i: usize, to: usize, zeroing: bool, moves: &mut Vec<_>| {
+            let mut child = *sq;
+            child[i] = to;
+            moves.push((WhiteChild::Same(child), zeroing));
+        };
+        for to in bits(attacks(KING, sq[0], occupied) & !occupied) {
+            if distance(to, sq[1]) > 1 {
+                to_same(0, to, false, &mut moves);
+            }
+        }
+        for (i, &s) in sq.iter().enumerate().take(self.n).skip(2) {
+            match self.pieces[i - 2] {
+                PAWN => {
+                    let to = s + 8;
+                    if occupied & bit(to) != 0 {
+                        continue;
+                    }
+                    if rank(to) == 7 {
+                        for piece in [QUEEN, ROOK, BISHOP, KNIGHT] {
+                            let black = self.promotion(sq, s, to, piece);
+                            moves.push((WhiteChild::Other(black), true));
+                        }
+                        continue;
+                    }
+                    to_same(i, to, true, &mut moves);
+                    if rank(s) == 1 && occupied & bit(s + 16) == 0 {
+                        to_same(i, s + 16, true, &mut moves);
+                    }
+                }
+                piece => {
+                    for to in bits(attacks(piece, s, occupied) & !occupied) {
+                        to_same(i, to, false, &mut moves);
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    /// The positions with black to move whose black king move led to `sq`.
+    fn black_unmoves(&self, sq: &Squares) -> Vec<Squares> {
+        let occupied = self.occupied(sq);
+        bits(attacks(KING, sq[1], occupied) & !occupied)
+            .filter(|&from| distance(from, sq[0]) > 1)
+            .map(|from| {
+                let mut parent = *sq;
+                parent[1] = from;
+                parent
+            })
+            .collect()
+    }
+
+    /// The positions with white to move whose white move led to `sq`,
+    /// leaving out pawn moves unless `pawns`.
+    fn white_unmoves(&self, sq: &Squares, pawns: bool) -> Vec<Squares> {
+        let occupied = self.occupied(sq);
+        let mut parents = Vec::new();
+        for i in (0..self.n).filter(|&i| i != 1) {
+            let s = sq[i];
+            let piece = if i == 0 { KING } else { self.pieces[i - 2] };
+            let froms: Vec<usize> = match piece {
+                PAWN if !pawns => Vec::new(),
+                PAWN => {
+                    let mut froms = Vec::new();
+                    if rank(s) >= 2 && occupied & bit(s - 8) == 0 {
+                        froms.push(s - 8);
+                        if rank(s) == 3 && occupied & bit(s - 16) == 0 {
+                            froms.push(s - 16);
+                        }
+                    }
+                    froms
+                }
+                KING => bits(attacks(KING, s, occupied) & !occupied)
+                    .filter(|&from| distance(from, sq[1]) > 1)
+                    .collect(),
+                piece => bits(attacks(piece, s, occupied) & !occupied).collect(),
+            };
+            for from in froms {
+                let mut parent = *sq;
+                parent[i] = from;
+                if !self.black_in_check(&parent) {
+                    parents.push(parent);
+                }
+            }
+        }
+        parents
+    }
+
+    fn solve(&self) -> Solved {
+        let size = 2 << (6 * self.n);
+        let idx = |stm: usize, sq: &Squares| index(stm, sq, self.n);
+        let mut wdl = vec![UNKNOWN; size];
+        let mut moves_left = vec![0u8; size];
+        let mut queue = VecDeque::new();
+
+        // First the results: mates, stalemates, captures and promotions
+        // decide some positions outright, and the rest follow back from
+        // those.
+        for i in 0..size {
+            let (stm, sq) = squares(i, self.n);
+            if !self.legal(stm, &sq) {
+                wdl[i] = ILLEGAL;
+            } else if stm == 1 {
+                let (moves, captures) = self.black_moves(&sq);
+                let best = captures.iter().copied().max().unwrap_or(LOSS);
+                if moves.is_empty() && captures.is_empty() {
+                    wdl[i] = if self.black_in_check(&sq) { LOSS } else { DRAW };
+                } else if best > LOSS {
+                    wdl[i] = best;
+                } else if moves.is_empty() {
+                    wdl[i] = LOSS;
+                } else {
+                    moves_left[i] = moves.len() as u8;
+                }
+            } else {
+                let moves = self.white_moves(&sq);
+                if moves.is_empty() {
+                    wdl[i] = DRAW;
+                } else if moves
+                    .iter()
+                    .any(|(child, _)| matches!(child, WhiteChild::Other(LOSS)))
+                {
+                    wdl[i] = WIN;
+                }
+            }
+            if wdl[i] == LOSS || wdl[i] == WIN {
+                queue.push_back(i);
+            }
+        }
+        while let Some(i) = queue.pop_front() {
+            let (stm, sq) = squares(i, self.n);
+            if stm == 1 {
+                for parent in self.white_unmoves(&sq, true) {
+                    let j = idx(0, &parent);
+                    if wdl[j] == UNKNOWN {
+                        wdl[j] = WIN;
+                        queue.push_back(j);
+                    }
+                }
+            } else {
+                for parent in self.black_unmoves(&sq) {
+                    let j = idx(1, &parent);
+                    if wdl[j] == UNKNOWN {
+                        moves_left[j] -= 1;
+                        if moves_left[j] == 0 {
+                            wdl[j] = LOSS;
+                            queue.push_back(j);
+                        }
+                    }
+                }
+            }
+        }
+        for value in wdl.iter_mut().filter(|value| **value == UNKNOWN) {
+            *value = DRAW;
+        }
+
+        // Then the distances to zeroing, in plies, nearest first: a pawn move
+        // or mate is one ply from it, and the loser puts it off as long as
+        // it can.
+        let mut dtz = vec![0u8; size];
+        for i in 0..size {
+            let (stm, sq) = squares(i, self.n);
+            if stm == 1 && wdl[i] == LOSS {
+                let (moves, _) = self.black_moves(&sq);
+                if moves.is_empty() {
+                    dtz[i] = 1;
+                } else {
+                    moves_left[i] = moves.len() as u8;
+                }
+            } else if stm == 0 && wdl[i] == WIN {
+                let zeroes = self
+                    .white_moves(&sq)
+                    .iter()
+                    .any(|(child, zeroing)| match child {
+                        WhiteChild::Same(child) => {
+                            wdl[idx(1, child)] == LOSS && (*zeroing || mated(self, child))
+                        }
+                        WhiteChild::Other(black) => *black == LOSS,
+                    });
+                if zeroes {
+                    dtz[i] = 1;
+                }
+            }
+            if dtz[i] == 1 {
+                queue.push_back(i);
+            }
+        }
+        while let Some(i) = queue.pop_front() {
+            let (stm, sq) = squares(i, self.n);
+            let next = dtz[i] + 1;
+            if stm == 1 {
+                for parent in self.white_unmoves(&sq, false) {
+                    let j = idx(0, &parent);
+                    if wdl[j] == WIN && dtz[j] == 0 {
+                        dtz[j] = next;
+                        queue.push_back(j);
+                    }
+                }
+            } else {
+                for parent in self.black_unmoves(&sq) {
+                    let j = idx(1, &parent);
+                    if wdl[j] == LOSS && dtz[j] == 0 {
+                        moves_left[j] -= 1;
+                        if moves_left[j] == 0 {
+                            dtz[j] = next;
+                            queue.push_back(j);
+                        }
+                    }
+                }
+            }
+        }
+        for i in 0..size {
+            if wdl[i] == WIN || wdl[i] == LOSS {
+                // Past 100 plies the fifty-move rule would spoil the result.
+                assert!((1..=100).contains(&dtz[i]), "{:?}", squares(i, self.n));
+            }
+        }
+
+        Solved {
+            pieces: self.pieces.clone(),
+            wdl,
+            dtz,
+        }
+    }
+}
+
+/// Whether black, to move in `sq`, is mated.
+fn mated(solver: &Solver, sq: &Squares) -> bool {
+    let (moves, captures) = solver.black_moves(sq);
+    moves.is_empty() && captures.is_empty() && solver.black_in_check(sq)
+}
+
+fn bits(mut bits: u64) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        (bits != 0).then(|| {
+            let s = bits.trailing_zeros() as usize;
+            bits &= bits - 1;
+            s
+        })
+    })
+}
+
+/// The tables positions are indexed with, as the original probing code
+/// builds them.
+struct Encoding {
+    map_a1d1d4: [usize; 64],
+    map_b1h1h7: [usize; 64],
+    map_kk: [[usize; 64]; 10],
+    binomial: [[u64; 64]; 7],
+    map_pawns: [usize; 64],
+    lead_pawn_idx: [u64; 64],
+    lead_pawns_size: [u64; 4],
+}
+
+/// How far a square is above the a1-h8 diagonal.
+fn off_diagonal(s: usize) -> i32 {
+    rank(s) as i32 - file(s) as i32
+}
+
+impl Encoding {
+    fn new() -> Self {
+        let mut e = Encoding {
+            map_a1d1d4: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 7],
+            map_pawns: [0; 64],
+            lead_pawn_idx: [0; 64],
+            lead_pawns_size: [0; 4],
+        };
+        let below: Vec<usize> = (0..64).filter(|&s| off_diagonal(s) < 0).collect();
+        for (code, &s) in below.iter().enumerate() {
+            e.map_b1h1h7[s] = code;
+        }
+
+        // The triangle b1-d1-d3 first, then its side on the diagonal.
+        let triangle: Vec<usize> = (0..28)
+            .filter(|&s| file(s) <= 3 && off_diagonal(s) < 0)
+            .chain((0..28).filter(|&s| file(s) <= 3 && off_diagonal(s) == 0))
+            .collect();
+        for (code, &s) in triangle.iter().enumerate() {
+            e.map_a1d1d4[s] = code;
+        }
+
+        let mut code = 0;
+        let mut both_on_diagonal = Vec::new();
+        for (t, &s1) in triangle.iter().enumerate() {
+            for s2 in 0..64 {
+                if distance(s1, s2) <= 1 || (off_diagonal(s1) == 0 && off_diagonal(s2) > 0) {
+                    continue;
+                }
+                if off_diagonal(s1) == 0 && off_diagonal(s2) == 0 {
+                    both_on_diagonal.push((t, s2));
+                } else {
+                    e.map_kk[t][s2] = code;
+                    code += 1;
+                }
+            }
+        }
+        for (t, s2) in both_on_diagonal {
+            e.map_kk[t][s2] = code;
+            code += 1;
+        }
+        assert_eq!(code, 462);
+
+        for n in 0..64 {
+            e.binomial[0][n] = 1;
+            for k in 1..7 {
+                e.binomial[k][n] = if n == 0 {
+                    0
+                } else {
+                    e.binomial[k - 1][n - 1] + e.binomial[k][n - 1]
+                };
+            }
+        }
+
+        // A lead pawn nearer the edge, then lower, numbers higher; only
+        // single lead pawns are needed here.
+        let mut available = 47;
+        for f in 0..4 {
+            let mut idx = 0;
+            for r in 1..7 {
+                let sq = r * 8 + f;
+                e.map_pawns[sq] = available;
+                e.map_pawns[sq ^ 7] = available - 1;
+                available -= 2;
+                e.lead_pawn_idx[sq] = idx;
+                idx += 1;
+            }
+            e.lead_pawns_size[f] = idx;
+        }
+        e
+    }
+}
+
+/// A table to write, white having a king and `white` against a bare king.
+struct Spec {
+    name: &'static str,
+    white: Vec<u8>,
+    /// The pieces in the order they're numbered in, with each side to move.
+    sides: [Vec<u8>; 2],
+    /// The DTZ table's flags, which also pick the side to move it holds.
+    dtz_flags: u8,
+}
+
+/// How one side's positions are numbered: the sizes of the groups the
+/// pieces are numbered in, and what each group's number is multiplied by,
+/// for each file the lead pawn can be on.
+struct Groups {
+    len: Vec<usize>,
+    idx: Vec<Vec<u64>>,
+}
+
+impl Spec {
+    fn has_pawns(&self) -> bool {
+        self.white.contains(&PAWN)
+    }
+
+    fn has_unique_pieces(&self) -> bool {
+        self.white
+            .iter()
+            .any(|p| self.white.iter().filter(|q| *q == p).count() == 1)
+    }
+
+    fn files(&self) -> usize {
+        if self.has_pawns() {
+            4
+        } else {
+            1
+        }
+    }
+
+    /// Groups the pieces as the original code does, each group numbered in
+    /// turn, the leading one first.
+    fn groups(&self, e: &Encoding, pieces: &[u8]) -> Groups {
+        let mut len = vec![1];
+        let mut first_len: i32 = if self.has_pawns() {
+            0
+        } else if self.has_unique_pieces() {
+            3
+        } else {
+            2
+        };
+        for i in 1..pieces.len() {
+            first_len -= 1;
+            if first_len > 0 || pieces[i] == pieces[i - 1] {
+                *len.last_mut().unwrap() += 1;
+            } else {
+                len.push(1);
+            }
+        }
+        let idx = (0..self.files())
+            .map(|f| {
+                let mut idx = vec![1];
+                let mut size = if self.has_pawns() {
+                    e.lead_pawns_size[f]
+                } else if self.has_unique_pieces() {
+                    31332
+                } else {
+                    462
+                };
+                let mut free = 64 - len[0];
+                for &n in &len[1..] {
+                    idx.push(size);
+                    size *= e.binomial[n][free];
+                    free -= n;
+                }
+                idx.push(size);
+                idx
+            })
+            .collect();
+        Groups { len, idx }
+    }
+
+    /// The file of the lead pawn and the index of the position with
+    /// `placed`, its pieces and their squares, numbered in the order of
+    /// `pieces`.
+    fn encode(
+        &self,
+        e: &Encoding,
+        pieces: &[u8],
+        groups: &Groups,
+        placed: &[(u8, usize)],
+    ) -> (usize, u64) {
+        let mut squares = Vec::new();
+        let mut used = vec![false; placed.len()];
+        for &piece in pieces {
+            let i = (0..placed.len())
+                .find(|&i| !used[i] && placed[i].0 == piece)
+                .unwrap();
+            used[i] = true;
+            squares.push(placed[i].1);
+        }
+
+        let f = if self.has_pawns() {
+            file(squares[0]).min(7 - file(squares[0]))
+        } else {
+            0
+        };
+        if file(squares[0]) > 3 {
+            squares.iter_mut().for_each(|s| *s ^= 7);
+        }
+        let mut idx = if self.has_pawns() {
+            e.lead_pawn_idx[squares[0]]
+        } else {
+            if rank(squares[0]) > 3 {
+                squares.iter_mut().for_each(|s| *s ^= 56);
+            }
+            if let Some(i) = (0..groups.len[0]).find(|&i| off_diagonal(squares[i]) != 0) {
+                if off_diagonal(squares[i]) > 0 {
+                    squares[i..]
+                        .iter_mut()
+                        .for_each(|s| *s = ((*s >> 3) | (*s << 3)) & 63);
+                }
+            }
+            let (s0, s1) = (squares[0], squares[1]);
+            if self.has_unique_pieces() {
+                let s2 = squares[2];
+                let adjust1 = (s1 > s0) as usize;
+                let adjust2 = (s2 > s0) as usize + (s2 > s1) as usize;
+                (if off_diagonal(s0) != 0 {
+                    (e.map_a1d1d4[s0] * 63 + s1 - adjust1) * 62 + s2 - adjust2
+                } else if off_diagonal(s1) != 0 {
+                    (6 * 63 + rank(s0) * 28 + e.map_b1h1h7[s1]) * 62 + s2 - adjust2
+                } else if off_diagonal(s2) != 0 {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank(s0) * 7 * 28
+                        + (rank(s1) - adjust1) * 28
+                        + e.map_b1h1h7[s2]
+                } else {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + rank(s0) * 7 * 6
+                        + (rank(s1) - adjust1) * 6
+                        + rank(s2)
+                        - adjust2
+                }) as u64
+            } else {
+                e.map_kk[e.map_a1d1d4[s0]][s1] as u64
+            }
+        };
+
+        idx *= groups.idx[f][0];
+        let mut start = groups.len[0];
+        for (g, &len) in groups.len.iter().enumerate().skip(1) {
+            squares[start..start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let s = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&t| s > t).count();
+                n += e.binomial[i + 1][s - adjust];
+            }
+            idx += n * groups.idx[f][g];
+            start += len;
+        }
+        (f, idx)
+    }
+
+    /// The bytes of the WDL or DTZ file.
+    fn write(&self, e: &Encoding, table: &Solved, dtz: bool) -> Vec<u8> {
+        let stored = (self.dtz_flags & STM) as usize;
+        let sides: Vec<usize> = if dtz { vec![stored] } else { vec![0, 1] };
+        let groups: Vec<Groups> = sides
+            .iter()
+            .map(|&s| self.groups(e, &self.sides[s]))
+            .collect();
+
+        // Every value, by side and file, with `None` for illegal positions
+        // and, in DTZ tables, draws.
+        let mut values: Vec<Vec<Vec<Option<u16>>>> = groups
+            .iter()
+            .map(|g| {
+                g.idx
+                    .iter()
+                    .map(|idx| vec![None; *idx.last().unwrap() as usize])
+                    .collect()
+            })
+            .collect();
+        let n = self.white.len() + 2;
+        for i in 0..table.wdl.len() {
+            let (stm, sq) = squares(i, n);
+            let wdl = table.wdl[i];
+            let Some(side) = sides.iter().position(|&s| s == stm) else {
+                continue;
+            };
+            if wdl == ILLEGAL || (dtz && wdl == DRAW) {
+                continue;
+            }
+            let mut placed = vec![(KING, sq[0]), (KING | BLACK, sq[1])];
+            placed.extend((2..n).map(|j| (self.white[j - 2], sq[j])));
+            let (f, idx) = self.encode(e, &self.sides[stm], &groups[side], &placed);
+            let value = if dtz {
+                let plies = if wdl == WIN { WIN_PLIES } else { LOSS_PLIES };
+                let d = table.dtz[i] as u16;
+                // Probers add one back, and double first when moves are
+                // stored, so an even (losing) distance reads one short.
+                if self.dtz_flags & plies != 0 {
+                    d - 1
+                } else {
+                    (d - 1) / 2
+                }
+            } else {
+                (wdl + 2) as u16
+            };
+            let slot = &mut values[side][f][idx as usize];
+            // Mirror images share a slot, and must agree.
+            assert!(
+                slot.is_none() || *slot == Some(value),
+                "{} {:?}",
+                self.name,
+                sq
+            );
+            *slot = Some(value);
+        }
+
+        // DTZ values may go through a map for each result, so that the
+        // table stores small numbers. White can't lose, so the side stored
+        // only ever wins or only ever loses.
+        let mut maps = Vec::new();
+        if dtz {
+            for file_values in values[0].iter_mut() {
+                let mut list: Vec<u16> = file_values.iter().flatten().copied().collect();
+                list.sort_unstable();
+                list.dedup();
+                if self.dtz_flags & MAPPED != 0 {
+                    for v in file_values.iter_mut().flatten() {
+                        *v = list.binary_search(v).unwrap() as u16;
+                    }
+                }
+                let mut map = [Vec::new(), Vec::new(), Vec::new(), Vec::new()];
+                map[stored] = list;
+                maps.push(map);
+            }
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend(if dtz { DTZ_MAGIC } else { WDL_MAGIC });
+        bytes.push(1 | (self.has_pawns() as u8) << 1);
+        for _ in 0..self.files() {
+            // The leading group is numbered first on both sides.
+            bytes.push(0);
+            let low = &self.sides[sides[0]];
+            let high = &self.sides[*sides.last().unwrap()];
+            bytes.extend(low.iter().zip(high).map(|(l, h)| l | h << 4));
+        }
+        bytes.resize(bytes.len() + (bytes.len() & 1), 0);
+
+        let flags = if dtz { self.dtz_flags } else { 0 };
+        let compressed: Vec<Vec<Compressed>> = values
+            .iter()
+            .map(|files| files.iter().map(|v| compress(&fill(v), flags)).collect())
+            .collect();
+        for f in 0..self.files() {
+            for side in &compressed {
+                bytes.extend(&side[f].header);
+            }
+        }
+        if dtz {
+            for map in maps.iter().filter(|_| self.dtz_flags & MAPPED != 0) {
+                if self.dtz_flags & WIDE != 0 {
+                    bytes.resize(bytes.len() + (bytes.len() & 1), 0);
+                    for list in map {
+                        bytes.extend((list.len() as u16).to_le_bytes());
+                        for &v in list {
+                            bytes.extend(v.to_le_bytes());
+                        }
+                    }
+                } else {
+                    for list in map {
+                        bytes.push(list.len() as u8);
+                        bytes.extend(list.iter().map(|&v| u8::try_from(v).unwrap()));
+                    }
+                }
+            }
+            bytes.resize(bytes.len() + (bytes.len() & 1), 0);
+        }
+        for f in 0..self.files() {
+            for side in &compressed {
+                bytes.extend(&side[f].sparse_index);
+            }
+        }
+        for f in 0..self.files() {
+            for side in &compressed {
+                bytes.extend(&side[f].block_lengths);
+            }
+        }
+        for f in 0..self.files() {
+            for side in &compressed {
+                bytes.resize((bytes.len() + 0x3F) & !0x3F, 0);
+                bytes.extend(&side[f].data);
+            }
+        }
+        bytes
+    }
+}
+
+/// Gives positions whose values don't matter the value before them, so
+/// they compress away.
+fn fill(values: &[Option<u16>]) -> Vec<u16> {
+    let mut last = values.iter().flatten().next().copied().unwrap_or(0);
+    values
+        .iter()
+        .map(|v| {
+            last = v.unwrap_or(last);
+            last
+        })
+        .collect()
+}
+
+/// One side's (or file's) values, compressed: the parameters read before
+/// the DTZ maps, and the sparse index, block lengths and blocks.
+struct Compressed {
+    header: Vec<u8>,
+    sparse_index: Vec<u8>,
+    block_lengths: Vec<u8>,
+    data: Vec<u8>,
+}
+
+fn compress(values: &[u16], flags: u8) -> Compressed {
+    if values.iter().all(|&v| v == values[0]) {
+        return Compressed {
+            header: vec![flags | SINGLE_VALUE, values[0] as u8],
+            sparse_index: Vec::new(),
+            block_lengths: Vec::new(),
+            data: Vec::new(),
+        };
+    }
+
+    // Replace the most common pair of neighbouring symbols by a new one,
+    // for as long as that pays, keeping each to at most 256 values.
+    let leaves = *values.iter().max().unwrap() as usize + 1;
+    let mut length: Vec<usize> = vec![1; leaves];
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+    let mut seq: Vec<usize> = values.iter().map(|&v| v as usize).collect();
+    while leaves + pairs.len() < 4000 {
+        let mut counts: HashMap<(usize, usize), usize> = HashMap::new();
+        for w in seq.windows(2) {
+            if length[w[0]] + length[w[1]] <= 256 {
+                *counts.entry((w[0], w[1])).or_default() += 1;
+            }
+        }
+        let Some((&pair, &count)) = counts
+            .iter()
+            .max_by_key(|(&pair, &count)| (count, Reverse(pair)))
+        else {
+            break;
+        };
+        if count < 8 {
+            break;
+        }
+        let sym = leaves + pairs.len();
+        pairs.push(pair);
+        length.push(length[pair.0] + length[pair.1]);
+        let mut next = Vec::with_capacity(seq.len());
+        let mut i = 0;
+        while i < seq.len() {
+            if i + 1 < seq.len() && (seq[i], seq[i + 1]) == pair {
+                next.push(sym);
+                i += 2;
+            } else {
+                next.push(seq[i]);
+                i += 1;
+            }
+        }
+        seq = next;
+    }
+    let symbols = leaves + pairs.len();
+
+    // Huffman code lengths for the symbols left in the sequence.
+    let mut freq = vec![0u64; symbols];
+    for &s in &seq {
+        freq[s] += 1;
+    }
+    let mut code_len = vec![0usize; symbols];
+    let used: Vec<usize> = (0..symbols).filter(|&s| freq[s] > 0).collect();
+    if used.len() == 1 {
+        code_len[used[0]] = 1;
+    } else {
+        let mut parent = vec![usize::MAX; symbols];
+        let mut heap: BinaryHeap<Reverse<(u64, usize)>> =
+            used.iter().map(|&s| Reverse((freq[s], s))).collect();
+        while heap.len() > 1 {
+            let Reverse((a, x)) = heap.pop().unwrap();
+            let Reverse((b, y)) = heap.pop().unwrap();
+            let node = parent.len();
+            parent.push(usize::MAX);
+            parent[x] = node;
+            parent[y] = node;
+            heap.push(Reverse((a + b, node)));
+        }
+        for &s in &used {
+            let mut n = s;
+            while parent[n] != usize::MAX {
+                n = parent[n];
+                code_len[s] += 1;
+            }
+        }
+    }
+    let max_len = *code_len.iter().max().unwrap();
+    let min_len = used.iter().map(|&s| code_len[s]).min().unwrap();
+    assert!(max_len <= 32);
+
+    // Number the coded symbols longest code first, then the ones only
+    // pairs use.
+    let mut order = used.clone();
+    order.sort_by_key(|&s| (Reverse(code_len[s]), s));
+    let mut reachable = vec![false; symbols];
+    let mut stack = used.clone();
+    while let Some(s) = stack.pop() {
+        if !reachable[s] {
+            reachable[s] = true;
+            if s >= leaves {
+                stack.extend([pairs[s - leaves].0, pairs[s - leaves].1]);
+            }
+        }
+    }
+    order.extend((0..symbols).filter(|&s| reachable[s] && freq[s] == 0));
+    assert!(order.len() < 0xFFF);
+    let mut number = vec![usize::MAX; symbols];
+    for (k, &s) in order.iter().enumerate() {
+        number[s] = k;
+    }
+
+    // The canonical code: longer codes are numerically lower.
+    let mut lowest = vec![0usize; max_len + 1];
+    let mut base = vec![0u64; max_len + 1];
+    let count = |len: usize| used.iter().filter(|&&s| code_len[s] == len).count();
+    for len in (min_len..max_len).rev() {
+        lowest[len] = lowest[len + 1] + count(len + 1);
+        base[len] = (base[len + 1] + count(len + 1) as u64) / 2;
+    }
+    let code = |s: usize| base[code_len[s]] + (number[s] - lowest[code_len[s]]) as u64;
+
+    // Pack the codes into blocks, each holding whole symbols.
+    let block_size = 1usize << BLOCK_LOG2;
+    let mut data = Vec::new();
+    let mut block_values = Vec::new();
+    let (mut bits, mut values_in_block) = (Vec::<bool>::new(), 0);
+    let mut flush = |bits: &mut Vec<bool>, values_in_block: &mut usize| {
+        let mut block = vec![0u8; block_size];
+        for (i, &b) in bits.iter().enumerate() {
+            block[i / 8] |= (b as u8) << (7 - i % 8);
+        }
+        data.extend(block);
+        block_values.push(*values_in_block);
+        bits.clear();
+        *values_in_block = 0;
+    };
+    for &s in &seq {
+        if bits.len() + code_len[s] > 8 * block_size || values_in_block + length[s] > 65536 {
+            flush(&mut bits, &mut values_in_block);
+        }
+        let c = code(s);
+        bits.extend((0..code_len[s]).rev().map(|i| c >> i & 1 != 0));
+        values_in_block += length[s];
+    }
+    flush(&mut bits, &mut values_in_block);
+
+    // The sparse index gives the block and offset of the middle of each
+    // span; past the end, an offset from the end of the last block.
+    let span = 1usize << SPAN_LOG2;
+    let mut starts = vec![0];
+    for &n in &block_values {
+        starts.push(starts.last().unwrap() + n);
+    }
+    let total = values.len();
+    let mut padding = 0;
+    let mut sparse_index = Vec::new();
+    for k in 0..total.div_ceil(span) {
+        let mid = k * span + span / 2;
+        let (block, offset) = if mid < total {
+            let block = starts.partition_point(|&start| start <= mid) - 1;
+            (block, mid - starts[block])
+        } else {
+            padding = 1;
+            (block_values.len(), mid - total)
+        };
+        sparse_index.extend((block as u32).to_le_bytes());
+        sparse_index.extend((offset as u16).to_le_bytes());
+    }
+    let mut block_lengths = Vec::new();
+    for &n in &block_values {
+        block_lengths.extend(((n - 1) as u16).to_le_bytes());
+    }
+    block_lengths.extend(vec![0; 2 * padding]);
+
+    let mut header = vec![flags, BLOCK_LOG2, SPAN_LOG2, padding as u8];
+    header.extend((block_values.len() as u32).to_le_bytes());
+    header.extend([max_len as u8, min_len as u8]);
+    for &lowest in &lowest[min_len..] {
+        header.extend((lowest as u16).to_le_bytes());
+    }
+    header.extend((order.len() as u16).to_le_bytes());
+    for &s in &order {
+        let (left, right) = if s < leaves {
+            (s, 0xFFF)
+        } else {
+            (number[pairs[s - leaves].0], number[pairs[s - leaves].1])
+        };
+        header.extend([
+            left as u8,
+            (left >> 8) as u8 | (right << 4) as u8,
+            (right >> 4) as u8,
+        ]);
+    }
+    if order.len() % 2 == 1 {
+        header.push(0);
+    }
+    Compressed {
+        header,
+        sparse_index,
+        block_lengths,
+        data,
+    }
+}
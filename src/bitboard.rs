@@ -1,3 +1,5 @@
+use std::fmt;
+
 use crate::types::{Color, PieceType};
 
 #[derive(Copy, Clone, Debug)]
@@ -44,25 +46,22 @@ impl Default for BitBoard {
 }
 
 impl BitBoard {
-    /// Gets the total piece weights for a given side.
-    pub fn piece_mass(&self, color: Color) -> i32 {
-        match color {
-            Color::Black => {
-                self.king_black.count_ones() as i32 * PieceType::King as i32
-                    + self.queens_black.count_ones() as i32 * PieceType::Queen as i32
-                    + self.rooks_black.count_ones() as i32 * PieceType::Rook as i32
-                    + self.bishops_black.count_ones() as i32 * PieceType::Bishop as i32
-                    + self.knights_black.count_ones() as i32 * PieceType::Knight as i32
-                    + self.pawns_black.count_ones() as i32 * PieceType::Pawn as i32
-            }
-            Color::White => {
-                self.king_white.count_ones() as i32 * PieceType::King as i32
-                    + self.queens_white.count_ones() as i32 * PieceType::Queen as i32
-                    + self.rooks_white.count_ones() as i32 * PieceType::Rook as i32
-                    + self.bishops_white.count_ones() as i32 * PieceType::Bishop as i32
-                    + self.knights_white.count_ones() as i32 * PieceType::Knight as i32
-                    + self.pawns_white.count_ones() as i32 * PieceType::Pawn as i32
-            }
+    /// Mirrors the board top to bottom and swaps the colors of every piece,
+    /// so that white's pieces on the first rank become black's on the eighth.
+    pub fn flipped(&self) -> Self {
+        BitBoard {
+            pawns_white: self.pawns_black.swap_bytes(),
+            rooks_white: self.rooks_black.swap_bytes(),
+            knights_white: self.knights_black.swap_bytes(),
+            bishops_white: self.bishops_black.swap_bytes(),
+            queens_white: self.queens_black.swap_bytes(),
+            king_white: self.king_black.swap_bytes(),
+            pawns_black: self.pawns_white.swap_bytes(),
+            rooks_black: self.rooks_white.swap_bytes(),
+            knights_black: self.knights_white.swap_bytes(),
+            bishops_black: self.bishops_white.swap_bytes(),
+            queens_black: self.queens_white.swap_bytes(),
+            king_black: self.king_white.swap_bytes(),
         }
     }
 
@@ -112,7 +111,7 @@ impl BitBoard {
 
     /// A utility method generating a FEN string representation of this `BitBoard`
     /// * `returns` - a `String` representing the board token of a string in FEN
-    pub fn to_fen_string(&self) -> String {
+    pub fn to_fen_string(self) -> String {
         let mut s = String::new();
         let board = self.to_board();
 
@@ -152,23 +151,9 @@ impl BitBoard {
         s
     }
 
-    /// **Debugging** A utility method generating a `String` representation of this `BitBoard`
-    /// * `returns` - a `String` representing the board
-    pub fn to_string(&self) -> String {
-        let mut s = String::new();
-        let board = self.to_board();
-
-        for row in board {
-            s.push_str(&String::from_iter(row.iter()));
-            s.push('\n');
-        }
-
-        s
-    }
-
     /// A utility method creating a 2D `char` array representation of this `BitBoard`
     /// * `returns` - a `[[char; 8]; 8]` 2D array representing the board
-    fn to_board(&self) -> [[char; 8]; 8] {
+    fn to_board(self) -> [[char; 8]; 8] {
         let mut board = [['.'; 8]; 8];
         let bitboards = [
             (self.pawns_white, 'P'),
@@ -226,3 +211,13 @@ impl BitBoard {
         }
     }
 }
+
+/// **Debugging** Draws the board one rank per line, white pieces in capitals.
+impl fmt::Display for BitBoard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.to_board() {
+            writeln!(f, "{}", String::from_iter(row))?;
+        }
+        Ok(())
+    }
+}
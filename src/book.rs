@@ -0,0 +1,259 @@
+//! Polyglot opening books: files of moves to play in positions, looked up by
+//! the positions' Polyglot Zobrist keys.
+//!
+//! A book is a list of 16-byte entries sorted by key, each holding a key, a
+//! move, a weight and four bytes for learning we don't use, all big-endian.
+//! A move packs the destination file and rank into bits 0 to 5, the origin's
+//! into bits 6 to 11, and the promotion piece, from 1 for a knight to 4 for a
+//! queen, into bits 12 to 14. Castling is written as the king taking its rook.
+
+use std::{
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    gamemanager::GameManager,
+    movetable::MoveTable,
+    types::{MoveType, PieceType, Square},
+};
+
+/// The length of one entry in a book file.
+const ENTRY_SIZE: usize = 16;
+
+/// One move for one position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub mv: u16,
+    /// How often the move should be played, relative to the others for
+    /// the same position.
+    pub weight: u16,
+    pub learn: u32,
+}
+
+/// A book's entries, sorted by key.
+#[derive(Default)]
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    /// Makes a book of `entries`, in any order.
+    pub fn new(mut entries: Vec<BookEntry>) -> Self {
+        entries.sort_by_key(|entry| (entry.key, u16::MAX - entry.weight, entry.mv));
+        Self { entries }
+    }
+
+    /// Reads a book file.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|err| format!("{path}: {err}"))?;
+        Self::from_bytes(&bytes).map_err(|err| format!("{path}: {err}"))
+    }
+
+    /// Writes the book to a file.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_bytes()).map_err(|err| format!("{path}: {err}"))
+    }
+
+    /// Reads a book in the format described at the top of this module.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(format!("not a book: {} bytes long", bytes.len()));
+        }
+        let entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|chunk| BookEntry {
+                key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+                mv: u16::from_be_bytes([chunk[8], chunk[9]]),
+                weight: u16::from_be_bytes([chunk[10], chunk[11]]),
+                learn: u32::from_be_bytes(chunk[12..16].try_into().unwrap()),
+            })
+            .collect();
+        Ok(Self::new(entries))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.entries.len() * ENTRY_SIZE);
+        for entry in &self.entries {
+            bytes.extend(entry.key.to_be_bytes());
+            bytes.extend(entry.mv.to_be_bytes());
+            bytes.extend(entry.weight.to_be_bytes());
+            bytes.extend(entry.learn.to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The entries for the position with `key`, heaviest first.
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+
+    /// Picks a move to play in `gm`: the heaviest if `best`, and otherwise
+    /// one at random, in proportion to the weights, using `random`. Entries
+    /// whose moves aren't legal, as in books written for other variants, are
+    /// passed over, and so are those of weight zero unless they're all zero.
+    pub fn choose(
+        &self,
+        gm: &GameManager,
+        tbl: &MoveTable,
+        best: bool,
+        random: u64,
+    ) -> Option<(Square, Square, MoveType)> {
+        let moves = gm.legal_moves(tbl);
+        let candidates: Vec<(u64, (Square, Square, MoveType))> = self
+            .entries(gm.zobrist_key())
+            .iter()
+            .filter_map(|entry| {
+                let mv = moves
+                    .iter()
+                    .find(|mv| encode_move(&mv.0, mv.1, mv.2, mv.3) == entry.mv & 0x7FFF)?;
+                Some((entry.weight as u64, (mv.1, mv.2, mv.3)))
+            })
+            .collect();
+
+        let total: u64 = candidates.iter().map(|(weight, _)| weight).sum();
+        if best || total == 0 {
+            return candidates.first().map(|(_, mv)| *mv);
+        }
+        let mut pick = random % total;
+        for (weight, mv) in candidates {
+            if pick < weight {
+                return Some(mv);
+            }
+            pick -= weight;
+        }
+        None
+    }
+}
+
+/// Packs a move as Polyglot does.
+pub fn encode_move(piece: &PieceType, from: Square, to: Square, move_type: MoveType) -> u16 {
+    // Polyglot counts files from a and ranks from 1; our bit 0 is h1.
+    let coordinates = |square: Square| {
+        let bit = square.to_u64().trailing_zeros() as u16;
+        (7 - bit % 8, bit / 8)
+    };
+    let (from_file, from_rank) = coordinates(from);
+    let (mut to_file, to_rank) = coordinates(to);
+    match move_type {
+        MoveType::KingCastle if *piece == PieceType::King => to_file = 7,
+        MoveType::QueenCastle if *piece == PieceType::King => to_file = 0,
+        _ => {}
+    }
+    let promotion = match move_type {
+        MoveType::NPromotion | MoveType::NPromoCapture => 1,
+        MoveType::BPromotion | MoveType::BPromoCapture => 2,
+        MoveType::RPromotion | MoveType::RPromoCapture => 3,
+        MoveType::QPromotion | MoveType::QPromoCapture => 4,
+        _ => 0,
+    };
+    to_file | to_rank << 3 | from_file << 6 | from_rank << 9 | promotion << 12
+}
+
+/// A number that's different every time, for picking book moves at random.
+pub fn random() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |t| t.as_nanos() as u64);
+    // SplitMix64, to spread the clock's low bits across the whole number.
+    let mut z = nanos.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movetable::MOVE_TABLE;
+
+    /// Packs a move given as in UCI, without promotion.
+    fn polyglot(uci: &str) -> u16 {
+        let b = uci.as_bytes();
+        let file = |i: usize| (b[i] - b'a') as u16;
+        let rank = |i: usize| (b[i] - b'1') as u16;
+        file(2) | rank(3) << 3 | file(0) << 6 | rank(1) << 9
+    }
+
+    fn entry(fen: &str, uci: &str, weight: u16) -> BookEntry {
+        BookEntry {
+            key: GameManager::from_fen_str(fen).zobrist_key(),
+            mv: polyglot(uci),
+            weight,
+            learn: 0,
+        }
+    }
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn reads_and_writes_books() {
+        let book = Book::new(vec![
+            entry(START, "d2d4", 1),
+            entry(START, "e2e4", 3),
+            entry(START, "e2e5", 5),
+        ]);
+        let read = Book::from_bytes(&book.to_bytes()).unwrap();
+        assert_eq!(read.entries, book.entries);
+        assert_eq!(read.entries(GameManager::default().zobrist_key()).len(), 3);
+        assert!(read.entries(0).is_empty());
+        assert!(Book::from_bytes(&[0; 17]).is_err());
+        assert!(Book::load("/nonexistent.bin").is_err());
+    }
+
+    #[test]
+    fn chooses_by_weight() {
+        let book = Book::new(vec![
+            entry(START, "d2d4", 1),
+            entry(START, "e2e4", 3),
+            entry(START, "e2e5", 5), // Not legal; passed over.
+        ]);
+        let gm = GameManager::default();
+        let choose = |best, random| {
+            let (from, to, _) = book.choose(&gm, &MOVE_TABLE, best, random).unwrap();
+            (from, to)
+        };
+        assert_eq!(choose(true, 3), (Square::E2, Square::E4));
+        assert_eq!(choose(false, 0), (Square::E2, Square::E4));
+        assert_eq!(choose(false, 2), (Square::E2, Square::E4));
+        assert_eq!(choose(false, 3), (Square::D2, Square::D4));
+        assert!(book
+            .choose(
+                &GameManager::from_fen_str("4k3/8/8/8/8/8/8/4K3 w - - 0 1"),
+                &MOVE_TABLE,
+                false,
+                0
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn encodes_castling_and_promotion() {
+        let fen = "r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let gm = GameManager::from_fen_str(fen);
+        let encoded: Vec<u16> = gm
+            .legal_moves(&MOVE_TABLE)
+            .iter()
+            .map(|mv| encode_move(&mv.0, mv.1, mv.2, mv.3))
+            .collect();
+        assert!(encoded.contains(&polyglot("e1h1")));
+        assert!(encoded.contains(&polyglot("e1a1")));
+        assert!(!encoded.contains(&polyglot("e1g1")));
+        assert!(encoded.contains(&(polyglot("b7a8") | 4 << 12)));
+        assert!(encoded.contains(&(polyglot("b7b8") | 1 << 12)));
+
+        let book = Book::new(vec![entry(fen, "e1h1", 1)]);
+        let (from, to, move_type) = book.choose(&gm, &MOVE_TABLE, true, 0).unwrap();
+        assert_eq!(
+            (from, to, move_type),
+            (Square::E1, Square::G1, MoveType::KingCastle)
+        );
+    }
+}
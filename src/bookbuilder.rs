@@ -0,0 +1,456 @@
+//! Builds Polyglot opening books from collections of games.
+//!
+//! Run as `swordfish book <output> <pgn>... [--ply N] [--min-games N]
+//! [--min-score X]`. Every game in the PGN files is replayed from its start,
+//! or from its `FEN` tag, for up to `--ply` plies (16 by default), counting
+//! how often each move was played in each position and how it scored for the
+//! side that played it. Moves played in fewer than `--min-games` games (3 by
+//! default), or scoring less than `--min-score` (a fraction from 0 to 1, 0 by
+//! default), are left out, as are moves that never scored at all. The rest
+//! are written to the book weighted by their score, two points a win and one
+//! a draw, as Polyglot does. Games without a result are passed over.
+
+use std::{collections::HashMap, fs};
+
+use crate::{
+    book::{encode_move, Book, BookEntry},
+    gamemanager::GameManager,
+    movetable::{MoveTable, MOVE_TABLE},
+    notation::parse_san,
+};
+
+const USAGE: &str =
+    "Usage: swordfish book <output> <pgn>... [--ply N] [--min-games N] [--min-score X]";
+
+/// What decides which moves go in the book.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Settings {
+    /// How many plies of each game to look at.
+    max_ply: usize,
+    /// The fewest games a move must have been played in.
+    min_games: u32,
+    /// The lowest score a move may have, from 0 to 1, for the side playing it.
+    min_score: f64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_ply: 16,
+            min_games: 3,
+            min_score: 0.0,
+        }
+    }
+}
+
+/// One game from a PGN file.
+#[derive(Debug, Default, PartialEq)]
+struct Game {
+    /// The `FEN` tag, if the game didn't start from the usual position.
+    fen: Option<String>,
+    /// The moves of the main line, in SAN.
+    moves: Vec<String>,
+    /// White's score: 1 for a win, 0.5 for a draw and 0 for a loss.
+    result: Option<f64>,
+}
+
+/// How a move fared, for the side that played it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Stats {
+    games: u32,
+    wins: u32,
+    draws: u32,
+}
+
+impl Stats {
+    fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games as f64
+    }
+
+    /// The move's weight in a Polyglot book.
+    fn weight(&self) -> u64 {
+        2 * self.wins as u64 + self.draws as u64
+    }
+}
+
+/// Runs the book builder on the command line arguments that follow `book`.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let (output, pgns, settings) = parse_args(args)?;
+
+    let mut stats = HashMap::new();
+    let (mut used, mut skipped) = (0, 0);
+    for pgn in &pgns {
+        let text = fs::read_to_string(pgn).map_err(|err| format!("{pgn}: {err}"))?;
+        for (number, game) in parse_pgn(&text).iter().enumerate() {
+            match add_game(&mut stats, game, &MOVE_TABLE, settings.max_ply) {
+                Ok(true) => used += 1,
+                Ok(false) => skipped += 1,
+                Err(err) => {
+                    eprintln!("{pgn}: game {}: {err}", number + 1);
+                    skipped += 1;
+                }
+            }
+        }
+    }
+
+    let book = build(&stats, &settings);
+    book.save(&output)?;
+    println!(
+        "Read {used} games, skipped {skipped}; wrote {} moves to {output}",
+        book.len()
+    );
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> Result<(String, Vec<String>, Settings), String> {
+    let mut settings = Settings::default();
+    let mut files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(USAGE);
+        match arg.as_str() {
+            "--ply" => {
+                let v = value()?;
+                settings.max_ply = v
+                    .parse()
+                    .map_err(|_| format!("Not a number of plies: {v}"))?
+            }
+            "--min-games" => {
+                let v = value()?;
+                settings.min_games = v
+                    .parse()
+                    .map_err(|_| format!("Not a number of games: {v}"))?
+            }
+            "--min-score" => {
+                let v = value()?;
+                settings.min_score = v
+                    .parse()
+                    .ok()
+                    .filter(|score| (0.0..=1.0).contains(score))
+                    .ok_or(format!("Not a score from 0 to 1: {v}"))?
+            }
+            _ => files.push(arg.clone()),
+        }
+    }
+    if files.len() < 2 {
+        return Err(String::from(USAGE));
+    }
+    let output = files.remove(0);
+    Ok((output, files, settings))
+}
+
+/// Splits PGN text into games, keeping only what a book needs: the start
+/// position, the main line and the result. Comments, variations, move
+/// numbers and annotations are dropped.
+fn parse_pgn(text: &str) -> Vec<Game> {
+    let mut games = Vec::new();
+    let mut game = Game::default();
+    let mut in_moves = false;
+    // How deep we are in variations, and whether in a comment.
+    let mut depth = 0;
+    let mut in_comment = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if !in_comment && depth == 0 && trimmed.starts_with('[') {
+            if in_moves {
+                // A new game's tags, without the last one's result.
+                games.push(std::mem::take(&mut game));
+                in_moves = false;
+            }
+            if let Some((name, value)) = parse_tag(trimmed) {
+                match name {
+                    "FEN" => game.fen = Some(String::from(value)),
+                    "Result" => game.result = parse_result(value),
+                    _ => {}
+                }
+            }
+            continue;
+        }
+        if trimmed.starts_with('%') {
+            continue; // An escaped line.
+        }
+
+        let mut token = String::new();
+        for c in line.chars().chain([' ']) {
+            if in_comment {
+                in_comment = c != '}';
+                continue;
+            }
+            if !c.is_whitespace() && !"{;()".contains(c) {
+                token.push(c);
+                continue;
+            }
+            if depth == 0 && !token.is_empty() {
+                in_moves = true;
+                if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token.as_str()) {
+                    game.result = parse_result(&token).or(game.result);
+                    games.push(std::mem::take(&mut game));
+                    in_moves = false;
+                } else if let Some(mv) = move_token(&token) {
+                    game.moves.push(mv);
+                }
+            }
+            token.clear();
+            match c {
+                '{' => in_comment = true,
+                '(' => depth += 1,
+                ')' => depth = (depth - 1).max(0),
+                ';' => break, // The rest of the line is a comment.
+                _ => {}
+            }
+        }
+    }
+    if in_moves {
+        games.push(game);
+    }
+    games
+}
+
+/// Reads a tag pair like `[Result "1-0"]`.
+fn parse_tag(line: &str) -> Option<(&str, &str)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    Some((name, value.trim().strip_prefix('"')?.strip_suffix('"')?))
+}
+
+fn parse_result(result: &str) -> Option<f64> {
+    match result {
+        "1-0" => Some(1.0),
+        "0-1" => Some(0.0),
+        "1/2-1/2" => Some(0.5),
+        _ => None,
+    }
+}
+
+/// The move in a token from a game's moves, less any move number before it
+/// and annotations after it; `None` if it's only a move number or a NAG.
+fn move_token(token: &str) -> Option<String> {
+    if token.starts_with('$') {
+        return None;
+    }
+    let mv = match token.rfind('.') {
+        Some(dot) => &token[dot + 1..],
+        None if token.bytes().all(|b| b.is_ascii_digit()) => "",
+        None => token,
+    }
+    .trim_end_matches(['!', '?']);
+    (!mv.is_empty()).then(|| String::from(mv))
+}
+
+/// Replays `game` for up to `max_ply` plies, counting its moves in `stats`
+/// by position key and Polyglot move. Returns whether the game was used,
+/// which it isn't without a result, or an error at the first move that
+/// couldn't be played.
+fn add_game(
+    stats: &mut HashMap<(u64, u16), Stats>,
+    game: &Game,
+    tbl: &MoveTable,
+    max_ply: usize,
+) -> Result<bool, String> {
+    let Some(result) = game.result else {
+        return Ok(false);
+    };
+    let mut gm = match &game.fen {
+        Some(fen) => {
+            GameManager::try_from_fen_str(fen).ok_or_else(|| format!("Invalid FEN: {fen}"))?
+        }
+        None => GameManager::default(),
+    };
+
+    // Work out all the moves first, so that a bad game adds nothing.
+    let mut played = Vec::new();
+    for text in game.moves.iter().take(max_ply) {
+        let mv = parse_san(&gm, tbl, text).ok_or_else(|| format!("Illegal move: {text}"))?;
+        let score = if gm.white_to_move {
+            result
+        } else {
+            1.0 - result
+        };
+        played.push((
+            (gm.zobrist_key(), encode_move(&mv.0, mv.1, mv.2, mv.3)),
+            score,
+        ));
+        gm = mv.4;
+    }
+
+    for (key, score) in played {
+        let entry = stats.entry(key).or_default();
+        entry.games += 1;
+        if score == 1.0 {
+            entry.wins += 1;
+        } else if score == 0.5 {
+            entry.draws += 1;
+        }
+    }
+    Ok(true)
+}
+
+/// Makes a book of the moves in `stats` that pass the filters in `settings`.
+fn build(stats: &HashMap<(u64, u16), Stats>, settings: &Settings) -> Book {
+    let mut kept: HashMap<u64, Vec<(u16, u64)>> = HashMap::new();
+    for (&(key, mv), s) in stats {
+        if s.games >= settings.min_games && s.score() >= settings.min_score && s.weight() > 0 {
+            kept.entry(key).or_default().push((mv, s.weight()));
+        }
+    }
+
+    let mut entries = Vec::new();
+    for (key, moves) in kept {
+        // Scale the weights down if need be to fit, keeping their ratios.
+        let max = moves.iter().map(|&(_, weight)| weight).max().unwrap_or(1);
+        let scale = |weight: u64| (weight * u16::MAX as u64 / max.max(u16::MAX as u64)).max(1);
+        for (mv, weight) in moves {
+            entries.push(BookEntry {
+                key,
+                mv,
+                weight: scale(weight) as u16,
+                learn: 0,
+            });
+        }
+    }
+    Book::new(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Square;
+
+    const GAMES: &str = r#"[Event "One"]
+[Result "1-0"]
+
+1. e4 {best by test} e5 2. Nf3 (2. f4 exf4 3. Nf3) Nc6 3. Bb5 a6!? $1 1-0
+
+[Event "Two"]
+[Result "1/2-1/2"]
+
+1.e4 c5 ; the Sicilian
+2.Nf3 d6 1/2-1/2
+
+[Event "Three"]
+[Result "0-1"]
+
+1. d4 d5 2. c4 0-1
+
+[Event "Four"]
+[Result "*"]
+
+1. e4 e5 *
+
+[Event "Five"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"]
+[Result "1-0"]
+
+1. 0-0-0+ Kf7 1-0
+"#;
+
+    #[test]
+    fn reads_pgn() {
+        let games = parse_pgn(GAMES);
+        assert_eq!(games.len(), 5);
+        assert_eq!(games[0].moves, ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]);
+        assert_eq!(games[0].result, Some(1.0));
+        assert_eq!(games[1].moves, ["e4", "c5", "Nf3", "d6"]);
+        assert_eq!(games[1].result, Some(0.5));
+        assert_eq!(games[3].result, None);
+        assert_eq!(
+            games[4].fen.as_deref(),
+            Some("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1")
+        );
+        assert_eq!(games[4].moves, ["0-0-0+", "Kf7"]);
+    }
+
+    #[test]
+    fn builds_books() {
+        let mut stats = HashMap::new();
+        let used: Vec<bool> = parse_pgn(GAMES)
+            .iter()
+            .map(|game| add_game(&mut stats, game, &MOVE_TABLE, 2).unwrap())
+            .collect();
+        assert_eq!(used, [true, true, true, false, true]);
+
+        let start = GameManager::default();
+        let e4 = encode_move(
+            &crate::types::PieceType::Pawn,
+            Square::E2,
+            Square::E4,
+            crate::types::MoveType::DoublePawnPush,
+        );
+        assert_eq!(
+            stats[&(start.zobrist_key(), e4)],
+            Stats {
+                games: 2,
+                wins: 1,
+                draws: 1
+            }
+        );
+
+        // 1. d4 only scored for black, and was only played once.
+        let settings = Settings {
+            min_games: 1,
+            ..Default::default()
+        };
+        let book = build(&stats, &settings);
+        let entries = book.entries(start.zobrist_key());
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].mv, entries[0].weight), (e4, 3));
+        // Black's replies were each played once, and 1... c5 drew.
+        let after_e4 = GameManager::from_fen_str(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        );
+        assert_eq!(book.entries(after_e4.zobrist_key()).len(), 1);
+        assert_eq!(book.entries(after_e4.zobrist_key())[0].weight, 1);
+
+        let book = build(&stats, &Settings::default());
+        assert_eq!(book.len(), 0);
+        let settings = Settings {
+            min_games: 2,
+            min_score: 0.8,
+            ..Default::default()
+        };
+        assert_eq!(build(&stats, &settings).len(), 0);
+    }
+
+    #[test]
+    fn stops_at_illegal_moves() {
+        let mut stats = HashMap::new();
+        let game = Game {
+            fen: None,
+            moves: vec![String::from("e4"), String::from("Ke2"), String::from("Nf6")],
+            result: Some(1.0),
+        };
+        assert_eq!(
+            add_game(&mut stats, &game, &MOVE_TABLE, 10),
+            Err(String::from("Illegal move: Ke2"))
+        );
+        assert!(stats.is_empty());
+    }
+
+    #[test]
+    fn reads_arguments() {
+        let args: Vec<String> = [
+            "out.bin",
+            "a.pgn",
+            "--ply",
+            "8",
+            "b.pgn",
+            "--min-score",
+            "0.5",
+        ]
+        .map(String::from)
+        .to_vec();
+        let (output, pgns, settings) = parse_args(&args).unwrap();
+        assert_eq!(output, "out.bin");
+        assert_eq!(pgns, ["a.pgn", "b.pgn"]);
+        assert_eq!((settings.max_ply, settings.min_score), (8, 0.5));
+        assert!(parse_args(&args[..1]).is_err());
+        assert!(parse_args(&[
+            String::from("x"),
+            String::from("--min-score"),
+            String::from("2")
+        ])
+        .is_err());
+    }
+}
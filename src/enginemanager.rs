@@ -1,23 +1,49 @@
+use std::sync::Arc;
+
 use vampirc_uci::UciMove;
 
 use crate::{
-    gamemanager::GameManager,
-    movetable::{noarc::NoArc, MoveTable},
+    book::Book,
+    gamemanager::{legal_moves::transposition::TranspositionTable, GameManager},
+    movetable::{MoveTable, MOVE_TABLE},
+    options::{self, Options},
 };
 
 pub struct Engine {
-    pub tbl: NoArc<MoveTable>,
+    pub tbl: &'static MoveTable,
+    /// The FEN the game started from, or `None` for the standard start position.
+    pub start_fen: Option<String>,
+    /// The moves played since the start position, leading to `board`.
     pub move_history: Vec<UciMove>,
     pub board: GameManager,
-    //pub set_new_game: bool,
+    pub options: Options,
+    pub tt: Arc<TranspositionTable>,
+    /// The opening book in the `BookFile`, if any.
+    pub book: Option<Book>,
 }
 
 impl Default for Engine {
     fn default() -> Self {
+        let options = Options::default();
+        let tt = Arc::new(TranspositionTable::new(options.spin(options::HASH) as usize));
         Self {
-            tbl: NoArc::new(MoveTable::default()),
+            tbl: &MOVE_TABLE,
+            start_fen: None,
             move_history: Vec::new(),
             board: GameManager::default(),
+            options,
+            tt,
+            book: None,
         }
     }
 }
+
+impl Engine {
+    /// Forgets everything learned about the previous game.
+    pub fn new_game(&mut self) {
+        self.tt.clear();
+        self.start_fen = None;
+        self.move_history.clear();
+        self.board = GameManager::default();
+    }
+}
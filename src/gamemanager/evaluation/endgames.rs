@@ -0,0 +1,353 @@
+//! Knowledge of particular endgames, recognized by the material on the
+//! board: draws neither side can win, wins the usual terms can't find the
+//! way to, and drawish endgames whose scores should be damped.
+//!
+//! As in `pawns`, everything is worked out for the stronger side as white;
+//! when black is the stronger side, it's done on a board flipped top to
+//! bottom.
+
+use super::{kpk, params::EvalParams, pawns::distance, GameManager};
+
+/// A score for a win that's certain but a long way from mate, well above
+/// anything the usual terms add up to and well below mate scores.
+pub const KNOWN_WIN: i32 = 10_000;
+
+/// Endgame scales are out of this: it leaves the score alone.
+pub const NORMAL_SCALE: i32 = 64;
+
+/// What the endgame knowledge makes of a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The position's score for white, in place of the usual evaluation.
+    Exact(i32),
+    /// The usual evaluation holds, with its endgame part multiplied by
+    /// this and divided by `NORMAL_SCALE`.
+    Scale(i32),
+}
+
+/// A recognized endgame: its material signature, such as `KBNK`, with the
+/// stronger side first, and the verdict on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Endgame {
+    pub name: &'static str,
+    pub verdict: Verdict,
+}
+
+/// Bonuses for driving the losing king towards the edge or the right
+/// corner, and for bringing the winning king closer to it, per square.
+const PUSH_TO_EDGE: i32 = 20;
+const PUSH_TO_CORNER: i32 = 40;
+const PUSH_CLOSE: i32 = 10;
+/// A bonus for a winning pawn in KPK, per rank it has advanced.
+const PUSH_PAWN: i32 = 20;
+
+/// One side's pieces, seen from that side of the board.
+struct Side {
+    king: u32,
+    pawns: u64,
+    knights: u64,
+    bishops: u64,
+    rooks: u64,
+    queens: u64,
+}
+
+impl Side {
+    fn pieces(&self) -> u64 {
+        self.knights | self.bishops | self.rooks | self.queens
+    }
+
+    fn is_bare(&self) -> bool {
+        self.pieces() | self.pawns == 0
+    }
+
+    /// The endgame worth of the side's pieces and pawns.
+    fn material(&self, p: &EvalParams) -> i32 {
+        let count = |bits: u64| bits.count_ones() as i32;
+        count(self.pawns) * p.pawn_value.eg()
+            + count(self.knights) * p.knight_value.eg()
+            + count(self.bishops) * p.bishop_value.eg()
+            + count(self.rooks) * p.rook_value.eg()
+            + count(self.queens) * p.queen_value.eg()
+    }
+}
+
+/// Recognizes the endgame in `gm`, if it's one we know about.
+pub fn probe(p: &EvalParams, gm: &GameManager) -> Option<Endgame> {
+    let board = &gm.bitboard;
+    let sides = |flip: bool| {
+        let relative = |bits: u64| if flip { bits.swap_bytes() } else { bits };
+        let white = Side {
+            king: relative(board.king_white).trailing_zeros(),
+            pawns: relative(board.pawns_white),
+            knights: relative(board.knights_white),
+            bishops: relative(board.bishops_white),
+            rooks: relative(board.rooks_white),
+            queens: relative(board.queens_white),
+        };
+        let black = Side {
+            king: relative(board.king_black).trailing_zeros(),
+            pawns: relative(board.pawns_black),
+            knights: relative(board.knights_black),
+            bishops: relative(board.bishops_black),
+            rooks: relative(board.rooks_black),
+            queens: relative(board.queens_black),
+        };
+        if flip {
+            (black, white)
+        } else {
+            (white, black)
+        }
+    };
+
+    let (white, black) = sides(false);
+    if let Some(endgame) = draw(&white, &black) {
+        return Some(endgame);
+    }
+    if let Some(endgame) = opposite_bishops(&white, &black) {
+        return Some(endgame);
+    }
+    // Try white as the stronger side, and then black.
+    for (flip, sign) in [(false, 1), (true, -1)] {
+        let (strong, weak) = sides(flip);
+        let strong_to_move = gm.white_to_move != flip;
+        if let Some(mut endgame) = win(p, &strong, &weak)
+            .or_else(|| king_and_pawn(p, &strong, &weak, strong_to_move))
+            .or_else(|| wrong_bishop(&strong, &weak))
+        {
+            if let Verdict::Exact(score) = endgame.verdict {
+                endgame.verdict = Verdict::Exact(score * sign);
+            }
+            return Some(endgame);
+        }
+    }
+    None
+}
+
+/// Neither side has the material to mate: no pawns, rooks or queens, and
+/// at most a minor piece each, or two knights against a bare king.
+fn draw(white: &Side, black: &Side) -> Option<Endgame> {
+    let minors = |side: &Side| (side.knights | side.bishops).count_ones();
+    if white.pawns | black.pawns | white.rooks | black.rooks | white.queens | black.queens != 0 {
+        return None;
+    }
+    let name = match (
+        minors(white).max(minors(black)),
+        minors(white).min(minors(black)),
+    ) {
+        (0, 0) => "KK",
+        (1, 0) if white.knights | black.knights != 0 => "KNK",
+        (1, 0) => "KBK",
+        (1, 1) => "KmKm",
+        (2, 0) if white.bishops | black.bishops == 0 => "KNNK",
+        _ => return None,
+    };
+    Some(Endgame {
+        name,
+        verdict: Verdict::Exact(0),
+    })
+}
+
+/// A lone king against enough material to mate it, without pawns: drive
+/// the king to the edge, or for a bishop and knight, to a corner the
+/// bishop can cover.
+fn win(p: &EvalParams, strong: &Side, weak: &Side) -> Option<Endgame> {
+    if !weak.is_bare() || strong.pawns != 0 {
+        return None;
+    }
+    let close = PUSH_CLOSE * (7 - distance(strong.king, weak.king));
+
+    if strong.pieces() == strong.knights | strong.bishops
+        && strong.knights.count_ones() == 1
+        && strong.bishops.count_ones() == 1
+    {
+        let corners = if is_dark(strong.bishops.trailing_zeros()) {
+            [7, 56] // a1 and h8
+        } else {
+            [0, 63] // h1 and a8
+        };
+        let corner = corners
+            .iter()
+            .map(|&corner| distance(weak.king, corner))
+            .min()
+            .unwrap_or_default();
+        return Some(Endgame {
+            name: "KBNK",
+            verdict: Verdict::Exact(
+                KNOWN_WIN + strong.material(p) + PUSH_TO_CORNER * (7 - corner) + close,
+            ),
+        });
+    }
+
+    let both_bishops = strong.bishops & LIGHT != 0 && strong.bishops & !LIGHT != 0;
+    if strong.rooks | strong.queens == 0 && !both_bishops {
+        return None;
+    }
+    let name = if strong.pieces() == strong.queens && strong.queens.count_ones() == 1 {
+        "KQK"
+    } else if strong.pieces() == strong.rooks && strong.rooks.count_ones() == 1 {
+        "KRK"
+    } else {
+        "KXK"
+    };
+    Some(Endgame {
+        name,
+        verdict: Verdict::Exact(
+            KNOWN_WIN + strong.material(p) + PUSH_TO_EDGE * centre_distance(weak.king) + close,
+        ),
+    })
+}
+
+/// A king and pawn against a lone king, looked up in the KPK bitbase.
+fn king_and_pawn(
+    p: &EvalParams,
+    strong: &Side,
+    weak: &Side,
+    strong_to_move: bool,
+) -> Option<Endgame> {
+    if !weak.is_bare() || strong.pieces() != 0 || strong.pawns.count_ones() != 1 {
+        return None;
+    }
+    let pawn = strong.pawns.trailing_zeros();
+    let score = if kpk::probe(strong.king, pawn, weak.king, strong_to_move) {
+        KNOWN_WIN + strong.material(p) + PUSH_PAWN * (pawn / 8) as i32
+    } else {
+        0
+    };
+    Some(Endgame {
+        name: "KPK",
+        verdict: Verdict::Exact(score),
+    })
+}
+
+/// A bishop and rook pawns against a lone king that has reached the
+/// promotion square's corner, when the bishop can't drive it out.
+fn wrong_bishop(strong: &Side, weak: &Side) -> Option<Endgame> {
+    if !weak.is_bare()
+        || strong.pieces() != strong.bishops
+        || strong.bishops == 0
+        || strong.pawns == 0
+    {
+        return None;
+    }
+    let (a_file, h_file) = (0x80808080_80808080, 0x01010101_01010101);
+    let promotion = if strong.pawns & !a_file == 0 {
+        63 // a8
+    } else if strong.pawns & !h_file == 0 {
+        56 // h8
+    } else {
+        return None;
+    };
+    let covered = if is_dark(promotion) {
+        strong.bishops & !LIGHT != 0
+    } else {
+        strong.bishops & LIGHT != 0
+    };
+    if covered || distance(weak.king, promotion) > 1 {
+        return None;
+    }
+    Some(Endgame {
+        name: "KBPK",
+        verdict: Verdict::Exact(0),
+    })
+}
+
+/// Bishops on opposite colors with nothing but pawns beside them: even a
+/// pawn or two up, that's often a draw.
+fn opposite_bishops(white: &Side, black: &Side) -> Option<Endgame> {
+    let lone_bishop = |side: &Side| side.pieces() == side.bishops && side.bishops.count_ones() == 1;
+    if !lone_bishop(white)
+        || !lone_bishop(black)
+        || (white.bishops & LIGHT == 0) == (black.bishops & LIGHT == 0)
+    {
+        return None;
+    }
+    Some(Endgame {
+        name: "KBPsKBPs",
+        verdict: Verdict::Scale(NORMAL_SCALE / 2),
+    })
+}
+
+/// The light squares; bit 0, h1, is one of them.
+pub(super) const LIGHT: u64 = 0xAA55AA55_AA55AA55;
+
+fn is_dark(bit: u32) -> bool {
+    LIGHT & 1 << bit == 0
+}
+
+/// The number of king moves from `bit` to the nearest of the four centre squares.
+fn centre_distance(bit: u32) -> i32 {
+    let (rank, file) = ((bit / 8) as i32, (bit % 8) as i32);
+    (3 - rank).max(rank - 4) + (3 - file).max(file - 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamemanager::GameManager;
+
+    fn endgame(fen: &str) -> Option<Endgame> {
+        probe(&EvalParams::default(), &GameManager::from_fen_str(fen))
+    }
+
+    fn exact(fen: &str) -> i32 {
+        match endgame(fen).map(|e| e.verdict) {
+            Some(Verdict::Exact(score)) => score,
+            other => panic!("{fen}: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn knows_draws() {
+        assert_eq!(exact("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"), 0);
+        assert_eq!(exact("4k3/8/8/8/8/8/8/2N1K3 b - - 0 1"), 0);
+        assert_eq!(exact("4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1"), 0);
+        assert_eq!(exact("4kn2/8/8/8/8/8/8/2B1K3 w - - 0 1"), 0);
+        assert_eq!(
+            endgame("4k3/8/8/8/8/8/8/1BB1K3 w - - 0 1").unwrap().name,
+            "KXK"
+        );
+        assert_eq!(endgame("4k3/8/8/8/8/8/4P3/2B1K3 w - - 0 1"), None);
+    }
+
+    #[test]
+    fn drives_the_king_to_the_edge() {
+        let centre = exact("8/8/8/3k4/8/8/8/Q3K3 w - - 0 1");
+        let edge = exact("3k4/8/8/8/8/8/8/Q3K3 w - - 0 1");
+        assert!(centre > KNOWN_WIN);
+        assert!(edge > centre);
+        assert_eq!(exact("q3k3/8/8/8/8/8/8/3K4 w - - 0 1"), -edge);
+        assert_eq!(
+            endgame("3k4/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap().name,
+            "KRK"
+        );
+    }
+
+    #[test]
+    fn mates_with_bishop_and_knight_in_the_right_corner() {
+        // A light-squared bishop mates on a8 or h1.
+        let right = exact("k7/8/8/8/8/8/8/1BN1K3 w - - 0 1");
+        let wrong = exact("7k/8/8/8/8/8/8/1BN1K3 w - - 0 1");
+        assert!(right > wrong);
+    }
+
+    #[test]
+    fn knows_king_and_pawn_endgames() {
+        assert!(exact("8/8/8/4k3/8/4K3/4P3/8 b - - 0 1") > KNOWN_WIN);
+        assert_eq!(exact("8/8/8/4k3/8/4K3/4P3/8 w - - 0 1"), 0);
+        // The same, with black's pawn.
+        assert!(exact("8/4p3/4k3/8/4K3/8/8/8 w - - 0 1") < -KNOWN_WIN);
+        assert_eq!(exact("8/4p3/4k3/8/4K3/8/8/8 b - - 0 1"), 0);
+    }
+
+    #[test]
+    fn scales_drawish_endgames() {
+        // The king holds a8, which the dark-squared bishop can't cover.
+        assert_eq!(exact("8/1k6/8/8/8/8/P7/B3K3 w - - 0 1"), 0);
+        assert_eq!(endgame("8/1k6/8/8/8/8/P7/1B2K3 w - - 0 1"), None);
+        assert_eq!(exact("8/8/8/4b3/8/5k1p/8/7K w - - 0 1"), 0);
+
+        let opposite = endgame("4k3/3b4/8/3p4/8/3P4/2P5/2B1K3 w - - 0 1").unwrap();
+        assert_eq!(opposite.verdict, Verdict::Scale(NORMAL_SCALE / 2));
+        assert_eq!(endgame("4k3/2b5/8/3p4/8/3P4/2P5/2B1K3 w - - 0 1"), None);
+    }
+}
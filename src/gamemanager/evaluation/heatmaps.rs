@@ -1,79 +1,105 @@
+use super::score::Score;
+
+/// Piece-square tables for one color, holding a midgame and an endgame
+/// bonus for each piece on each square, indexed by bit position.
+#[derive(Clone)]
 pub struct Heatmap {
-    pub pawns_start: [i32; 64],
-    pub pawns_end: [i32; 64],
-    pub knights: [i32; 64],
-    pub bishops: [i32; 64],
-    pub rooks: [i32; 64],
-    pub queens: [i32; 64],
-    pub kings_start: [i32; 64],
-    pub kings_end: [i32; 64],
+    pub pawns: [Score; 64],
+    pub knights: [Score; 64],
+    pub bishops: [Score; 64],
+    pub rooks: [Score; 64],
+    pub queens: [Score; 64],
+    pub kings: [Score; 64],
 }
 
 impl Default for Heatmap {
     /// Returns the heatmaps for black.
     /// Use .rev() to get the heatmaps for white.
     fn default() -> Self {
-        let pawns_start = [
+        let pawns_mg = [
             0, 0, 0, 0, 0, 0, 0, 0, 50, 50, 50, 50, 50, 50, 50, 50, 10, 10, 20, 30, 30, 20, 10, 10,
             5, 5, 10, 25, 25, 10, 5, 5, 0, 0, 0, 20, 20, 0, 0, 0, 5, -5, -10, 0, 0, -10, -5, 5, 5,
             10, 10, -20, -20, 10, 10, 5, 0, 0, 0, 0, 0, 0, 0, 0,
         ];
 
-        let pawns_end = [
+        let pawns_eg = [
             0, 0, 0, 0, 0, 0, 0, 0, 80, 80, 80, 80, 80, 80, 80, 80, 50, 50, 50, 50, 50, 50, 50, 50,
             30, 30, 30, 30, 30, 30, 30, 30, 20, 20, 20, 20, 20, 20, 20, 20, 10, 10, 10, 10, 10, 10,
             10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 0, 0, 0, 0, 0, 0, 0, 0,
         ];
 
-        let knights = [
+        let knights_mg = [
             -50, -40, -30, -30, -30, -30, -40, -50, -40, -20, 0, 0, 0, 0, -20, -40, -30, 0, 10, 15,
             15, 10, 0, -30, -30, 5, 15, 20, 20, 15, 5, -30, -30, 0, 15, 20, 20, 15, 0, -30, -30, 5,
             10, 15, 15, 10, 5, -30, -40, -20, 0, 5, 5, 0, -20, -40, -50, -40, -30, -30, -30, -30,
             -40, -50,
         ];
 
-        let bishops = [
+        let bishops_mg = [
             -20, -10, -10, -10, -10, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 10, 10,
             5, 0, -10, -10, 5, 5, 10, 10, 5, 5, -10, -10, 0, 10, 10, 10, 10, 0, -10, -10, 10, 10,
             10, 10, 10, 10, -10, -10, 5, 0, 0, 0, 0, 5, -10, -20, -10, -10, -10, -10, -10, -10,
             -20,
         ];
 
-        let rooks = [
+        let rooks_mg = [
             0, 0, 0, 0, 0, 0, 0, 0, 5, 10, 10, 10, 10, 10, 10, 5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0,
             0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0,
             0, 0, -5, 0, 0, 0, 5, 5, 0, 0, 0,
         ];
 
-        let queens = [
+        let queens_mg = [
             -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5,
             0, -10, -5, 0, 5, 5, 5, 5, 0, -5, 0, 0, 5, 5, 5, 5, 0, -5, -10, 5, 5, 5, 5, 5, 0, -10,
             -10, 0, 5, 0, 0, 0, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
         ];
 
-        let kings_start = [
+        let kings_mg = [
             -80, -70, -70, -70, -70, -70, -70, -80, -60, -60, -60, -60, -60, -60, -60, -60, -40,
             -50, -50, -60, -60, -50, -50, -40, -30, -40, -40, -50, -50, -40, -40, -30, -20, -30,
             -30, -40, -40, -30, -30, -20, -10, -20, -20, -20, -20, -20, -20, -10, 20, 20, -5, -5,
             -5, -5, 20, 20, 20, 30, 10, 0, 0, 10, 30, 20,
         ];
 
-        let kings_end = [
+        let kings_eg = [
             -20, -10, -10, -10, -10, -10, -10, -20, -5, 0, 5, 5, 5, 5, 0, -5, -10, -5, 20, 30, 30,
             20, -5, -10, -15, -10, 35, 45, 45, 35, -10, -15, -20, -15, 30, 40, 40, 30, -15, -20,
             -25, -20, 20, 25, 25, 20, -20, -25, -30, -25, 0, 0, 0, 0, -25, -30, -50, -30, -30, -30,
             -30, -30, -30, -50,
         ];
 
+        let knights_eg = [
+            -50, -40, -30, -25, -25, -30, -40, -50, -40, -20, -5, 0, 0, -5, -20, -40, -30, -5, 10,
+            15, 15, 10, -5, -30, -25, 0, 15, 20, 20, 15, 0, -25, -25, 0, 15, 20, 20, 15, 0, -25,
+            -30, -5, 10, 15, 15, 10, -5, -30, -40, -20, -5, 0, 0, -5, -20, -40, -50, -40, -30, -25,
+            -25, -30, -40, -50,
+        ];
+
+        let bishops_eg = [
+            -15, -10, -8, -6, -6, -8, -10, -15, -10, -4, 0, 2, 2, 0, -4, -10, -8, 0, 4, 6, 6, 4, 0,
+            -8, -6, 2, 6, 10, 10, 6, 2, -6, -6, 2, 6, 10, 10, 6, 2, -6, -8, 0, 4, 6, 6, 4, 0, -8,
+            -10, -4, 0, 2, 2, 0, -4, -10, -15, -10, -8, -6, -6, -8, -10, -15,
+        ];
+
+        let rooks_eg = [
+            5, 5, 5, 5, 5, 5, 5, 5, 10, 10, 10, 10, 10, 10, 10, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, -5, 0, 0, 0, 0, 0, 0, -5,
+        ];
+
+        let queens_eg = [
+            -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 5, 5, 5, 5, 0, -10, -10, 5, 10, 10, 10,
+            10, 5, -10, -5, 5, 10, 15, 15, 10, 5, -5, -5, 5, 10, 15, 15, 10, 5, -5, -10, 5, 10, 10,
+            10, 10, 5, -10, -10, 0, 5, 5, 5, 5, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
+        ];
+
         Self {
-            pawns_start,
-            pawns_end,
-            knights,
-            bishops,
-            rooks,
-            queens,
-            kings_start,
-            kings_end,
+            pawns: pack(pawns_mg, pawns_eg),
+            knights: pack(knights_mg, knights_eg),
+            bishops: pack(bishops_mg, bishops_eg),
+            rooks: pack(rooks_mg, rooks_eg),
+            queens: pack(queens_mg, queens_eg),
+            kings: pack(kings_mg, kings_eg),
         }
     }
 }
@@ -83,24 +109,29 @@ impl Heatmap {
     /// heatmap is for black, and vice versa.
     pub fn rev(&self) -> Self {
         Self {
-            pawns_start: reverse_array(self.pawns_start),
-            pawns_end: reverse_array(self.pawns_end),
-            knights: reverse_array(self.knights),
-            bishops: reverse_array(self.bishops),
-            rooks: reverse_array(self.rooks),
-            queens: reverse_array(self.queens),
-            kings_start: reverse_array(self.kings_start),
-            kings_end: reverse_array(self.kings_end),
+            pawns: flip_ranks(self.pawns),
+            knights: flip_ranks(self.knights),
+            bishops: flip_ranks(self.bishops),
+            rooks: flip_ranks(self.rooks),
+            queens: flip_ranks(self.queens),
+            kings: flip_ranks(self.kings),
         }
     }
 }
 
-fn reverse_array(arr: [i32; 64]) -> [i32; 64] {
-    let mut ret = [0; 64];
+fn pack(mg: [i32; 64], eg: [i32; 64]) -> [Score; 64] {
+    let mut ret = [Score::ZERO; 64];
+    for idx in 0..64 {
+        ret[idx] = Score::new(mg[idx], eg[idx]);
+    }
+    ret
+}
 
-    // All heatmaps are symmetric about the central vertical axis.
-    for (idx, v) in arr.into_iter().rev().enumerate() {
-        ret[idx] = v;
+/// Mirrors a table top to bottom, so the first rank becomes the eighth.
+fn flip_ranks(arr: [Score; 64]) -> [Score; 64] {
+    let mut ret = [Score::ZERO; 64];
+    for idx in 0..64 {
+        ret[idx ^ 56] = arr[idx];
     }
     ret
 }
@@ -0,0 +1,157 @@
+//! King safety: the pawns sheltering the king and storming towards it, open
+//! files beside it, and the enemy pieces bearing down on the squares around it.
+//!
+//! As in `pawns`, everything is worked out for white; black's king is scored
+//! on a board flipped top to bottom.
+
+use crate::types::PieceType;
+
+use super::{params::EvalParams, pawns::FILE_A, piece_attacks, score::Score};
+
+/// The enemy pieces that can join an attack on the king, as bitboards.
+pub struct Attackers {
+    pub knights: u64,
+    pub bishops: u64,
+    pub rooks: u64,
+    pub queens: u64,
+}
+
+/// Scores the safety of the king on `king`, sheltered by `our_pawns` and
+/// attacked by `their_pawns` and `enemy`, with the board seen from the
+/// king's side.
+pub fn king_safety(
+    p: &EvalParams,
+    king: u64,
+    our_pawns: u64,
+    their_pawns: u64,
+    enemy: &Attackers,
+    occupied: u64,
+) -> Score {
+    if king == 0 {
+        return Score::ZERO;
+    }
+    shelter(p, king, our_pawns, their_pawns) + attack(p, king, enemy, occupied)
+}
+
+/// Scores the pawn shield, pawn storm and open files on the king's wing:
+/// the king's file and those either side of it, shifted in from the edge.
+fn shelter(p: &EvalParams, king: u64, our_pawns: u64, their_pawns: u64) -> Score {
+    let square = king.trailing_zeros();
+    let rank = square / 8;
+    let centre = (7 - square % 8).clamp(1, 6);
+    let ahead = u64::MAX.checked_shl(8 * (rank + 1)).unwrap_or(0);
+
+    let mut score = Score::ZERO;
+    for file in centre - 1..=centre + 1 {
+        let file = FILE_A >> file;
+        let ours = our_pawns & file;
+        let theirs = their_pawns & file;
+
+        if ours == 0 {
+            score += if theirs == 0 {
+                p.open_file
+            } else {
+                p.semi_open_file
+            };
+        }
+        if ours & ahead != 0 {
+            score += p.shield[((ours & ahead).trailing_zeros() / 8 - rank) as usize];
+        }
+        if theirs & ahead != 0 {
+            score += p.storm[((theirs & ahead).trailing_zeros() / 8 - rank) as usize];
+        }
+    }
+    score
+}
+
+/// Scores the enemy pieces attacking the king zone, the king's square and
+/// those around it, by the attack units they add up to. The penalty grows
+/// faster than the units do, so one more piece joining an attack matters
+/// more than the first one did.
+fn attack(p: &EvalParams, king: u64, enemy: &Attackers, occupied: u64) -> Score {
+    let zone = king | piece_attacks(PieceType::King, king, occupied);
+    let mut attackers = 0;
+    let mut units = 0;
+
+    for (bits, piece, weight) in [
+        (enemy.knights, PieceType::Knight, p.knight_units),
+        (enemy.bishops, PieceType::Bishop, p.bishop_units),
+        (enemy.rooks, PieceType::Rook, p.rook_units),
+        (enemy.queens, PieceType::Queen, p.queen_units),
+    ] {
+        let mut bits = bits;
+        while bits != 0 {
+            let piece_bit = bits & bits.wrapping_neg();
+            bits &= bits - 1;
+            let hits = piece_attacks(piece.clone(), piece_bit, occupied) & zone;
+            if hits != 0 {
+                attackers += 1;
+                units += weight * hits.count_ones() as i32;
+            }
+        }
+    }
+
+    if attackers < p.min_attackers {
+        return Score::ZERO;
+    }
+    let penalty = p.safety_table[(units.max(0) as usize).min(p.safety_table.len() - 1)];
+    Score::new(-penalty, -penalty / 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamemanager::GameManager;
+
+    /// Scores white's king in `fen`.
+    fn white_king(fen: &str) -> Score {
+        let bb = GameManager::from_fen_str(fen).bitboard;
+        let occupied = bb.pawns_white
+            | bb.knights_white
+            | bb.bishops_white
+            | bb.rooks_white
+            | bb.queens_white
+            | bb.king_white
+            | bb.pawns_black
+            | bb.knights_black
+            | bb.bishops_black
+            | bb.rooks_black
+            | bb.queens_black
+            | bb.king_black;
+        let enemy = Attackers {
+            knights: bb.knights_black,
+            bishops: bb.bishops_black,
+            rooks: bb.rooks_black,
+            queens: bb.queens_black,
+        };
+        king_safety(
+            &EvalParams::default(),
+            bb.king_white,
+            bb.pawns_white,
+            bb.pawns_black,
+            &enemy,
+            occupied,
+        )
+    }
+
+    #[test]
+    fn shelter_matters() {
+        let sheltered = white_king("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        let advanced = white_king("6k1/5ppp/8/8/8/5PPP/8/6K1 w - - 0 1");
+        let stormed = white_king("6k1/8/8/8/8/5ppp/5PPP/6K1 w - - 0 1");
+        let open = white_king("6k1/5ppp/8/8/8/8/8/6K1 w - - 0 1");
+        assert!(sheltered.mg() > advanced.mg());
+        assert!(sheltered.mg() > stormed.mg());
+        assert!(advanced.mg() > open.mg());
+    }
+
+    #[test]
+    fn attackers_add_up() {
+        let one = white_king("6k1/8/8/8/8/8/5PPP/3q2K1 w - - 0 1");
+        let two = white_king("6k1/8/8/8/8/7n/5PPP/3q2K1 w - - 0 1");
+        assert_eq!(one, white_king("6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1"));
+        assert!(two.mg() < one.mg());
+        let table = EvalParams::default().safety_table;
+        assert!(table[20] - table[10] > table[10] - table[0]);
+    }
+}
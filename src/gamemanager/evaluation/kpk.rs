@@ -0,0 +1,222 @@
+//! A bitbase for king and pawn against king: whether each position is a win
+//! for the side with the pawn, worked out by retrograde analysis when the
+//! engine starts.
+//!
+//! Positions are stored for the pawn on the a to d files only, with the
+//! stronger side as white; the others are mirrored onto those. Squares are
+//! numbered from a1 = 0 along each rank to h8 = 63 here, unlike the
+//! bitboards, where bit 0 is h1.
+
+use std::sync::LazyLock;
+
+/// The side to move, the pawn's 24 squares on files a to d and ranks 2 to 7,
+/// and both kings' squares.
+const ENTRIES: usize = 2 * 24 * 64 * 64;
+
+/// Which positions are wins, one bit each.
+static KPK: LazyLock<Vec<u64>> = LazyLock::new(generate);
+
+/// Generates the bitbase now, rather than at the first probe.
+pub fn init() {
+    LazyLock::force(&KPK);
+}
+
+/// Whether the side with the pawn wins, given its king's, its pawn's and
+/// the other king's bits on a board seen from its side, and whether it's
+/// to move.
+pub fn probe(strong_king: u32, pawn: u32, weak_king: u32, strong_to_move: bool) -> bool {
+    // Turn our bits into squares, mirrored so that the pawn is on a to d.
+    let mirror = if 7 - pawn % 8 < 4 { 0 } else { 7 };
+    let square = |bit: u32| (bit / 8 * 8 + (7 - bit % 8)) as usize ^ mirror;
+    let index = index(
+        !strong_to_move,
+        square(strong_king),
+        square(weak_king),
+        square(pawn),
+    );
+    KPK[index / 64] & 1 << (index % 64) != 0
+}
+
+/// The entry for a position. `black` is whether the weaker side is to move.
+fn index(black: bool, white_king: usize, black_king: usize, pawn: usize) -> usize {
+    let pawn = (pawn / 8 - 1) * 4 + pawn % 8;
+    white_king + black_king * 64 + pawn * 64 * 64 + black as usize * 24 * 64 * 64
+}
+
+/// What's known about a position while the bitbase is worked out. The
+/// values are bits, so that the results of several moves can be combined.
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+
+fn generate() -> Vec<u64> {
+    let mut results = vec![INVALID; ENTRIES];
+    for (index, result) in results.iter_mut().enumerate() {
+        *result = initial(index);
+    }
+    // Settle the unknown positions from the ones they lead to, until none
+    // of them changes.
+    loop {
+        let mut changed = false;
+        for index in 0..ENTRIES {
+            if results[index] == UNKNOWN {
+                let result = classify(&results, index);
+                if result != UNKNOWN {
+                    results[index] = result;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut bits = vec![0; ENTRIES / 64];
+    for (index, &result) in results.iter().enumerate() {
+        if result == WIN {
+            bits[index / 64] |= 1 << (index % 64);
+        }
+    }
+    bits
+}
+
+/// Splits an entry into the side to move and the white king's, black
+/// king's and pawn's squares.
+fn decode(index: usize) -> (bool, usize, usize, usize) {
+    let pawn = index / (64 * 64) % 24;
+    (
+        index >= 24 * 64 * 64,
+        index % 64,
+        index / 64 % 64,
+        (pawn / 4 + 1) * 8 + pawn % 4,
+    )
+}
+
+/// The number of king moves between two squares.
+fn distance(a: usize, b: usize) -> usize {
+    (a / 8).abs_diff(b / 8).max((a % 8).abs_diff(b % 8))
+}
+
+/// The squares a king on `square` can step to.
+fn king_moves(square: usize) -> impl Iterator<Item = usize> {
+    (0..64).filter(move |&to| distance(square, to) == 1)
+}
+
+/// Whether a white pawn on `pawn` attacks `square`.
+fn pawn_attacks(pawn: usize, square: usize) -> bool {
+    square / 8 == pawn / 8 + 1 && (square % 8).abs_diff(pawn % 8) == 1
+}
+
+/// What can be told about a position without looking at what follows it.
+fn initial(index: usize) -> u8 {
+    let (black, white_king, black_king, pawn) = decode(index);
+    if distance(white_king, black_king) <= 1
+        || white_king == pawn
+        || black_king == pawn
+        || (!black && pawn_attacks(pawn, black_king))
+    {
+        return INVALID;
+    }
+
+    let queening = pawn + 8;
+    if !black {
+        // The pawn queens and can't be taken at once.
+        if pawn / 8 == 6
+            && white_king != queening
+            && black_king != queening
+            && (distance(black_king, queening) > 1 || distance(white_king, queening) == 1)
+        {
+            return WIN;
+        }
+    } else {
+        let attacked =
+            |square: usize| distance(white_king, square) <= 1 || pawn_attacks(pawn, square);
+        // Stalemate, or the pawn falls.
+        if king_moves(black_king).all(attacked)
+            || (distance(black_king, pawn) == 1 && distance(white_king, pawn) > 1)
+        {
+            return DRAW;
+        }
+    }
+    UNKNOWN
+}
+
+/// Works out a position from the positions its moves lead to: white wins if
+/// any move wins, and black draws if any move draws.
+fn classify(results: &[u8], index: usize) -> u8 {
+    let (black, white_king, black_king, pawn) = decode(index);
+    let mut outcomes = INVALID;
+    if black {
+        for to in king_moves(black_king) {
+            outcomes |= results[self::index(false, white_king, to, pawn)];
+        }
+        if outcomes & DRAW != 0 {
+            DRAW
+        } else if outcomes & UNKNOWN != 0 {
+            UNKNOWN
+        } else {
+            WIN
+        }
+    } else {
+        for to in king_moves(white_king) {
+            outcomes |= results[self::index(true, to, black_king, pawn)];
+        }
+        if pawn / 8 < 6 {
+            outcomes |= results[self::index(true, white_king, black_king, pawn + 8)];
+            if pawn / 8 == 1 && pawn + 8 != white_king && pawn + 8 != black_king {
+                outcomes |= results[self::index(true, white_king, black_king, pawn + 16)];
+            }
+        }
+        if outcomes & WIN != 0 {
+            WIN
+        } else if outcomes & UNKNOWN != 0 {
+            UNKNOWN
+        } else {
+            DRAW
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamemanager::GameManager;
+
+    /// Probes the bitbase for a position with white's king and pawn.
+    fn wins(fen: &str) -> bool {
+        let gm = GameManager::from_fen_str(fen);
+        let bb = gm.bitboard;
+        probe(
+            bb.king_white.trailing_zeros(),
+            bb.pawns_white.trailing_zeros(),
+            bb.king_black.trailing_zeros(),
+            gm.white_to_move,
+        )
+    }
+
+    #[test]
+    fn knows_kpk() {
+        // The king in front of its pawn wins with the opposition, and on
+        // the sixth rank, even without.
+        assert!(wins("8/8/8/4k3/8/4K3/4P3/8 b - - 0 1"));
+        assert!(!wins("8/8/8/4k3/8/4K3/4P3/8 w - - 0 1"));
+        assert!(wins("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"));
+        // Behind its pawn, it doesn't.
+        assert!(!wins("4k3/8/4P3/4K3/8/8/8/8 w - - 0 1"));
+        // A rook pawn draws once the defending king reaches the corner.
+        assert!(!wins("k7/8/8/8/P7/8/8/4K3 w - - 0 1"));
+        assert!(!wins("7k/8/8/8/8/7P/8/7K w - - 0 1"));
+        // The pawn outruns the king, or doesn't.
+        assert!(wins("8/8/8/8/P7/8/8/k6K w - - 0 1"));
+        assert!(!wins("8/8/8/8/P7/4k3/8/7K b - - 0 1"));
+        assert!(wins("8/P7/8/8/8/8/8/k6K w - - 0 1"));
+        assert!(!wins("8/Pk6/8/8/8/8/8/7K w - - 0 1"));
+        // Mirrored across the board, the result is the same.
+        assert_eq!(
+            wins("8/8/8/2k5/8/2KP4/8/8 w - - 0 1"),
+            wins("8/8/8/5k2/8/4PK2/8/8 w - - 0 1")
+        );
+    }
+}
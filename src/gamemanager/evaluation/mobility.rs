@@ -0,0 +1,199 @@
+//! Piece mobility and activity: how many safe squares each piece can reach,
+//! the bishop pair, rooks on open files and the seventh rank, knight
+//! outposts, and pieces that have walked into a trap.
+//!
+//! As in `pawns`, everything is worked out for white; black's pieces are
+//! scored on a board flipped top to bottom.
+
+use crate::types::PieceType;
+
+use super::{
+    endgames::LIGHT,
+    params::EvalParams,
+    pawns::{east, north_fill, south_fill, west},
+    piece_attacks,
+    score::Score,
+};
+
+const RANK_1: u64 = 0xFF;
+const RANK_7: u64 = 0xFF << 48;
+const RANK_8: u64 = 0xFF << 56;
+/// The fourth, fifth and sixth ranks, where an outpost can stand.
+const OUTPOST_RANKS: u64 = 0xFFFFFF << 24;
+
+/// One side's pieces as bitboards, seen from that side of the board.
+#[derive(Clone, Copy)]
+pub struct Army {
+    pub pawns: u64,
+    pub knights: u64,
+    pub bishops: u64,
+    pub rooks: u64,
+    pub queens: u64,
+    pub king: u64,
+}
+
+impl Army {
+    fn all(&self) -> u64 {
+        self.pawns | self.knights | self.bishops | self.rooks | self.queens | self.king
+    }
+}
+
+/// Scores `ours`' mobility and the activity of its pieces, in that order,
+/// against `theirs`. Each piece earns its mobility bonus for every safe
+/// square it can reach beyond the number its kind usually can.
+pub fn mobility(p: &EvalParams, ours: &Army, theirs: &Army) -> (Score, Score) {
+    let occupied = ours.all() | theirs.all();
+    // Squares an enemy pawn guards are no place to go.
+    let unsafe_squares = ours.all() | west(theirs.pawns >> 8) | east(theirs.pawns >> 8);
+
+    let mut mobility = Score::ZERO;
+    let mut activity = Score::ZERO;
+    let mut reach = |bits: u64, piece: PieceType, (bonus, usual): (Score, i32)| {
+        let mut squares = Vec::new();
+        let mut bits = bits;
+        while bits != 0 {
+            let square = bits & bits.wrapping_neg();
+            bits &= bits - 1;
+            let safe = piece_attacks(piece.clone(), square, occupied) & !unsafe_squares;
+            let count = safe.count_ones() as i32;
+            mobility += bonus * (count - usual);
+            squares.push((square, count));
+        }
+        squares
+    };
+
+    let knights = reach(
+        ours.knights,
+        PieceType::Knight,
+        (p.knight_mobility, p.knight_usual_squares),
+    );
+    let bishops = reach(
+        ours.bishops,
+        PieceType::Bishop,
+        (p.bishop_mobility, p.bishop_usual_squares),
+    );
+    let rooks = reach(
+        ours.rooks,
+        PieceType::Rook,
+        (p.rook_mobility, p.rook_usual_squares),
+    );
+    reach(
+        ours.queens,
+        PieceType::Queen,
+        (p.queen_mobility, p.queen_usual_squares),
+    );
+
+    // The pair is only worth having if it covers squares of both colours.
+    if ours.bishops & LIGHT != 0 && ours.bishops & !LIGHT != 0 {
+        activity += p.bishop_pair;
+    }
+
+    // Squares no enemy pawn can ever attack, and those our pawns guard.
+    let their_pawn_reach = south_fill(west(theirs.pawns) | east(theirs.pawns)) >> 8;
+    let our_pawn_guard = west(ours.pawns << 8) | east(ours.pawns << 8);
+    for (knight, _) in knights {
+        if knight & OUTPOST_RANKS & our_pawn_guard & !their_pawn_reach != 0 {
+            activity += p.knight_outpost;
+        }
+    }
+
+    // A bishop on a7 (h7) is caught by a pawn on b6 (g6).
+    for (bishop, _) in bishops {
+        if (bishop == 1 << 55 && theirs.pawns & 1 << 46 != 0)
+            || (bishop == 1 << 48 && theirs.pawns & 1 << 41 != 0)
+        {
+            activity += p.trapped_bishop;
+        }
+    }
+
+    for (rook, count) in rooks {
+        let file = north_fill(rook) | south_fill(rook);
+        if file & ours.pawns == 0 {
+            activity += if file & theirs.pawns == 0 {
+                p.rook_open_file
+            } else {
+                p.rook_semi_open_file
+            };
+        }
+        if rook & RANK_7 != 0 && (theirs.king & RANK_8 != 0 || theirs.pawns & RANK_7 != 0) {
+            activity += p.rook_on_seventh;
+        }
+        if count <= 3 && rook & RANK_1 != 0 && ours.king & RANK_1 != 0 {
+            // Files count from a, so a larger file is further towards h.
+            let file = |bits: u64| 7 - bits.trailing_zeros() % 8;
+            let (king_file, rook_file) = (file(ours.king), file(rook));
+            if (king_file > 4 && rook_file > king_file) || (king_file < 4 && rook_file < king_file)
+            {
+                activity += p.trapped_rook;
+            }
+        }
+    }
+
+    (mobility, activity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamemanager::GameManager;
+
+    /// Scores white's mobility and activity in `fen`.
+    fn white(fen: &str) -> (Score, Score) {
+        let bb = GameManager::from_fen_str(fen).bitboard;
+        let white = Army {
+            pawns: bb.pawns_white,
+            knights: bb.knights_white,
+            bishops: bb.bishops_white,
+            rooks: bb.rooks_white,
+            queens: bb.queens_white,
+            king: bb.king_white,
+        };
+        let black = Army {
+            pawns: bb.pawns_black,
+            knights: bb.knights_black,
+            bishops: bb.bishops_black,
+            rooks: bb.rooks_black,
+            queens: bb.queens_black,
+            king: bb.king_black,
+        };
+        mobility(&EvalParams::default(), &white, &black)
+    }
+
+    #[test]
+    fn free_pieces_are_more_mobile() {
+        let (centre, _) = white("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1");
+        let (corner, _) = white("4k3/8/8/8/8/8/8/N3K3 w - - 0 1");
+        let (guarded, _) = white("4k3/8/2p1p3/8/3N4/8/8/4K3 w - - 0 1");
+        assert!(centre.mg() > corner.mg());
+        assert!(centre.mg() > guarded.mg());
+    }
+
+    #[test]
+    fn rewards_active_pieces() {
+        let p = EvalParams::default();
+        let (_, pair) = white("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1");
+        let (_, single) = white("4k3/8/8/8/8/8/8/4KB2 w - - 0 1");
+        assert_eq!(pair - single, p.bishop_pair);
+        let (_, same_colour) = white("4k3/8/8/8/8/8/8/3BKB2 w - - 0 1");
+        assert_eq!(same_colour, single);
+
+        let (_, open) = white("4k3/pp6/8/8/8/8/PP6/3RK3 w - - 0 1");
+        let (_, closed) = white("4k3/pp1p4/8/8/8/8/PP1P4/3RK3 w - - 0 1");
+        assert_eq!(open - closed, p.rook_open_file);
+
+        let (_, outpost) = white("4k3/p7/8/3N4/2P5/8/8/4K3 w - - 0 1");
+        let (_, chased) = white("4k3/p3p3/8/3N4/2P5/8/8/4K3 w - - 0 1");
+        assert_eq!(outpost - chased, p.knight_outpost);
+    }
+
+    #[test]
+    fn punishes_trapped_pieces() {
+        let p = EvalParams::default();
+        let (_, trapped) = white("4k3/B7/1pp5/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(trapped, p.trapped_bishop);
+
+        let (_, hemmed) = white("4k3/8/8/8/8/8/6PP/5K1R w - - 0 1");
+        let (_, castled) = white("4k3/8/8/8/8/8/6PP/5RK1 w - - 0 1");
+        assert!(hemmed.mg() < castled.mg());
+    }
+}
@@ -1,114 +1,382 @@
-use heatmaps::Heatmap;
+use std::fmt::{self, Display};
+
+use endgames::{Endgame, Verdict, NORMAL_SCALE};
+use king_safety::Attackers;
+use mobility::Army;
+use params::{with_params, EvalParams};
+use pawns::PawnEntry;
+use psqt::Psqt;
+use score::{taper, Score, MAX_PHASE};
 
 use crate::{
-    bitboard::BitBoard,
-    types::{Color, MoveType, PieceType},
+    movetable::MOVE_TABLE,
+    types::{Color, PieceType},
 };
 
 use super::GameManager;
 
+pub mod endgames;
 mod heatmaps;
+mod king_safety;
+pub mod kpk;
+mod mobility;
+pub mod nnue;
+pub mod params;
+mod pawns;
+pub mod psqt;
+pub mod score;
+
+/// One side's evaluation, term by term.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalTerms {
+    /// The worth of the pieces.
+    pub material: Score,
+    /// Where the pieces stand, by the piece-square tables.
+    pub position: Score,
+    /// Pawn structure, passed pawns included.
+    pub pawns: Score,
+    /// King shelter and the enemy pieces attacking the king.
+    pub king: Score,
+    /// The safe squares the pieces can reach.
+    pub mobility: Score,
+    /// Bishop pair, rooks on open files, outposts and trapped pieces.
+    pub pieces: Score,
+}
 
-const START_MASS: i32 = PieceType::Queen as i32
-    + PieceType::Rook as i32 * 2
-    + PieceType::Bishop as i32 * 2
-    + PieceType::Knight as i32 * 2
-    + PieceType::Pawn as i32 * 8;
+impl EvalTerms {
+    pub fn total(&self) -> Score {
+        self.material + self.position + self.pawns + self.king + self.mobility + self.pieces
+    }
+}
 
 impl GameManager {
-    pub fn evaluate(&self, movetype: MoveType) -> i32 {
-        if self.white_to_move {
-            // Evaluate for black; better or worse after its move?
-            let mass_score = self.bitboard.piece_mass(Color::Black);
-            let heatmap = heatmaps::Heatmap::default();
+    /// Scores the position from the point of view of the side to move: the
+    /// worth of its pieces, their placement and activity, its pawn structure
+    /// and the safety of its king, less that of the opponent's. With the NNUE
+    /// switched on and a network loaded, the network scores it instead.
+    pub fn evaluate(&self) -> i32 {
+        if nnue::enabled() {
+            if let Some(eval) = nnue::evaluate(self) {
+                return eval;
+            }
+        }
+        self.evaluate_traced().eval
+    }
+
+    /// Evaluates the position like `evaluate()`, keeping every term of both
+    /// sides' scores and the phase they were blended by.
+    pub fn evaluate_traced(&self) -> EvalTrace {
+        with_params(|p| {
+            let psqt = if self.psqt.is_current() {
+                debug_assert!(
+                    self.psqt.matches(&Psqt::compute(&self.bitboard, p)),
+                    "incremental material and piece-square scores went astray"
+                );
+                self.psqt
+            } else {
+                Psqt::compute(&self.bitboard, p)
+            };
+            self.trace(p, &pawns::probe(&self.bitboard, p), &psqt)
+        })
+    }
 
-            // endgame_weight shows how close we are to the endgame. The more pieces our side has, the further
-            // it is from the end of the game, and the fewer, the closer to the end of the game.
-            let endgame_weight = ((mass_score as f32 * 100.0) / START_MASS as f32) as i32;
+    /// Evaluates the position with parameters other than the ones in use,
+    /// without touching the pawn hash table, for trying out new weights.
+    pub fn evaluate_with(&self, p: &EvalParams) -> EvalTrace {
+        self.trace(
+            p,
+            &pawns::score(&self.bitboard, p),
+            &Psqt::compute(&self.bitboard, p),
+        )
+    }
 
-            let position_score =
-                eval_heatmaps(Color::Black, self.bitboard, heatmap, endgame_weight);
+    fn trace(&self, p: &EvalParams, pawns: &PawnEntry, psqt: &Psqt) -> EvalTrace {
+        let white = self.eval_terms(Color::White, pawns, psqt, p);
+        let black = self.eval_terms(Color::Black, pawns, psqt, p);
+        let phase = self.phase_with(p);
+        let (mg, eg) = white.total().difference(black.total());
+        let endgame = endgames::probe(p, self);
+        let eval = match endgame.map(|endgame| endgame.verdict) {
+            Some(Verdict::Exact(score)) => score,
+            Some(Verdict::Scale(scale)) => taper(mg, eg * scale / NORMAL_SCALE, phase),
+            None => taper(mg, eg, phase),
+        };
+        EvalTrace {
+            white,
+            black,
+            phase,
+            endgame,
+            eval: if self.white_to_move { eval } else { -eval },
+        }
+    }
 
-            position_score + mass_score + movetype_weight(movetype)
-        } else {
-            // Ditto for white.
-            let mass_score = self.bitboard.piece_mass(Color::White);
-            let heatmap = heatmaps::Heatmap::default().rev();
+    /// How far the game is from the endgame, judged by the non-pawn material
+    /// left on the board: `MAX_PHASE` with all of it, zero with none.
+    pub fn phase(&self) -> i32 {
+        with_params(|p| self.phase_with(p))
+    }
 
-            let endgame_weight = ((mass_score as f32 * 100.0) / START_MASS as f32) as i32;
+    fn phase_with(&self, p: &EvalParams) -> i32 {
+        let bb = &self.bitboard;
+        let count = |bits: u64| bits.count_ones() as i32;
+        let phase = (count(bb.knights_white) + count(bb.knights_black)) * p.knight_phase
+            + (count(bb.bishops_white) + count(bb.bishops_black)) * p.bishop_phase
+            + (count(bb.rooks_white) + count(bb.rooks_black)) * p.rook_phase
+            + (count(bb.queens_white) + count(bb.queens_black)) * p.queen_phase;
+        phase.min(MAX_PHASE)
+    }
 
-            let position_score =
-                eval_heatmaps(Color::White, self.bitboard, heatmap, endgame_weight);
+    /// Returns the scores of `color`'s pieces, given the pawn structure and
+    /// the material and piece-square scores.
+    fn eval_terms(
+        &self,
+        color: Color,
+        pawn_entry: &PawnEntry,
+        psqt: &Psqt,
+        p: &EvalParams,
+    ) -> EvalTerms {
+        let bb = &self.bitboard;
+        let white = [
+            bb.pawns_white,
+            bb.knights_white,
+            bb.bishops_white,
+            bb.rooks_white,
+            bb.queens_white,
+            bb.king_white,
+        ];
+        let black = [
+            bb.pawns_black,
+            bb.knights_black,
+            bb.bishops_black,
+            bb.rooks_black,
+            bb.queens_black,
+            bb.king_black,
+        ];
+        let occupied = white.iter().chain(&black).fold(0, |all, bits| all | bits);
+        let (ours, theirs, side) = match color {
+            Color::White => (white, black, 0),
+            Color::Black => (black, white, 1),
+        };
+        // Pawn structure and king safety see the board from `color`'s side.
+        let relative = |bits: u64| match color {
+            Color::White => bits,
+            Color::Black => bits.swap_bytes(),
+        };
+        let [pawns, .., king] = ours;
 
-            position_score + mass_score + movetype_weight(movetype)
+        let pawn_structure = pawn_entry.scores[side]
+            + pawns::passed_extras(
+                p,
+                pawn_entry.passed[side],
+                relative(king),
+                relative(theirs[5]),
+                relative(occupied),
+            );
+
+        let enemy = Attackers {
+            knights: relative(theirs[1]),
+            bishops: relative(theirs[2]),
+            rooks: relative(theirs[3]),
+            queens: relative(theirs[4]),
+        };
+        let king_safety = king_safety::king_safety(
+            p,
+            relative(king),
+            relative(pawns),
+            relative(theirs[0]),
+            &enemy,
+            relative(occupied),
+        );
+
+        let army = |[pawns, knights, bishops, rooks, queens, king]: [u64; 6]| Army {
+            pawns: relative(pawns),
+            knights: relative(knights),
+            bishops: relative(bishops),
+            rooks: relative(rooks),
+            queens: relative(queens),
+            king: relative(king),
+        };
+        let (mobility, pieces) = mobility::mobility(p, &army(ours), &army(theirs));
+
+        EvalTerms {
+            material: psqt.material[side],
+            position: psqt.position[side],
+            pawns: pawn_structure,
+            king: king_safety,
+            mobility,
+            pieces,
         }
     }
 }
 
-fn movetype_weight(mt: MoveType) -> i32 {
-    use MoveType::*;
-    use PieceType::*;
-    match mt {
-        QPromotion => Queen as i32,
-        QPromoCapture => Queen as i32 + 50,
-        RPromotion => Rook as i32,
-        RPromoCapture => Rook as i32 + 50,
-        BPromotion => Bishop as i32,
-        BPromoCapture => Bishop as i32 + 50,
-        NPromotion => Knight as i32,
-        NPromoCapture => Knight as i32 + 50,
-        Capture => 25000,
-        _ => 0,
-    }
+/// The evaluation of a position, term by term, as returned by
+/// `GameManager::evaluate_traced()`. Displays as a table, for debugging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalTrace {
+    pub white: EvalTerms,
+    pub black: EvalTerms,
+    /// The phase the scores were tapered by; see `GameManager::phase()`.
+    pub phase: i32,
+    /// The endgame recognized on the board, which overrides or scales the
+    /// scores above.
+    pub endgame: Option<Endgame>,
+    /// The final evaluation, for the side to move.
+    pub eval: i32,
 }
 
-fn eval_heatmaps(color: Color, board: BitBoard, map: Heatmap, endgame_weight: i32) -> i32 {
-    let base_value = match color {
-        Color::Black => {
-            eval_heatmap(map.knights, board.knights_black)
-                + eval_heatmap(map.bishops, board.bishops_black)
-                + eval_heatmap(map.rooks, board.rooks_black)
-                + eval_heatmap(map.queens, board.queens_black)
+impl Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (white, black) = (&self.white, &self.black);
+        let row = |f: &mut fmt::Formatter<'_>, term: &str, white: Score, black: Score| {
+            let (mg, eg) = white.difference(black);
+            writeln!(
+                f,
+                " {:>11} | {:>6} {:>6} | {:>6} {:>6} | {:>6}",
+                term,
+                white.mg(),
+                white.eg(),
+                black.mg(),
+                black.eg(),
+                taper(mg, eg, self.phase)
+            )
+        };
+        writeln!(f, "        Term |     White     |     Black     |  Blend")?;
+        writeln!(f, "             |     MG     EG |     MG     EG |  (W-B)")?;
+        writeln!(f, "-------------+---------------+---------------+-------")?;
+        row(f, "Material", white.material, black.material)?;
+        row(f, "PST", white.position, black.position)?;
+        row(f, "Pawns", white.pawns, black.pawns)?;
+        row(f, "King safety", white.king, black.king)?;
+        row(f, "Mobility", white.mobility, black.mobility)?;
+        row(f, "Pieces", white.pieces, black.pieces)?;
+        writeln!(f, "-------------+---------------+---------------+-------")?;
+        row(f, "Total", white.total(), black.total())?;
+        writeln!(f)?;
+        writeln!(f, "Phase: {} / {}", self.phase, MAX_PHASE)?;
+        if let Some(endgame) = self.endgame {
+            writeln!(f, "Endgame: {} ({:?})", endgame.name, endgame.verdict)?;
         }
-        Color::White => {
-            eval_heatmap(map.knights, board.knights_white)
-                + eval_heatmap(map.bishops, board.bishops_white)
-                + eval_heatmap(map.rooks, board.rooks_white)
-                + eval_heatmap(map.queens, board.queens_white)
-        }
-    };
-
-    let weighted_value = match color {
-        Color::Black => {
-            eval_heatmap(map.pawns_start, board.pawns_black) * (100 - endgame_weight)
-                + eval_heatmap(map.pawns_end, board.pawns_black) * endgame_weight
-                + eval_heatmap(map.kings_start, board.king_black) * (100 - endgame_weight)
-                + eval_heatmap(map.kings_end, board.king_black) * endgame_weight
-        }
-        Color::White => {
-            eval_heatmap(map.pawns_start, board.pawns_white) * (100 - endgame_weight)
-                + eval_heatmap(map.pawns_end, board.pawns_white) * endgame_weight
-                + eval_heatmap(map.kings_start, board.king_white) * (100 - endgame_weight)
-                + eval_heatmap(map.kings_end, board.king_white) * endgame_weight
+        write!(f, "Evaluation: {} (for the side to move)", self.eval)
+    }
+}
+
+/// Returns the squares attacked by a `piece` standing on the single bit
+/// `square`, as far as the `occupied` squares let it see.
+fn piece_attacks(piece: PieceType, square: u64, occupied: u64) -> u64 {
+    let mut attacks = 0;
+    for ray in MOVE_TABLE.get_moves(Color::White, piece, square) {
+        for target in ray {
+            attacks |= target;
+            if target & occupied != 0 {
+                break;
+            }
         }
-    };
+    }
+    attacks
+}
 
-    base_value + weighted_value
+/// Positions each one special move on from the last, as (parent, child)
+/// pairs: en passant, castling on both sides and promotions that capture,
+/// for both colours. For testing incremental updates against refreshes.
+#[cfg(test)]
+pub(super) fn special_moves() -> Vec<(GameManager, GameManager)> {
+    use crate::{movetable::MOVE_TABLE, types::uci_move_string};
+
+    let cases = [
+        ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"),
+        ("4k3/8/8/8/3Pp3/8/8/4K3 b - d3 0 1", "e4d3"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1c1"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8g8"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8"),
+        ("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q"),
+        ("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8n"),
+        ("4k3/8/8/8/8/8/p7/1N2K3 b - - 0 1", "a2b1r"),
+    ];
+    cases
+        .iter()
+        .map(|(fen, uci)| {
+            let parent = GameManager::from_fen_str(fen);
+            let (_, _, _, _, child) = parent
+                .legal_moves(&MOVE_TABLE)
+                .into_iter()
+                .find(|mv| uci_move_string(mv.1, mv.2, mv.3) == *uci)
+                .unwrap_or_else(|| panic!("{uci} is not legal in {fen}"));
+            (parent, child)
+        })
+        .collect()
 }
 
-fn eval_heatmap(table: [i32; 64], bits: u64) -> i32 {
-    let mut score = 0;
-    let split_bits = split_bits(bits);
-    for idx in 0..64 {
-        score += table[idx] * split_bits[idx] as i32;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirrored_positions_score_the_same() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+        for fen in fens {
+            let gm = GameManager::from_fen_str(fen);
+            assert_eq!(gm.evaluate(), gm.flipped().evaluate(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn trace_adds_up() {
+        let gm = GameManager::from_fen_str(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+        );
+        let trace = gm.evaluate_traced();
+        assert_eq!(trace.eval, gm.evaluate());
+        assert_eq!(trace.phase, gm.phase());
+        assert_eq!(
+            -trace.eval,
+            (trace.white.total() - trace.black.total()).taper(trace.phase)
+        );
+
+        // A flipped position swaps the sides' terms.
+        let flipped = gm.flipped().evaluate_traced();
+        assert_eq!((flipped.white, flipped.black), (trace.black, trace.white));
+
+        let table = trace.to_string();
+        for term in [
+            "Material",
+            "PST",
+            "Pawns",
+            "King safety",
+            "Mobility",
+            "Pieces",
+        ] {
+            assert!(table.contains(term), "{term} in {table}");
+        }
+        assert!(table.ends_with(&format!(
+            "Evaluation: {} (for the side to move)",
+            trace.eval
+        )));
+    }
+
+    #[test]
+    fn phase_follows_material() {
+        assert_eq!(GameManager::default().phase(), MAX_PHASE);
+        let rooks = GameManager::from_fen_str("r3k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+        assert_eq!(rooks.phase(), 2 * EvalParams::default().rook_phase);
+        assert_eq!(
+            GameManager::from_fen_str("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1").phase(),
+            0
+        );
     }
-    score
-}
 
-fn split_bits(int: u64) -> [u8; 64] {
-    let mut bits = [0u8; 64];
-    for idx in 0..64 {
-        bits[idx] = ((int >> idx) & 1) as u8;
+    #[test]
+    fn scores_are_side_relative() {
+        // White is a queen up; that's good for white and bad for black.
+        let white = GameManager::from_fen_str("4k3/8/8/8/8/8/8/3QK3 w - - 0 1");
+        let black = GameManager::from_fen_str("4k3/8/8/8/8/8/8/3QK3 b - - 0 1");
+        assert!(white.evaluate() > 0);
+        assert_eq!(white.evaluate(), -black.evaluate());
     }
-    bits
 }
@@ -0,0 +1,333 @@
+//! An efficiently updatable neural network (NNUE), usable in place of the
+//! handcrafted evaluation.
+//!
+//! The network is `(768 -> HIDDEN) x 2 -> 1`. Its inputs are one per piece
+//! type, color and square, seen from each side in turn: from white's point of
+//! view as the board stands, and from black's with the board flipped and the
+//! colors swapped. Both views go through the same hidden layer, whose sums
+//! (the accumulators) are kept up to date as moves are made rather than
+//! worked out afresh, and the output layer reads them side to move first.
+//!
+//! Network files hold the magic bytes `SWNN`, the hidden layer size as a
+//! little-endian `u32`, and then little-endian `i16`s: the hidden weights,
+//! feature by feature, the hidden biases, the output weights for the side to
+//! move and the other side, and the output bias. Features are numbered as
+//! `color * 384 + piece * 64 + square`, with color 0 for the side whose view
+//! it is, pieces in the order pawn, knight, bishop, rook, queen, king, and
+//! squares from a1 = 0 along each rank to h8 = 63.
+
+use std::cell::RefCell;
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, LazyLock, RwLock};
+
+use crate::bitboard::BitBoard;
+
+use super::{psqt::pieces, GameManager};
+
+/// The size of the hidden layer.
+pub const HIDDEN: usize = 128;
+const FEATURES: usize = 768;
+const MAGIC: &[u8; 4] = b"SWNN";
+
+/// The hidden layer's activations are clipped to `0..=QA`, and the output
+/// weights are scaled up by `QB`.
+const QA: i32 = 255;
+const QB: i32 = 64;
+/// Turns the network's output into centipawns.
+const SCALE: i32 = 400;
+
+/// The hidden layer's sums from white's and black's points of view, and
+/// the generation of the network they were worked out with. Generation
+/// zero means they were never worked out.
+#[derive(Clone, Copy)]
+pub struct Accumulator {
+    values: [[i16; HIDDEN]; 2],
+    generation: u32,
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Self {
+            values: [[0; HIDDEN]; 2],
+            generation: 0,
+        }
+    }
+}
+
+/// A network's quantized weights.
+pub struct Network {
+    feature_weights: Vec<[i16; HIDDEN]>,
+    feature_biases: [i16; HIDDEN],
+    output_weights: [[i16; HIDDEN]; 2],
+    output_bias: i16,
+}
+
+impl Network {
+    /// Reads a network from the file at `path`.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|err| format!("{path}: {err}"))?;
+        Self::from_bytes(&bytes).map_err(|err| format!("{path}: {err}"))
+    }
+
+    /// Reads a network in the format described at the top of this module.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 8 || &bytes[..4] != MAGIC {
+            return Err(String::from("not a network file"));
+        }
+        let hidden = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        if hidden != HIDDEN {
+            return Err(format!(
+                "the hidden layer has {hidden} neurons, not {HIDDEN}"
+            ));
+        }
+        let expected = FEATURES * HIDDEN + HIDDEN + 2 * HIDDEN + 1;
+        if bytes.len() != 8 + 2 * expected {
+            return Err(format!("expected {expected} weights"));
+        }
+        let mut values = bytes[8..]
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]));
+
+        let mut next_row = || {
+            let mut row = [0; HIDDEN];
+            for (weight, value) in row.iter_mut().zip(&mut values) {
+                *weight = value;
+            }
+            row
+        };
+        let feature_weights = (0..FEATURES).map(|_| next_row()).collect();
+        let feature_biases = next_row();
+        let output_weights = [next_row(), next_row()];
+        Ok(Self {
+            feature_weights,
+            feature_biases,
+            output_weights,
+            output_bias: values.next().unwrap_or_default(),
+        })
+    }
+
+    /// Writes the network in the format `from_bytes()` reads.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend((HIDDEN as u32).to_le_bytes());
+        let rows = self
+            .feature_weights
+            .iter()
+            .chain([&self.feature_biases])
+            .chain(&self.output_weights);
+        for row in rows {
+            bytes.extend(row.iter().flat_map(|w| w.to_le_bytes()));
+        }
+        bytes.extend(self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    /// Works out both accumulators for `board` from scratch.
+    fn refresh(&self, board: &BitBoard) -> [[i16; HIDDEN]; 2] {
+        let mut values = [self.feature_biases; 2];
+        for (color, piece, mut bits) in pieces(board) {
+            while bits != 0 {
+                let bit = bits.trailing_zeros();
+                bits &= bits - 1;
+                self.add(&mut values, color, piece, bit);
+            }
+        }
+        values
+    }
+
+    /// Brings `values` from `before` up to date with `after`, adding and
+    /// removing only the pieces that moved, appeared or were taken.
+    fn update(&self, values: &mut [[i16; HIDDEN]; 2], before: &BitBoard, after: &BitBoard) {
+        for ((color, piece, old), (_, _, new)) in pieces(before).into_iter().zip(pieces(after)) {
+            let (mut removed, mut added) = (old & !new, new & !old);
+            while removed != 0 {
+                self.sub(values, color, piece, removed.trailing_zeros());
+                removed &= removed - 1;
+            }
+            while added != 0 {
+                self.add(values, color, piece, added.trailing_zeros());
+                added &= added - 1;
+            }
+        }
+    }
+
+    fn add(&self, values: &mut [[i16; HIDDEN]; 2], color: usize, piece: usize, bit: u32) {
+        for (view, features) in values.iter_mut().zip(features(color, piece, bit)) {
+            for (value, weight) in view.iter_mut().zip(&self.feature_weights[features]) {
+                *value = value.wrapping_add(*weight);
+            }
+        }
+    }
+
+    fn sub(&self, values: &mut [[i16; HIDDEN]; 2], color: usize, piece: usize, bit: u32) {
+        for (view, features) in values.iter_mut().zip(features(color, piece, bit)) {
+            for (value, weight) in view.iter_mut().zip(&self.feature_weights[features]) {
+                *value = value.wrapping_sub(*weight);
+            }
+        }
+    }
+
+    /// The network's evaluation from accumulators, for the side to move.
+    fn output(&self, values: &[[i16; HIDDEN]; 2], white_to_move: bool) -> i32 {
+        let (us, them) = if white_to_move {
+            (&values[0], &values[1])
+        } else {
+            (&values[1], &values[0])
+        };
+        let mut sum = 0_i32;
+        for (view, weights) in [us, them].into_iter().zip(&self.output_weights) {
+            for (value, weight) in view.iter().zip(weights) {
+                sum += (*value as i32).clamp(0, QA) * *weight as i32;
+            }
+        }
+        (sum + self.output_bias as i32 * QA) * SCALE / (QA * QB)
+    }
+}
+
+/// The features a piece on bit `bit` turns on in white's and black's views.
+fn features(color: usize, piece: usize, bit: u32) -> [usize; 2] {
+    // Our bit 0 is h1, where the network's square 0 is a1.
+    let square = (bit ^ 7) as usize;
+    [
+        color * 384 + piece * 64 + square,
+        (1 - color) * 384 + piece * 64 + (square ^ 56),
+    ]
+}
+
+/// The network in use, if any, replaced by `set_network()`.
+static NETWORK: LazyLock<RwLock<Option<Arc<Network>>>> = LazyLock::new(|| RwLock::new(None));
+
+/// Bumped every time the network changes, so that accumulators worked out
+/// with an old one get worked out again.
+static GENERATION: AtomicU32 = AtomicU32::new(0);
+
+/// Whether `GameManager::evaluate()` should use the network.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// This thread's copy of the network, and the generation it's from.
+    static LOCAL: RefCell<(u32, Option<Arc<Network>>)> = RefCell::new(fetch());
+}
+
+fn fetch() -> (u32, Option<Arc<Network>>) {
+    let generation = GENERATION.load(Ordering::Acquire);
+    (generation, NETWORK.read().unwrap().clone())
+}
+
+/// Makes `network` the one in use, or leaves none in use.
+pub fn set_network(network: Option<Network>) {
+    *NETWORK.write().unwrap() = network.map(Arc::new);
+    GENERATION.fetch_add(1, Ordering::Release);
+}
+
+/// Switches the evaluation between the network and the handcrafted terms.
+/// Without a network, the handcrafted evaluation is used regardless.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Calls `f` with the network in use and its generation, if there is one.
+fn with_network<R>(f: impl FnOnce(&Network, u32) -> R) -> Option<R> {
+    LOCAL.with(|local| {
+        let mut local = local.borrow_mut();
+        if local.0 != GENERATION.load(Ordering::Acquire) {
+            *local = fetch();
+        }
+        let (generation, network) = &*local;
+        network.as_deref().map(|network| f(network, *generation))
+    })
+}
+
+/// The network's evaluation of `gm` for the side to move, if a network is
+/// loaded, whether or not it's enabled.
+pub fn evaluate(gm: &GameManager) -> Option<i32> {
+    with_network(|network, generation| {
+        if gm.accumulator.generation == generation {
+            network.output(&gm.accumulator.values, gm.white_to_move)
+        } else {
+            network.output(&network.refresh(&gm.bitboard), gm.white_to_move)
+        }
+    })
+}
+
+/// Updates the accumulator of `child`, a position one move on from `parent`
+/// that started out with a copy of its accumulator. Does nothing unless the
+/// network is enabled.
+pub fn update(parent: &GameManager, child: &mut GameManager) {
+    if !enabled() {
+        return;
+    }
+    with_network(|network, generation| {
+        if parent.accumulator.generation == generation {
+            network.update(
+                &mut child.accumulator.values,
+                &parent.bitboard,
+                &child.bitboard,
+            );
+        } else {
+            child.accumulator.values = network.refresh(&child.bitboard);
+        }
+        child.accumulator.generation = generation;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamemanager::evaluation::special_moves;
+
+    /// A network with made-up, but repeatable, weights.
+    fn network() -> Network {
+        let mut seed = 0x2545F491_u32;
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend((HIDDEN as u32).to_le_bytes());
+        for _ in 0..FEATURES * HIDDEN + 3 * HIDDEN + 1 {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            bytes.extend((((seed % 61) as i16) - 30).to_le_bytes());
+        }
+        Network::from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn reads_and_writes_files() {
+        let net = network();
+        let bytes = net.to_bytes();
+        assert_eq!(Network::from_bytes(&bytes).unwrap().to_bytes(), bytes);
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 2]).is_err());
+        assert!(Network::from_bytes(b"NOPE").is_err());
+    }
+
+    #[test]
+    fn updates_match_refreshes() {
+        let net = network();
+        for (parent, child) in special_moves() {
+            let mut values = net.refresh(&parent.bitboard);
+            net.update(&mut values, &parent.bitboard, &child.bitboard);
+            assert_eq!(values, net.refresh(&child.bitboard));
+
+            // Taking the move back restores the parent's accumulator.
+            net.update(&mut values, &child.bitboard, &parent.bitboard);
+            assert_eq!(values, net.refresh(&parent.bitboard));
+        }
+    }
+
+    #[test]
+    fn mirrored_positions_score_the_same() {
+        let net = network();
+        let gm = GameManager::from_fen_str(
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        );
+        let flipped = gm.flipped();
+        assert_eq!(
+            net.output(&net.refresh(&gm.bitboard), gm.white_to_move),
+            net.output(&net.refresh(&flipped.bitboard), flipped.white_to_move)
+        );
+    }
+}
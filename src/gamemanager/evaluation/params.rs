@@ -0,0 +1,558 @@
+//! Every weight the evaluation uses, gathered in one place so they can be
+//! saved to and loaded from a file instead of being compiled in.
+//!
+//! The file format is plain text, one parameter per line: its name followed
+//! by its values, with each `Score` written as its midgame and endgame values.
+//! Blank lines and lines starting with `#` are ignored, and parameters left
+//! out keep their built-in values.
+
+use std::cell::RefCell;
+use std::fs;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, RwLock};
+
+use super::{
+    heatmaps::Heatmap,
+    score::{Score, MAX_PHASE, MAX_VALUE},
+};
+
+/// The evaluation's weights. `Default` gives the built-in ones.
+#[derive(Clone)]
+pub struct EvalParams {
+    // Material and game phase.
+    pub pawn_value: Score,
+    pub knight_value: Score,
+    pub bishop_value: Score,
+    pub rook_value: Score,
+    pub queen_value: Score,
+    pub knight_phase: i32,
+    pub bishop_phase: i32,
+    pub rook_phase: i32,
+    pub queen_phase: i32,
+
+    /// The piece-square tables for white and black, in that order. Only
+    /// black's are stored in files; white's are their mirror image.
+    pub heatmaps: (Heatmap, Heatmap),
+
+    // Pawn structure; see `pawns`.
+    pub doubled: Score,
+    pub isolated: Score,
+    pub backward: Score,
+    pub connected: [Score; 8],
+    pub passed: [Score; 8],
+    pub passed_blocked: [Score; 8],
+    pub their_king_distance: i32,
+    pub our_king_distance: i32,
+
+    // King safety; see `king_safety`.
+    pub shield: [Score; 8],
+    pub storm: [Score; 8],
+    pub open_file: Score,
+    pub semi_open_file: Score,
+    pub knight_units: i32,
+    pub bishop_units: i32,
+    pub rook_units: i32,
+    pub queen_units: i32,
+    pub min_attackers: i32,
+    pub safety_table: [i32; 100],
+
+    // Mobility and piece activity; see `mobility`.
+    pub knight_mobility: Score,
+    pub bishop_mobility: Score,
+    pub rook_mobility: Score,
+    pub queen_mobility: Score,
+    pub knight_usual_squares: i32,
+    pub bishop_usual_squares: i32,
+    pub rook_usual_squares: i32,
+    pub queen_usual_squares: i32,
+    pub bishop_pair: Score,
+    pub rook_open_file: Score,
+    pub rook_semi_open_file: Score,
+    pub rook_on_seventh: Score,
+    pub knight_outpost: Score,
+    pub trapped_bishop: Score,
+    pub trapped_rook: Score,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        let black = Heatmap::default();
+        Self {
+            pawn_value: Score::new(100, 120),
+            knight_value: Score::new(300, 280),
+            bishop_value: Score::new(320, 310),
+            rook_value: Score::new(500, 540),
+            queen_value: Score::new(1000, 950),
+            knight_phase: 1,
+            bishop_phase: 1,
+            rook_phase: 2,
+            queen_phase: 4,
+
+            heatmaps: (black.rev(), black),
+
+            doubled: Score::new(-10, -25),
+            isolated: Score::new(-10, -15),
+            backward: Score::new(-8, -12),
+            connected: scores([
+                (0, 0),
+                (4, 2),
+                (6, 4),
+                (10, 8),
+                (18, 16),
+                (30, 30),
+                (50, 50),
+                (0, 0),
+            ]),
+            passed: scores([
+                (0, 0),
+                (5, 10),
+                (8, 15),
+                (15, 30),
+                (30, 55),
+                (55, 95),
+                (90, 150),
+                (0, 0),
+            ]),
+            passed_blocked: scores([
+                (0, 0),
+                (0, -3),
+                (0, -5),
+                (-3, -10),
+                (-8, -20),
+                (-15, -35),
+                (-25, -55),
+                (0, 0),
+            ]),
+            their_king_distance: 5,
+            our_king_distance: 2,
+
+            shield: scores([
+                (0, 0),
+                (20, 0),
+                (12, 0),
+                (5, 0),
+                (0, 0),
+                (0, 0),
+                (0, 0),
+                (0, 0),
+            ]),
+            storm: scores([
+                (0, 0),
+                (-10, 0),
+                (-30, -5),
+                (-20, 0),
+                (-10, 0),
+                (-5, 0),
+                (0, 0),
+                (0, 0),
+            ]),
+            open_file: Score::new(-25, 0),
+            semi_open_file: Score::new(-12, 0),
+            knight_units: 2,
+            bishop_units: 2,
+            rook_units: 3,
+            queen_units: 5,
+            min_attackers: 2,
+            // Grows with the square of the attack units, up to a cap.
+            safety_table: std::array::from_fn(|units| (units * units / 6).min(500) as i32),
+
+            knight_mobility: Score::new(4, 4),
+            bishop_mobility: Score::new(5, 5),
+            rook_mobility: Score::new(2, 4),
+            queen_mobility: Score::new(1, 2),
+            knight_usual_squares: 4,
+            bishop_usual_squares: 6,
+            rook_usual_squares: 7,
+            queen_usual_squares: 13,
+            bishop_pair: Score::new(30, 50),
+            rook_open_file: Score::new(25, 10),
+            rook_semi_open_file: Score::new(12, 6),
+            rook_on_seventh: Score::new(10, 20),
+            knight_outpost: Score::new(20, 10),
+            trapped_bishop: Score::new(-100, -100),
+            trapped_rook: Score::new(-40, -5),
+        }
+    }
+}
+
+/// A mutable view of one parameter, whatever its shape.
+pub enum Param<'a> {
+    Int(&'a mut i32),
+    Ints(&'a mut [i32]),
+    Score(&'a mut Score),
+    Scores(&'a mut [Score]),
+}
+
+impl Param<'_> {
+    /// The parameter's values, with each `Score` as its midgame and endgame values.
+    pub fn values(&self) -> Vec<i32> {
+        match self {
+            Param::Int(v) => vec![**v],
+            Param::Ints(vs) => vs.to_vec(),
+            Param::Score(s) => vec![s.mg(), s.eg()],
+            Param::Scores(ss) => ss.iter().flat_map(|s| [s.mg(), s.eg()]).collect(),
+        }
+    }
+
+    /// Replaces the parameter's values, laid out as `values()` returns them.
+    /// Every value must be within `MAX_VALUE` of zero, as a `Score` holds;
+    /// `EvalParams::check()` bounds what they add up to.
+    pub fn set(&mut self, values: &[i32]) -> Result<(), String> {
+        let expected = self.values().len();
+        if values.len() != expected {
+            return Err(format!("expected {expected} values, got {}", values.len()));
+        }
+        if let Some(v) = values
+            .iter()
+            .find(|v| !(-MAX_VALUE..=MAX_VALUE).contains(v))
+        {
+            return Err(format!(
+                "{v} is out of range; values must be from {} to {MAX_VALUE}",
+                -MAX_VALUE
+            ));
+        }
+        match self {
+            Param::Int(v) => **v = values[0],
+            Param::Ints(vs) => vs.copy_from_slice(values),
+            Param::Score(s) => **s = Score::new(values[0], values[1]),
+            Param::Scores(ss) => {
+                for (s, pair) in ss.iter_mut().zip(values.chunks(2)) {
+                    *s = Score::new(pair[0], pair[1]);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl EvalParams {
+    /// Every parameter with its name, in the order they're written to files.
+    pub fn params(&mut self) -> Vec<(&'static str, Param<'_>)> {
+        let black = &mut self.heatmaps.1;
+        vec![
+            ("pawn_value", Param::Score(&mut self.pawn_value)),
+            ("knight_value", Param::Score(&mut self.knight_value)),
+            ("bishop_value", Param::Score(&mut self.bishop_value)),
+            ("rook_value", Param::Score(&mut self.rook_value)),
+            ("queen_value", Param::Score(&mut self.queen_value)),
+            ("knight_phase", Param::Int(&mut self.knight_phase)),
+            ("bishop_phase", Param::Int(&mut self.bishop_phase)),
+            ("rook_phase", Param::Int(&mut self.rook_phase)),
+            ("queen_phase", Param::Int(&mut self.queen_phase)),
+            ("pst_pawns", Param::Scores(&mut black.pawns)),
+            ("pst_knights", Param::Scores(&mut black.knights)),
+            ("pst_bishops", Param::Scores(&mut black.bishops)),
+            ("pst_rooks", Param::Scores(&mut black.rooks)),
+            ("pst_queens", Param::Scores(&mut black.queens)),
+            ("pst_kings", Param::Scores(&mut black.kings)),
+            ("doubled", Param::Score(&mut self.doubled)),
+            ("isolated", Param::Score(&mut self.isolated)),
+            ("backward", Param::Score(&mut self.backward)),
+            ("connected", Param::Scores(&mut self.connected)),
+            ("passed", Param::Scores(&mut self.passed)),
+            ("passed_blocked", Param::Scores(&mut self.passed_blocked)),
+            (
+                "their_king_distance",
+                Param::Int(&mut self.their_king_distance),
+            ),
+            ("our_king_distance", Param::Int(&mut self.our_king_distance)),
+            ("shield", Param::Scores(&mut self.shield)),
+            ("storm", Param::Scores(&mut self.storm)),
+            ("open_file", Param::Score(&mut self.open_file)),
+            ("semi_open_file", Param::Score(&mut self.semi_open_file)),
+            ("knight_units", Param::Int(&mut self.knight_units)),
+            ("bishop_units", Param::Int(&mut self.bishop_units)),
+            ("rook_units", Param::Int(&mut self.rook_units)),
+            ("queen_units", Param::Int(&mut self.queen_units)),
+            ("min_attackers", Param::Int(&mut self.min_attackers)),
+            ("safety_table", Param::Ints(&mut self.safety_table)),
+            ("knight_mobility", Param::Score(&mut self.knight_mobility)),
+            ("bishop_mobility", Param::Score(&mut self.bishop_mobility)),
+            ("rook_mobility", Param::Score(&mut self.rook_mobility)),
+            ("queen_mobility", Param::Score(&mut self.queen_mobility)),
+            (
+                "knight_usual_squares",
+                Param::Int(&mut self.knight_usual_squares),
+            ),
+            (
+                "bishop_usual_squares",
+                Param::Int(&mut self.bishop_usual_squares),
+            ),
+            (
+                "rook_usual_squares",
+                Param::Int(&mut self.rook_usual_squares),
+            ),
+            (
+                "queen_usual_squares",
+                Param::Int(&mut self.queen_usual_squares),
+            ),
+            ("bishop_pair", Param::Score(&mut self.bishop_pair)),
+            ("rook_open_file", Param::Score(&mut self.rook_open_file)),
+            (
+                "rook_semi_open_file",
+                Param::Score(&mut self.rook_semi_open_file),
+            ),
+            ("rook_on_seventh", Param::Score(&mut self.rook_on_seventh)),
+            ("knight_outpost", Param::Score(&mut self.knight_outpost)),
+            ("trapped_bishop", Param::Score(&mut self.trapped_bishop)),
+            ("trapped_rook", Param::Score(&mut self.trapped_rook)),
+        ]
+    }
+
+    /// Checks that the parameters can be evaluated with: that the phase
+    /// weights are from zero to `MAX_PHASE`, and that no position can bring
+    /// one side's midgame or endgame total further than `MAX_VALUE` from
+    /// zero, where its `Score` would overflow.
+    pub fn check(&self) -> Result<(), String> {
+        for (name, phase) in [
+            ("knight_phase", self.knight_phase),
+            ("bishop_phase", self.bishop_phase),
+            ("rook_phase", self.rook_phase),
+            ("queen_phase", self.queen_phase),
+        ] {
+            if !(0..=MAX_PHASE).contains(&phase) {
+                return Err(format!("{name}: must be from 0 to {MAX_PHASE}"));
+            }
+        }
+        let worst = self.worst_total(false).max(self.worst_total(true));
+        if worst > i64::from(MAX_VALUE) {
+            return Err(format!(
+                "the values could add up to {worst}, more than the {MAX_VALUE} a side's score holds"
+            ));
+        }
+        Ok(())
+    }
+
+    /// The furthest from zero one side's midgame or endgame total could get,
+    /// by adding up the largest each term could be. It overestimates, as no
+    /// position scores the worst of every term at once.
+    fn worst_total(&self, endgame: bool) -> i64 {
+        let value = |s: &Score| i64::from(if endgame { s.eg() } else { s.mg() }).abs();
+        let most = |ss: &[Score]| ss.iter().map(value).max().unwrap_or(0);
+        let int = |v: i32| i64::from(v).abs();
+        let black = &self.heatmaps.1;
+
+        // Up to 15 pieces besides the king, and 16 in all, each on its best square.
+        let material = 15
+            * most(&[
+                self.pawn_value,
+                self.knight_value,
+                self.bishop_value,
+                self.rook_value,
+                self.queen_value,
+            ]);
+        let position = 16
+            * [
+                &black.pawns,
+                &black.knights,
+                &black.bishops,
+                &black.rooks,
+                &black.queens,
+                &black.kings,
+            ]
+            .iter()
+            .map(|table| most(&table[..]))
+            .max()
+            .unwrap_or(0);
+
+        // Up to 8 pawns, each scoring every pawn term, and for a passed pawn
+        // up to 4 times each king's distance of up to 7 from its stop square.
+        let mut pawns = 8
+            * (value(&self.doubled)
+                + value(&self.isolated)
+                + value(&self.backward)
+                + most(&self.connected)
+                + most(&self.passed)
+                + most(&self.passed_blocked));
+        if endgame {
+            pawns += 8 * 4 * 7 * (int(self.their_king_distance) + int(self.our_king_distance));
+        }
+
+        // Three files of shelter, and the attack penalty, a quarter of it in
+        // the endgame.
+        let penalty = self.safety_table.iter().map(|&v| int(v)).max().unwrap_or(0);
+        let king = 3
+            * (value(&self.open_file).max(value(&self.semi_open_file))
+                + most(&self.shield)
+                + most(&self.storm))
+            + if endgame { penalty / 4 } else { penalty };
+
+        // Up to 15 pieces, each reaching anywhere from none to all the squares
+        // its kind can.
+        let mobility = 15
+            * [
+                (self.knight_mobility, self.knight_usual_squares, 8),
+                (self.bishop_mobility, self.bishop_usual_squares, 13),
+                (self.rook_mobility, self.rook_usual_squares, 14),
+                (self.queen_mobility, self.queen_usual_squares, 27),
+            ]
+            .iter()
+            .map(|(bonus, usual, reach)| value(bonus) * int(*usual).max(int(reach - usual)))
+            .max()
+            .unwrap_or(0);
+
+        // The bishop pair, and up to 15 pieces on an outpost, trapped, or
+        // rooks on an open file and the seventh rank as well.
+        let pieces = value(&self.bishop_pair)
+            + 15 * value(&self.knight_outpost)
+                .max(value(&self.trapped_bishop))
+                .max(
+                    value(&self.rook_open_file).max(value(&self.rook_semi_open_file))
+                        + value(&self.rook_on_seventh)
+                        + value(&self.trapped_rook),
+                );
+
+        material + position + pawns + king + mobility + pieces
+    }
+
+    /// Parses parameters written by `to_text()`, starting from the built-in
+    /// ones. Fails on unknown names, wrongly sized or malformed values, and
+    /// values that don't pass `check()`.
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut params = Self::default();
+        {
+            let mut fields = params.params();
+            for (number, line) in text.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let mut tokens = line.split_whitespace();
+                let name = tokens.next().unwrap_or_default();
+                let values = tokens
+                    .map(str::parse::<i32>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| format!("line {}: {name}: {err}", number + 1))?;
+                let (_, param) = fields
+                    .iter_mut()
+                    .find(|(n, _)| *n == name)
+                    .ok_or_else(|| format!("line {}: unknown parameter {name}", number + 1))?;
+                param
+                    .set(&values)
+                    .map_err(|err| format!("line {}: {name}: {err}", number + 1))?;
+            }
+        }
+        params.mirror_heatmaps();
+        params.check()?;
+        Ok(params)
+    }
+
+    /// Brings white's piece-square tables in line with black's, after
+    /// black's have been changed through `params()`.
+    pub fn mirror_heatmaps(&mut self) {
+        self.heatmaps.0 = self.heatmaps.1.rev();
+    }
+
+    /// Writes every parameter out, in the format `from_text()` reads.
+    pub fn to_text(&self) -> String {
+        let mut params = self.clone();
+        let mut text = String::from("# Swordfish evaluation parameters\n");
+        for (name, param) in params.params() {
+            let values: Vec<String> = param.values().iter().map(i32::to_string).collect();
+            text.push_str(&format!("{name} {}\n", values.join(" ")));
+        }
+        text
+    }
+
+    /// Reads parameters from the file at `path`.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?;
+        Self::from_text(&text).map_err(|err| format!("{path}: {err}"))
+    }
+
+    /// Writes the parameters to the file at `path`.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|err| format!("{path}: {err}"))
+    }
+}
+
+/// Packs a table of midgame and endgame pairs.
+fn scores<const N: usize>(pairs: [(i32, i32); N]) -> [Score; N] {
+    pairs.map(|(mg, eg)| Score::new(mg, eg))
+}
+
+/// The parameters every evaluation uses, replaced by `set_params()`.
+static PARAMS: LazyLock<RwLock<Arc<EvalParams>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(EvalParams::default())));
+
+/// Bumped every time the parameters change, so that threads know to pick
+/// up the new ones and throw away anything worked out with the old.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// This thread's copy of the parameters, and the generation it's from.
+    static LOCAL: RefCell<(u64, Arc<EvalParams>)> = RefCell::new(fetch());
+}
+
+fn fetch() -> (u64, Arc<EvalParams>) {
+    let generation = GENERATION.load(Ordering::Acquire);
+    (generation, PARAMS.read().unwrap().clone())
+}
+
+/// Makes every evaluation from now on use `params`.
+pub fn set_params(params: EvalParams) {
+    *PARAMS.write().unwrap() = Arc::new(params);
+    GENERATION.fetch_add(1, Ordering::Release);
+}
+
+/// The current generation of the parameters; see `GENERATION`.
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Acquire)
+}
+
+/// Calls `f` with the current parameters, without taking a lock unless
+/// they've changed since this thread last looked.
+pub fn with_params<R>(f: impl FnOnce(&EvalParams) -> R) -> R {
+    LOCAL.with(|local| {
+        let mut local = local.borrow_mut();
+        if local.0 != GENERATION.load(Ordering::Acquire) {
+            *local = fetch();
+        }
+        f(&local.1)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trips() {
+        let mut params = EvalParams {
+            rook_value: Score::new(510, 560),
+            ..Default::default()
+        };
+        params.heatmaps.1.knights[27] = Score::new(-7, 9);
+        params.safety_table[3] = 42;
+
+        let parsed = EvalParams::from_text(&params.to_text()).unwrap();
+        assert_eq!(parsed.to_text(), params.to_text());
+        assert_eq!(parsed.rook_value, Score::new(510, 560));
+        // White's tables follow black's.
+        assert_eq!(parsed.heatmaps.0.knights[27 ^ 56], Score::new(-7, 9));
+    }
+
+    #[test]
+    fn reads_partial_files() {
+        let params = EvalParams::from_text("# Just one\n\nbishop_pair 40 60\n").unwrap();
+        assert_eq!(params.bishop_pair, Score::new(40, 60));
+        assert_eq!(params.rook_value, EvalParams::default().rook_value);
+
+        assert!(EvalParams::from_text("no_such_thing 1").is_err());
+        assert!(EvalParams::from_text("bishop_pair 40").is_err());
+        assert!(EvalParams::from_text("bishop_pair 40 sixty").is_err());
+        assert!(EvalParams::from_text("bishop_pair 40 40000").is_err());
+        assert!(EvalParams::from_text("min_attackers -2147483648").is_err());
+    }
+
+    #[test]
+    fn bounds_what_values_add_up_to() {
+        assert!(EvalParams::default().check().is_ok());
+        // Fine alone, but sixteen pawns would be worth more than a Score holds.
+        assert!(EvalParams::from_text("pawn_value 5000 120").is_err());
+        assert!(EvalParams::from_text("queen_mobility 2000 2").is_err());
+        assert!(EvalParams::from_text("their_king_distance 200").is_err());
+        assert!(EvalParams::from_text("knight_phase -1").is_err());
+        assert!(EvalParams::from_text("queen_phase 25").is_err());
+    }
+}
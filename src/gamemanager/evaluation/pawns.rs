@@ -0,0 +1,254 @@
+//! Pawn structure: doubled, isolated, backward, connected and passed pawns.
+//!
+//! Everything here is worked out for white, on a board seen from white's
+//! side; black's pawns are scored the same way on a board flipped top to
+//! bottom. The terms that depend on the pawns alone are cached per thread,
+//! keyed by `zobrist::pawn_key()`. Passed pawns' blockers and king distances
+//! depend on the other pieces too, so they are scored afresh every time.
+
+use std::cell::RefCell;
+
+use crate::{bitboard::BitBoard, zobrist::pawn_key};
+
+use super::{
+    params::{generation, EvalParams},
+    score::Score,
+};
+
+/// The number of entries in each thread's pawn hash table.
+const PAWN_TABLE_LEN: usize = 1 << 14;
+
+pub(super) const FILE_A: u64 = 0x80808080_80808080;
+const FILE_H: u64 = 0x01010101_01010101;
+
+/// The cached pawn structure of one position.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PawnEntry {
+    key: u64,
+    /// The generation of the parameters it was scored with.
+    generation: u64,
+    /// The scores of white's and black's pawns, in that order.
+    pub scores: [Score; 2],
+    /// White's and black's passed pawns, each seen from its own side.
+    pub passed: [u64; 2],
+}
+
+thread_local! {
+    static PAWN_TABLE: RefCell<Vec<PawnEntry>> =
+        RefCell::new(vec![PawnEntry::default(); PAWN_TABLE_LEN]);
+}
+
+/// Returns the pawn structure of `board` scored with `p`, from the cache
+/// if it's there.
+///
+/// A board with no pawns has the key zero, which matches an empty entry
+/// from the first generation; that's fine, as its structure scores nothing.
+pub fn probe(board: &BitBoard, p: &EvalParams) -> PawnEntry {
+    let key = pawn_key(board);
+    let generation = generation();
+    PAWN_TABLE.with(|table| {
+        let mut table = table.borrow_mut();
+        let slot = &mut table[key as usize % PAWN_TABLE_LEN];
+        if slot.key != key || slot.generation != generation {
+            *slot = structure(p, key, board.pawns_white, board.pawns_black);
+            slot.generation = generation;
+        }
+        *slot
+    })
+}
+
+/// Returns the pawn structure of `board` scored with `p`, bypassing the cache.
+pub fn score(board: &BitBoard, p: &EvalParams) -> PawnEntry {
+    structure(p, pawn_key(board), board.pawns_white, board.pawns_black)
+}
+
+/// Scores both sides' pawns, uncached.
+fn structure(p: &EvalParams, key: u64, white: u64, black: u64) -> PawnEntry {
+    let (white_score, white_passed) = side_structure(p, white, black);
+    let (black_score, black_passed) = side_structure(p, black.swap_bytes(), white.swap_bytes());
+    PawnEntry {
+        key,
+        generation: 0,
+        scores: [white_score, black_score],
+        passed: [white_passed, black_passed],
+    }
+}
+
+/// Scores `us`'s pawns against `them`'s, with `us` moving up the board.
+/// Returns the score and `us`'s passed pawns.
+fn side_structure(p: &EvalParams, us: u64, them: u64) -> (Score, u64) {
+    let our_attacks = west(us << 8) | east(us << 8);
+    let their_attacks = west(them >> 8) | east(them >> 8);
+    let files = north_fill(us) | south_fill(us);
+
+    let doubled = us & (north_fill(us) << 8);
+    let isolated = us & !(west(files) | east(files));
+    // Squares our pawns guard, now or after pushing, can be supported.
+    let backward = us & ((their_attacks & !north_fill(our_attacks)) >> 8) & !isolated;
+    let connected = us & (our_attacks | west(us) | east(us));
+    // Enemy pawns stop anything behind them on their own and adjacent files,
+    // and a pawn with one of ours ahead of it isn't passed either.
+    let stoppers = south_fill(them | west(them) | east(them)) >> 8;
+    let passed = us & !stoppers & !(south_fill(us) >> 8);
+
+    let mut score = p.doubled * doubled.count_ones() as i32
+        + p.isolated * isolated.count_ones() as i32
+        + p.backward * backward.count_ones() as i32;
+    score += by_rank(&p.connected, connected);
+    score += by_rank(&p.passed, passed);
+    (score, passed)
+}
+
+/// Scores what a passed pawn's prospects depend on besides the pawns: a
+/// piece blocking its way, and how close each king is to its path. Takes
+/// the board from the pawns' owner's side, as `PawnEntry::passed` is.
+pub fn passed_extras(
+    p: &EvalParams,
+    passed: u64,
+    our_king: u64,
+    their_king: u64,
+    occupied: u64,
+) -> Score {
+    let mut score = Score::ZERO;
+    let mut bits = passed;
+    while bits != 0 {
+        let square = bits.trailing_zeros();
+        bits &= bits - 1;
+        let rank = square as usize / 8;
+        let stop = square + 8;
+
+        if occupied & (1 << stop) != 0 {
+            score += p.passed_blocked[rank];
+        }
+        if our_king != 0 && their_king != 0 {
+            let weight = (rank as i32 - 2).max(0);
+            let theirs = distance(their_king.trailing_zeros(), stop);
+            let ours = distance(our_king.trailing_zeros(), stop);
+            score += Score::new(
+                0,
+                (p.their_king_distance * theirs - p.our_king_distance * ours) * weight,
+            );
+        }
+    }
+    score
+}
+
+/// Sums the bonus in `table` for the rank of every pawn in `bits`.
+fn by_rank(table: &[Score; 8], mut bits: u64) -> Score {
+    let mut score = Score::ZERO;
+    while bits != 0 {
+        score += table[bits.trailing_zeros() as usize / 8];
+        bits &= bits - 1;
+    }
+    score
+}
+
+/// The number of king moves between two squares.
+pub(super) fn distance(a: u32, b: u32) -> i32 {
+    let rank = (a / 8).abs_diff(b / 8);
+    let file = (a % 8).abs_diff(b % 8);
+    rank.max(file) as i32
+}
+
+/// Shifts every bit one file towards the A file.
+pub(super) fn west(bits: u64) -> u64 {
+    (bits << 1) & !FILE_H
+}
+
+/// Shifts every bit one file towards the H file.
+pub(super) fn east(bits: u64) -> u64 {
+    (bits >> 1) & !FILE_A
+}
+
+/// Smears every bit up the board to the eighth rank.
+pub(super) fn north_fill(mut bits: u64) -> u64 {
+    bits |= bits << 8;
+    bits |= bits << 16;
+    bits | bits << 32
+}
+
+/// Smears every bit down the board to the first rank.
+pub(super) fn south_fill(mut bits: u64) -> u64 {
+    bits |= bits >> 8;
+    bits |= bits >> 16;
+    bits | bits >> 32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamemanager::GameManager;
+
+    /// Returns white's pawns and black's pawns from `fen`.
+    fn pawns(fen: &str) -> (u64, u64) {
+        let gm = GameManager::from_fen_str(fen);
+        (gm.bitboard.pawns_white, gm.bitboard.pawns_black)
+    }
+
+    #[test]
+    fn finds_weak_pawns() {
+        let p = EvalParams::default();
+        // White: doubled c-pawns and an isolated (but passed) h-pawn;
+        // black: a backward d6, its stop square guarded by e4.
+        let (white, black) = pawns("4k3/8/3p4/2p1p3/3PP3/2P5/2P4P/4K3 w - - 0 1");
+        let files = north_fill(white) | south_fill(white);
+        assert_eq!((white & (north_fill(white) << 8)).count_ones(), 1);
+        assert_eq!(white & !(west(files) | east(files)), 1 << 8);
+
+        let (_, passed) = side_structure(&p, white, black);
+        assert_eq!(passed, 1 << 8);
+
+        let (score, black_passed) = side_structure(&p, black.swap_bytes(), white.swap_bytes());
+        assert_eq!(black_passed, 0);
+        // Without e4, d6 may come forward and nothing else changes for black.
+        let (unguarded, _) =
+            side_structure(&p, black.swap_bytes(), (white & !(1 << 27)).swap_bytes());
+        assert_eq!(score - unguarded, p.backward);
+    }
+
+    #[test]
+    fn finds_passed_pawns() {
+        let p = EvalParams::default();
+        // a5 and the front e-pawn are passed; the rear e-pawn and g2 aren't.
+        let (white, black) = pawns("4k3/7p/8/P3P3/4P3/8/6P1/4K3 w - - 0 1");
+        let (_, passed) = side_structure(&p, white, black);
+        let squares = |bits: u64| bits.count_ones();
+        assert_eq!(squares(passed), 2);
+        assert_ne!(passed & (1 << 39), 0, "a5");
+        assert_ne!(passed & (1 << 35), 0, "e5");
+
+        // Further up the board is worth more.
+        let (near, _) = side_structure(&p, white & (1 << 39), 0);
+        let (far, _) = side_structure(&p, (white & (1 << 39)) << 8, 0);
+        assert!(far.eg() > near.eg());
+    }
+
+    #[test]
+    fn kings_and_blockers_matter() {
+        let p = EvalParams::default();
+        // A white pawn on e6, stop square e7.
+        let pawn = 1 << 43;
+        let near_king = 1 << 52; // d7
+        let far_king = 1 << 7; // a1
+        let escort = passed_extras(&p, pawn, near_king, far_king, pawn);
+        let chased = passed_extras(&p, pawn, far_king, near_king, pawn);
+        assert!(escort.eg() > chased.eg());
+
+        let blocked = passed_extras(&p, pawn, far_king, near_king, pawn | 1 << 51);
+        assert!(blocked.eg() < chased.eg());
+    }
+
+    #[test]
+    fn caches_by_pawn_key() {
+        let p = EvalParams::default();
+        let gm = GameManager::from_fen_str("4k3/7p/8/P3P3/4P3/8/6P1/4K3 w - - 0 1");
+        let first = probe(&gm.bitboard, &p);
+        let again = probe(&gm.bitboard, &p);
+        assert_eq!(first.key, pawn_key(&gm.bitboard));
+        assert_eq!(first.scores, again.scores);
+        assert_eq!(
+            first.scores,
+            structure(&p, 0, gm.bitboard.pawns_white, gm.bitboard.pawns_black).scores
+        );
+    }
+}
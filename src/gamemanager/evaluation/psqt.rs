@@ -0,0 +1,144 @@
+//! Material and piece-square scores, kept up to date move by move rather
+//! than summed over the whole board at every evaluation.
+
+use crate::bitboard::BitBoard;
+
+use super::{
+    params::{generation, with_params, EvalParams},
+    score::Score,
+    GameManager,
+};
+
+/// Both sides' material and piece-square scores, white's first, and the
+/// generation of the parameters they were worked out with, plus one. Zero
+/// means they were never worked out.
+#[derive(Clone, Copy, Default)]
+pub struct Psqt {
+    pub material: [Score; 2],
+    pub position: [Score; 2],
+    generation: u64,
+}
+
+impl Psqt {
+    /// Works out the scores for `board` from scratch.
+    pub fn compute(board: &BitBoard, p: &EvalParams) -> Self {
+        let mut psqt = Self::default();
+        for (side, piece, bits) in pieces(board) {
+            psqt.add(p, side, piece, bits);
+        }
+        psqt
+    }
+
+    /// Whether the scores are the same as `other`'s, whatever generations of
+    /// the parameters they came from.
+    pub fn matches(&self, other: &Self) -> bool {
+        self.material == other.material && self.position == other.position
+    }
+
+    /// Whether the scores were worked out with the parameters in use.
+    pub fn is_current(&self) -> bool {
+        self.generation == generation() + 1
+    }
+
+    fn add(&mut self, p: &EvalParams, side: usize, piece: usize, mut bits: u64) {
+        let (value, table) = weights(p, side, piece);
+        while bits != 0 {
+            self.material[side] += value;
+            self.position[side] += table[bits.trailing_zeros() as usize];
+            bits &= bits - 1;
+        }
+    }
+
+    fn sub(&mut self, p: &EvalParams, side: usize, piece: usize, mut bits: u64) {
+        let (value, table) = weights(p, side, piece);
+        while bits != 0 {
+            self.material[side] -= value;
+            self.position[side] -= table[bits.trailing_zeros() as usize];
+            bits &= bits - 1;
+        }
+    }
+}
+
+/// Updates the scores of `child`, a position one move on from `parent` that
+/// started out with a copy of its scores, for the pieces that moved,
+/// appeared or were taken.
+pub fn update(parent: &GameManager, child: &mut GameManager) {
+    with_params(|p| {
+        if parent.psqt.is_current() {
+            for ((side, piece, old), (_, _, new)) in pieces(&parent.bitboard)
+                .into_iter()
+                .zip(pieces(&child.bitboard))
+            {
+                child.psqt.sub(p, side, piece, old & !new);
+                child.psqt.add(p, side, piece, new & !old);
+            }
+        } else {
+            child.psqt = Psqt::compute(&child.bitboard, p);
+        }
+        child.psqt.generation = generation() + 1;
+    });
+}
+
+/// The worth of one of `side`'s pieces and its piece-square table.
+fn weights(p: &EvalParams, side: usize, piece: usize) -> (Score, &[Score; 64]) {
+    let heatmap = if side == 0 {
+        &p.heatmaps.0
+    } else {
+        &p.heatmaps.1
+    };
+    match piece {
+        0 => (p.pawn_value, &heatmap.pawns),
+        1 => (p.knight_value, &heatmap.knights),
+        2 => (p.bishop_value, &heatmap.bishops),
+        3 => (p.rook_value, &heatmap.rooks),
+        4 => (p.queen_value, &heatmap.queens),
+        _ => (Score::ZERO, &heatmap.kings),
+    }
+}
+
+/// Every piece bitboard on `board`, with its side (0 for white) and piece
+/// index, pawns first and kings last.
+pub(super) fn pieces(board: &BitBoard) -> [(usize, usize, u64); 12] {
+    [
+        (0, 0, board.pawns_white),
+        (0, 1, board.knights_white),
+        (0, 2, board.bishops_white),
+        (0, 3, board.rooks_white),
+        (0, 4, board.queens_white),
+        (0, 5, board.king_white),
+        (1, 0, board.pawns_black),
+        (1, 1, board.knights_black),
+        (1, 2, board.bishops_black),
+        (1, 3, board.rooks_black),
+        (1, 4, board.queens_black),
+        (1, 5, board.king_black),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamemanager::evaluation::special_moves;
+
+    #[test]
+    fn updates_match_recomputation() {
+        let p = EvalParams::default();
+        for (mut parent, child) in special_moves() {
+            assert!(!parent.psqt.is_current());
+            parent.psqt = Psqt::compute(&parent.bitboard, &p);
+            parent.psqt.generation = generation() + 1;
+
+            let mut moved = child.clone();
+            moved.psqt = parent.psqt;
+            update(&parent, &mut moved);
+            assert!(moved.psqt.is_current());
+            assert!(moved.psqt.matches(&Psqt::compute(&child.bitboard, &p)));
+
+            // Taking the move back restores the parent's scores.
+            let mut unmade = parent.clone();
+            unmade.psqt = moved.psqt;
+            update(&moved, &mut unmade);
+            assert!(unmade.psqt.matches(&parent.psqt));
+        }
+    }
+}
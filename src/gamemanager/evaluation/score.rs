@@ -0,0 +1,122 @@
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+/// The most a midgame-to-endgame phase can be; see `GameManager::phase()`.
+pub const MAX_PHASE: i32 = 24;
+
+/// The furthest either value of a `Score` may be from zero.
+pub const MAX_VALUE: i32 = i16::MAX as i32;
+
+/// A pair of midgame and endgame values packed into one integer, so that
+/// both can be summed with a single addition. The midgame value lives in the
+/// upper 16 bits and the endgame value in the lower 16, so each must stay
+/// within `MAX_VALUE` of zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Score(i32);
+
+impl Score {
+    pub const ZERO: Score = Score(0);
+
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        debug_assert!(-MAX_VALUE <= mg && mg <= MAX_VALUE && -MAX_VALUE <= eg && eg <= MAX_VALUE);
+        Score((mg << 16) + eg)
+    }
+
+    /// The midgame value.
+    pub const fn mg(self) -> i32 {
+        // Round, so that a negative endgame value's borrow is undone.
+        ((self.0 + 0x8000) >> 16) as i16 as i32
+    }
+
+    /// The endgame value.
+    pub const fn eg(self) -> i32 {
+        self.0 as i16 as i32
+    }
+
+    /// Blends the two values by `phase`, from `MAX_PHASE` for a full midgame
+    /// down to zero for a bare endgame.
+    pub fn taper(self, phase: i32) -> i32 {
+        taper(self.mg(), self.eg(), phase)
+    }
+
+    /// The midgame and endgame values of `self - other`, kept apart as they
+    /// may be up to twice `MAX_VALUE` from zero, as two sides' totals can be.
+    pub fn difference(self, other: Score) -> (i32, i32) {
+        (self.mg() - other.mg(), self.eg() - other.eg())
+    }
+}
+
+/// Blends a midgame and an endgame value as `Score::taper()` does.
+pub fn taper(mg: i32, eg: i32, phase: i32) -> i32 {
+    (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+impl Add for Score {
+    type Output = Score;
+    fn add(self, rhs: Score) -> Score {
+        Score(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, rhs: Score) {
+        self.0 += rhs.0;
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+    fn sub(self, rhs: Score) -> Score {
+        Score(self.0 - rhs.0)
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, rhs: Score) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score {
+        Score(-self.0)
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+    fn mul(self, rhs: i32) -> Score {
+        Score(self.0 * rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_both_values() {
+        for (mg, eg) in [
+            (0, 0),
+            (100, -50),
+            (-3000, 2500),
+            (-1, -1),
+            (MAX_VALUE, -MAX_VALUE),
+        ] {
+            let score = Score::new(mg, eg);
+            assert_eq!((score.mg(), score.eg()), (mg, eg));
+        }
+
+        let sum = Score::new(10, -20) + Score::new(-30, 5) * 2 - Score::new(1, 1);
+        assert_eq!((sum.mg(), sum.eg()), (-51, -11));
+        assert_eq!((-sum).mg(), 51);
+    }
+
+    #[test]
+    fn tapers_between_phases() {
+        let score = Score::new(100, -20);
+        assert_eq!(score.taper(MAX_PHASE), 100);
+        assert_eq!(score.taper(0), -20);
+        assert_eq!(score.taper(MAX_PHASE / 2), 40);
+    }
+}
@@ -33,7 +33,7 @@ impl GameManager {
                         bishops_black: (self.bitboard.bishops_black ^ from.to_u64()) | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -50,7 +50,7 @@ impl GameManager {
                             queens_white: self.bitboard.queens_white & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -183,7 +183,7 @@ impl GameManager {
                         knights_black: (self.bitboard.knights_black ^ from.to_u64()) | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -200,7 +200,7 @@ impl GameManager {
                             queens_white: self.bitboard.queens_white & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -215,7 +215,7 @@ impl GameManager {
                         bishops_black: self.bitboard.bishops_black | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -225,7 +225,7 @@ impl GameManager {
                         rooks_black: self.bitboard.rooks_black | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -235,7 +235,7 @@ impl GameManager {
                         knights_black: self.bitboard.knights_black | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -245,7 +245,7 @@ impl GameManager {
                         queens_black: self.bitboard.queens_black | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -264,7 +264,7 @@ impl GameManager {
                             queens_white: self.bitboard.queens_white & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -282,7 +282,7 @@ impl GameManager {
                             queens_white: self.bitboard.queens_white & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -300,7 +300,7 @@ impl GameManager {
                             queens_white: self.bitboard.queens_white & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -318,7 +318,7 @@ impl GameManager {
                             queens_white: self.bitboard.queens_white & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -347,7 +347,7 @@ impl GameManager {
                             pawns_white: self.bitboard.pawns_white & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -375,7 +375,7 @@ impl GameManager {
                             pawns_black: (self.bitboard.pawns_black ^ from.to_u64()) | to.to_u64(),
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: String::from(target_coord),
                         ..*self
                     }
@@ -385,7 +385,7 @@ impl GameManager {
                         pawns_black: (self.bitboard.pawns_black ^ from.to_u64()) | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -401,7 +401,7 @@ impl GameManager {
                             queens_white: self.bitboard.queens_white & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
 
                         ..*self
@@ -418,7 +418,7 @@ impl GameManager {
                         queens_black: (self.bitboard.queens_black ^ from.to_u64()) | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -434,7 +434,7 @@ impl GameManager {
                             queens_white: self.bitboard.queens_white & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -449,7 +449,7 @@ impl GameManager {
         assert!(
             retval.bitboard.king_black.is_power_of_two(),
             "{} {:?} {:?} {:#X} {:#X}\n",
-            retval.bitboard.to_string(),
+            retval.bitboard,
             retval.castling_rights.black,
             retval.castling_rights.white,
             retval.bitboard.king_black,
@@ -458,7 +458,7 @@ impl GameManager {
         assert!(
             retval.bitboard.king_white.is_power_of_two(),
             "{} {:?} {:?} {:#X} {:#X}\n",
-            retval.bitboard.to_string(),
+            retval.bitboard,
             retval.castling_rights.black,
             retval.castling_rights.white,
             retval.bitboard.king_black,
@@ -1,13 +1,17 @@
 //! This module handles filtering of pseudolegal moves, and returns only legal moves from any game state.
 
 use crate::{
-    gamemanager::*,
+    gamemanager::{
+        evaluation::{nnue, psqt},
+        *,
+    },
     types::{Color, MoveType, PieceType, Square},
 };
 
 mod black;
 pub mod perft;
 pub mod search;
+pub mod transposition;
 mod white;
 
 impl GameManager {
@@ -17,7 +21,7 @@ impl GameManager {
     /// strengths and weaknesses.
     pub fn legal_moves(
         &self,
-        tbl: &NoArc<MoveTable>,
+        tbl: &MoveTable,
     ) -> Vec<(PieceType, Square, Square, MoveType, GameManager)> {
         /* ************************************************************************************* */
         /* WARNING: THIS FUNCTION WILL ERROR SILENTLY IF ANY COLOR-DEPENDENT LOGIC IS USED HERE. */
@@ -39,7 +43,7 @@ impl GameManager {
             &self.en_passant_target,
             self.halfmoves,
             self.fullmoves,
-            &tbl,
+            tbl,
         );
 
         let currently_attacked = self.attacked_by(
@@ -68,8 +72,8 @@ impl GameManager {
             // Create a new GameManager here.
             let mut modified_gm = {
                 match color {
-                    Color::Black => self.black_match_block(mv.0.clone(), mv.3.clone(), mv.1, mv.2),
-                    Color::White => self.white_match_block(mv.0.clone(), mv.3.clone(), mv.1, mv.2),
+                    Color::Black => self.black_match_block(mv.0.clone(), mv.3, mv.1, mv.2),
+                    Color::White => self.white_match_block(mv.0.clone(), mv.3, mv.1, mv.2),
                 }
             };
 
@@ -100,7 +104,7 @@ impl GameManager {
             }
 
             let enemy_attacked = modified_gm.attacked_by(
-                &tbl,
+                tbl,
                 match color {
                     Color::Black => Color::White,
                     Color::White => Color::Black,
@@ -111,26 +115,20 @@ impl GameManager {
             use Square::*;
             match mv.3 {
                 KingCastle => {
-                    if color == Color::Black
-                        && ((E8.to_u64() | F8.to_u64() | G8.to_u64()) & enemy_attacked != 0
-                            || (E8.to_u64() | F8.to_u64() | G8.to_u64()) & currently_attacked != 0)
-                        || color == Color::White
-                            && ((E1.to_u64() | F1.to_u64() | G1.to_u64()) & enemy_attacked != 0
-                                || (E1.to_u64() | F1.to_u64() | G1.to_u64()) & currently_attacked
-                                    != 0)
-                    {
+                    let path = match color {
+                        Color::Black => E8.to_u64() | F8.to_u64() | G8.to_u64(),
+                        Color::White => E1.to_u64() | F1.to_u64() | G1.to_u64(),
+                    };
+                    if path & (enemy_attacked | currently_attacked) != 0 {
                         continue; // We don't want this move!
                     }
                 }
                 QueenCastle => {
-                    if color == Color::Black
-                        && ((E8.to_u64() | D8.to_u64() | C8.to_u64()) & enemy_attacked != 0
-                            || (E8.to_u64() | D8.to_u64() | C8.to_u64()) & currently_attacked != 0)
-                        || color == Color::White
-                            && ((E1.to_u64() | D1.to_u64() | C1.to_u64()) & enemy_attacked != 0
-                                || (E1.to_u64() | D1.to_u64() | C1.to_u64()) & currently_attacked
-                                    != 0)
-                    {
+                    let path = match color {
+                        Color::Black => E8.to_u64() | D8.to_u64() | C8.to_u64(),
+                        Color::White => E1.to_u64() | D1.to_u64() | C1.to_u64(),
+                    };
+                    if path & (enemy_attacked | currently_attacked) != 0 {
                         continue; // Ditto.
                     }
                 }
@@ -140,14 +138,20 @@ impl GameManager {
             match color {
                 Color::Black => {
                     if modified_gm.bitboard.king_black & enemy_attacked == 0 {
-                        // Good move; push it.
-                        legal_moves.push((mv.0.clone(), mv.1, mv.2, mv.3.clone(), modified_gm));
+                        // Good move; carry the incrementally updated scores
+                        // over to the new position and push it.
+                        psqt::update(self, &mut modified_gm);
+                        nnue::update(self, &mut modified_gm);
+                        legal_moves.push((mv.0.clone(), mv.1, mv.2, mv.3, modified_gm));
                     }
                 }
                 Color::White => {
                     if modified_gm.bitboard.king_white & enemy_attacked == 0 {
-                        // Good move; push it.
-                        legal_moves.push((mv.0.clone(), mv.1, mv.2, mv.3.clone(), modified_gm));
+                        // Good move; carry the incrementally updated scores
+                        // over to the new position and push it.
+                        psqt::update(self, &mut modified_gm);
+                        nnue::update(self, &mut modified_gm);
+                        legal_moves.push((mv.0.clone(), mv.1, mv.2, mv.3, modified_gm));
                     }
                 }
             }
@@ -1,7 +1,7 @@
-use super::{GameManager, MoveTable, NoArc};
+use super::{GameManager, MoveTable};
 use rayon::prelude::*;
 
-pub fn perft(depth: u16, maxdepth: u16, gm: GameManager, tbl: &NoArc<MoveTable>) -> u64 {
+pub fn perft(depth: u16, maxdepth: u16, gm: GameManager, tbl: &MoveTable) -> u64 {
     if depth == maxdepth {
         1
     } else {
@@ -13,7 +13,7 @@ pub fn perft(depth: u16, maxdepth: u16, gm: GameManager, tbl: &NoArc<MoveTable>)
 }
 
 #[allow(dead_code)]
-pub fn printing_perft(depth: u16, maxdepth: u16, gm: GameManager, tbl: &NoArc<MoveTable>) {
+pub fn printing_perft(depth: u16, maxdepth: u16, gm: GameManager, tbl: &MoveTable) {
     //use crate::types::Square::*;
     for mv in gm.legal_moves(tbl) {
         println!(
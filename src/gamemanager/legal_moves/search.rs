@@ -1,137 +1,448 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc, Mutex,
+use std::{
+    cmp::Reverse,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
 };
 
 use rayon::prelude::*;
 
-use crate::types::{Move, MoveType, PieceType, Square};
+use crate::{
+    syzygy::{self, Tablebases, Wdl},
+    types::{uci_move_string, MoveType, PieceType, Square},
+};
+
+use super::{
+    transposition::{Bound, TranspositionTable},
+    GameManager, MoveTable,
+};
+
+/// The score of a checkmate at the root. Mates further from the root are
+/// scored lower by one point per ply, so shorter mates are preferred.
+pub const MATE_SCORE: i32 = 1_000_000;
+
+/// The deepest iteration we'll ever attempt; effectively "search forever".
+pub const MAX_DEPTH: u16 = 64;
+
+/// The score of a position the tablebases say is won at the root, lowered
+/// by one point per ply like mates. Below any mate score, so that the
+/// search still prefers a mate it can see.
+pub const TB_WIN: i32 = 100_000;
+
+/// A principal variation: the line of play the search expects, starting
+/// with the best move from the root.
+pub type Line = Vec<(Square, Square, MoveType)>;
+
+/// The limits placed on one search by a UCI `go` command. Time limits
+/// are enforced by the caller clearing the search flag, so they don't
+/// appear here.
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    /// Stop after completing this many plies.
+    pub depth: Option<u16>,
+    /// Stop after visiting this many nodes, counted across all threads.
+    pub nodes: Option<u64>,
+    /// Stop once a mate in this many moves has been found.
+    pub mate: Option<u16>,
+    /// Only consider these moves at the root. Empty means all legal moves.
+    pub search_moves: Vec<(Square, Square, MoveType)>,
+}
+
+impl SearchLimits {
+    /// The deepest iteration allowed by these limits.
+    fn max_depth(&self) -> u16 {
+        let mate_depth = self.mate.map(|m| (2 * m).saturating_sub(1).max(1));
+        match (self.depth, mate_depth) {
+            (Some(d), Some(m)) => d.min(m),
+            (Some(d), None) => d,
+            (None, Some(m)) => m,
+            (None, None) => MAX_DEPTH,
+        }
+        .clamp(1, MAX_DEPTH)
+    }
+}
 
-use super::{GameManager, MoveTable, NoArc};
+/// Engine options that shape how a search runs, rather than when it stops.
+#[derive(Clone, Debug, Default)]
+pub struct SearchSettings {
+    /// How much we dislike draws, in centipawns.
+    pub contempt: i32,
+    /// Whether to report win/draw/loss odds alongside scores.
+    pub show_wdl: bool,
+    /// How many of the best root moves to report lines for. Zero acts as one.
+    pub multi_pv: usize,
+    /// The most pieces, kings included, a position may have to be looked up
+    /// in the endgame tablebases. Zero turns them off.
+    pub syzygy_probe_limit: usize,
+}
+
+/// State shared by every thread taking part in one search.
+struct SearchContext<'a> {
+    tbl: &'a MoveTable,
+    tt: &'a TranspositionTable,
+    flag: &'a AtomicBool,
+    nodes: AtomicU64,
+    node_limit: Option<u64>,
+    contempt: i32,
+    /// The tablebases to probe, if any, and the most pieces a position may
+    /// have to be probed.
+    tablebases: Option<(Arc<Tablebases>, usize)>,
+    /// How many positions the tablebases have been asked about.
+    tb_hits: AtomicU64,
+}
 
-/// A Negamax search routine that runs in parallel.
+impl SearchContext<'_> {
+    /// Counts a visited node, and clears the search flag if
+    /// that exhausts the node budget.
+    fn visit(&self) {
+        let visited = self.nodes.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.node_limit.is_some_and(|limit| visited >= limit) {
+            self.flag.store(false, Ordering::Relaxed);
+        }
+    }
+
+    fn searching(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    /// The score of a draw for the side to move `ply` plies from the root,
+    /// where it's our move on even plies.
+    fn draw_score(&self, ply: u16) -> i32 {
+        if ply.is_multiple_of(2) {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    /// Looks `gm` up in the tablebases, if it has no more pieces than they
+    /// cover and its last move was a capture or pawn move; after any other,
+    /// the fifty-move counter might make the result wrong.
+    fn probe_wdl(&self, ply: u16, gm: &GameManager) -> Option<i32> {
+        let (tablebases, limit) = self.tablebases.as_ref()?;
+        if gm.halfmoves != 0 || syzygy::piece_count(&gm.bitboard) > *limit {
+            return None;
+        }
+        let wdl = tablebases.probe_wdl(gm, self.tbl)?;
+        self.tb_hits.fetch_add(1, Ordering::Relaxed);
+        Some(match wdl {
+            Wdl::Win => TB_WIN - ply as i32,
+            Wdl::Loss => -TB_WIN + ply as i32,
+            Wdl::CursedWin => self.draw_score(ply) + 1,
+            Wdl::BlessedLoss => self.draw_score(ply) - 1,
+            Wdl::Draw => self.draw_score(ply),
+        })
+    }
+}
+
+/// An iterative-deepening Negamax search routine whose root runs in parallel.
+/// Every root move is searched with a full window, so each gets an exact
+/// score and the best `settings.multi_pv` of them are passed to `report`
+/// as `info` lines after each iteration. When the root position is in the
+/// tablebases, only the moves they rank best are searched.
+/// * `returns` - the scores and lines of the last completed iteration, best first
 pub fn root_negamax(
-    depth: u16,
+    limits: SearchLimits,
+    settings: SearchSettings,
     gm: GameManager,
-    tbl: &NoArc<MoveTable>,
+    tbl: &MoveTable,
+    tt: &TranspositionTable,
     flag: Arc<AtomicBool>,
-    best_move: Arc<Mutex<(Square, Square, MoveType)>>,
-) {
-    /* ************************************************************************************* */
-    /* NOTE: This acquires the lock around best_move. If the lock is freed before the best   */
-    /*       move has been written, the UCI thread will print out an invalid move. DO NOT    */
-    /*       FREE THIS LOCK EARLY!                                                           */
-    /* ************************************************************************************* */
-    let mut state = best_move.lock().unwrap();
-    let moves = gm.legal_moves(tbl);
-
-    if moves.len() == 0 {
-        panic!("IDK how to handle checkmate or stalemate; help!");
-    }
-
-    let alpha = i32::MIN + 1;
-    let beta = i32::MAX - 1;
-
-    let labelled_scores: Vec<(i32, (Move, GameManager))> = moves
-        .into_par_iter()
-        .map(|mv| {
-            (
-                -negamax(depth, -beta, -alpha, mv.3, &mv.4, tbl, flag.clone()),
-                ((mv.0, mv.1, mv.2, mv.3), mv.4),
-            )
+    report: &(dyn Fn(&str) + Sync),
+) -> Vec<(i32, Line)> {
+    let mut moves: Vec<(PieceType, Square, Square, MoveType, GameManager)> = gm
+        .legal_moves(tbl)
+        .into_iter()
+        .filter(|mv| {
+            limits.search_moves.is_empty()
+                || limits
+                    .search_moves
+                    .iter()
+                    .any(|sm| (sm.0, sm.1, sm.2) == (mv.1, mv.2, mv.3))
         })
         .collect();
 
-    let mut scored_moves: Vec<(i32, (Move, GameManager))> = labelled_scores
-        .into_iter()
-        .map(|(s, movetuple)| (s, (movetuple.0, movetuple.1)))
-        .collect();
+    if moves.is_empty() {
+        // Checkmate, stalemate, or no legal searchmoves; the caller reports a null move.
+        return Vec::new();
+    }
+
+    // Keep the moves that preserve the root's tablebase result soonest. Past
+    // that, probing inside the search would only tell us what we know.
+    let found = syzygy::tablebases();
+    let limit = settings.syzygy_probe_limit.min(found.largest());
+    let mut tablebases = None;
+    if limit > 0 {
+        let ranks = (syzygy::piece_count(&gm.bitboard) <= limit)
+            .then(|| found.rank_root_moves(&gm, &moves, tbl))
+            .flatten();
+        if let Some(ranks) = ranks {
+            let best = ranks.iter().copied().max().unwrap_or(0);
+            let mut ranks = ranks.into_iter();
+            moves.retain(|_| ranks.next() == Some(best));
+        } else {
+            tablebases = Some((found, limit));
+        }
+    }
 
-    scored_moves.sort_by(|a, b| a.0.cmp(&b.0));
+    let ctx = SearchContext {
+        tbl,
+        tt,
+        flag: &flag,
+        nodes: AtomicU64::new(0),
+        node_limit: limits.nodes,
+        contempt: settings.contempt,
+        tablebases,
+        tb_hits: AtomicU64::new(0),
+    };
+    let start = Instant::now();
+    let multi_pv = settings.multi_pv.max(1);
+    let mut lines = Vec::new();
 
-    let best = scored_moves
-        .into_iter()
-        .inspect(|m| eprintln!("{}: {}{}", m.0, m.1 .0 .1.to_str(), m.1 .0 .2.to_str()))
-        .last()
-        .expect("Should be a move here!");
+    let alpha = -MATE_SCORE - 1;
+    let beta = MATE_SCORE + 1;
 
-    state.0 = best.1 .0 .1; // from
-    state.1 = best.1 .0 .2; // to
-    state.2 = best.1 .0 .3; // movetype
+    for depth in 1..=limits.max_depth() {
+        let mut scored_moves: Vec<(i32, Line)> = moves
+            .par_iter()
+            .map(|mv| {
+                let mut line = vec![(mv.1, mv.2, mv.3)];
+                let score = -negamax(depth - 1, 1, -beta, -alpha, &mv.4, &ctx, &mut line);
+                (score, line)
+            })
+            .collect();
+
+        // An iteration cut short by the flag has unreliable scores. Only keep it
+        // if we have nothing better, i.e. it was the very first iteration.
+        if !ctx.searching() && depth > 1 {
+            break;
+        }
+
+        scored_moves.sort_by_key(|m| Reverse(m.0));
+        scored_moves.truncate(multi_pv);
+
+        for (k, (score, line)) in scored_moves.iter().enumerate() {
+            report(&format!(
+                "info depth {} multipv {} score {}{} nodes {}{} time {} pv {}",
+                depth,
+                k + 1,
+                score_string(*score),
+                if settings.show_wdl {
+                    wdl_string(*score)
+                } else {
+                    String::new()
+                },
+                ctx.nodes.load(Ordering::Relaxed),
+                if limit > 0 {
+                    format!(" tbhits {}", ctx.tb_hits.load(Ordering::Relaxed))
+                } else {
+                    String::new()
+                },
+                start.elapsed().as_millis(),
+                line.iter()
+                    .map(|mv| uci_move_string(mv.0, mv.1, mv.2))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ));
+        }
+
+        let score = scored_moves[0].0;
+        lines = scored_moves;
+
+        if !ctx.searching() {
+            break;
+        }
+
+        if let Some(m) = limits.mate {
+            if score >= MATE_SCORE - (2 * m as i32 - 1) {
+                break; // Found the mate we were asked for.
+            }
+        }
+    }
+
+    lines
+}
+
+/// Formats a score for an `info` line, as either `cp <x>` or `mate <y>`.
+fn score_string(score: i32) -> String {
+    if score >= MATE_SCORE - MAX_DEPTH as i32 {
+        format!("mate {}", (MATE_SCORE - score + 1) / 2)
+    } else if score <= -MATE_SCORE + MAX_DEPTH as i32 {
+        format!("mate -{}", (MATE_SCORE + score) / 2)
+    } else {
+        format!("cp {}", score)
+    }
 }
 
+/// Estimates win/draw/loss odds per mille from a score, for an `info` line.
+fn wdl_string(score: i32) -> String {
+    let (win, loss) = if score >= MATE_SCORE - MAX_DEPTH as i32 {
+        (1000, 0)
+    } else if score <= -MATE_SCORE + MAX_DEPTH as i32 {
+        (0, 1000)
+    } else {
+        // A logistic model: each side's odds rise as the score moves past a
+        // margin in its favour, leaving the remainder as the draw odds.
+        let odds = |cp: f64| (1000.0 / (1.0 + ((250.0 - cp) / 80.0).exp())).round() as i32;
+        (odds(score as f64), odds(-score as f64))
+    };
+    format!(" wdl {} {} {}", win, 1000 - win - loss, loss)
+}
+
+/// Searches `gm` to `depth` plies. When a move raises alpha, it and the line
+/// following it are appended to `line`, which should hold the moves leading here.
+#[allow(clippy::too_many_arguments)]
 fn negamax(
     depth: u16,
+    ply: u16,
     mut alpha: i32,
     beta: i32,
-    movetype: MoveType,
     gm: &GameManager,
-    tbl: &NoArc<MoveTable>,
-    flag: Arc<AtomicBool>,
+    ctx: &SearchContext,
+    line: &mut Line,
 ) -> i32 {
-    if flag.load(Ordering::Relaxed) == false || depth == 0 {
+    ctx.visit();
+
+    if gm.halfmoves >= 100 {
+        return ctx.draw_score(ply); // Fifty-move rule.
+    }
+
+    if !ctx.searching() || depth == 0 {
         // NOTE: Call quiesence search on the current position regardless of
         // depth if the flag "continue searching" is false. We can't stop
         // immediately without throwing out the work at this depth entirely,
         // and I'm not that good at concurrent programs to make that work.
-        capture_search(alpha, beta, movetype, gm, tbl)
+        capture_search(ply, alpha, beta, gm, ctx)
     } else {
-        let moves = gm.legal_moves(tbl);
+        let key = gm.zobrist_key();
+        let mut hash_move = None;
+        if let Some(entry) = ctx.tt.probe(key) {
+            hash_move = entry.best_move;
+            if entry.depth >= depth {
+                let score = entry.score(ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(score) = ctx.probe_wdl(ply, gm) {
+            ctx.tt.store(key, depth, ply, score, Bound::Exact, None);
+            return score;
+        }
 
-        if moves.len() == 0 {
-            return i32::MIN + 1; // Return value of node.
+        let mut moves = gm.legal_moves(ctx.tbl);
+
+        if moves.is_empty() {
+            return terminal_score(ply, gm, ctx);
+        }
+
+        // Try the move that was best last time we were here first.
+        if let Some(hm) = hash_move {
+            if let Some(idx) = moves.iter().position(|mv| (mv.1, mv.2, mv.3) == hm) {
+                moves.swap(0, idx);
+            }
         }
 
-        let mut score = i32::MIN + 1;
+        let original_alpha = alpha;
+        let mut score = -MATE_SCORE - 1;
+        let mut best = None;
+        let mut best_line = Line::new();
         for mv in moves {
             // Call negamax and negate it's return value. Enemy's alpha is our -beta & v.v.
-            score = score.max(-negamax(
+            let mut child_line = vec![(mv.1, mv.2, mv.3)];
+            let child_score = -negamax(
                 depth - 1,
+                ply + 1,
                 -beta,
                 -alpha,
-                mv.3,
                 &mv.4,
-                tbl,
-                flag.clone(),
-            ));
+                ctx,
+                &mut child_line,
+            );
+            if child_score > score {
+                score = child_score;
+                best = Some((mv.1, mv.2, mv.3));
+                if score > alpha {
+                    best_line = child_line;
+                }
+            }
             alpha = alpha.max(score);
             if alpha >= beta {
                 break;
             }
         }
+
+        // Scores from an interrupted search are unreliable; don't keep them.
+        if ctx.searching() {
+            let bound = if score <= original_alpha {
+                Bound::Upper
+            } else if score >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            ctx.tt.store(key, depth, ply, score, bound, best);
+        }
+
+        line.append(&mut best_line);
         score
     }
 }
 
+/// The score of a position with no legal moves. Mated positions score worse
+/// the closer they are to the root; stalemates are a draw.
+fn terminal_score(ply: u16, gm: &GameManager, ctx: &SearchContext) -> i32 {
+    if gm.in_check(ctx.tbl) {
+        -MATE_SCORE + ply as i32
+    } else {
+        ctx.draw_score(ply)
+    }
+}
+
 fn capture_search(
+    ply: u16,
     mut alpha: i32,
     beta: i32,
-    movetype: MoveType,
     gm: &GameManager,
-    tbl: &NoArc<MoveTable>,
+    ctx: &SearchContext,
 ) -> i32 {
-    let mut eval = gm.evaluate(movetype);
+    let moves = gm.legal_moves(ctx.tbl);
+    if moves.is_empty() {
+        return terminal_score(ply, gm, ctx);
+    }
+
+    let mut eval = gm.evaluate();
 
     if eval >= beta {
         beta
     } else {
         alpha = alpha.max(eval);
 
-        let captures: Vec<(PieceType, Square, Square, MoveType, GameManager)> = gm
-            .legal_moves(tbl)
+        let captures: Vec<(PieceType, Square, Square, MoveType, GameManager)> = moves
             .into_iter()
             .filter(|m| {
                 use MoveType::*;
-                match m.3 {
-                    Capture | NPromoCapture | BPromoCapture | RPromoCapture | QPromoCapture
-                    | EPCapture => true,
-                    _ => false,
-                }
+                matches!(
+                    m.3,
+                    Capture
+                        | NPromoCapture
+                        | BPromoCapture
+                        | RPromoCapture
+                        | QPromoCapture
+                        | EPCapture
+                )
             })
             .collect();
 
         for capture in captures {
-            eval = -capture_search(-beta, -alpha, capture.3, &capture.4, tbl);
+            ctx.visit();
+            eval = -capture_search(ply + 1, -beta, -alpha, &capture.4, ctx);
             if eval >= beta {
                 return beta;
             }
@@ -140,3 +451,101 @@ fn capture_search(
         alpha
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movetable::MOVE_TABLE;
+
+    fn search(fen: &str, limits: SearchLimits) -> Line {
+        let mut lines = root_negamax(
+            limits,
+            SearchSettings::default(),
+            GameManager::from_fen_str(fen),
+            &MOVE_TABLE,
+            &TranspositionTable::new(1),
+            Arc::new(AtomicBool::new(true)),
+            &|_| {},
+        );
+        lines.swap_remove(0).1
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let line = search(
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+            SearchLimits {
+                mate: Some(1),
+                ..Default::default()
+            },
+        );
+        assert_eq!((line[0].0, line[0].1), (Square::A1, Square::A8));
+    }
+
+    #[test]
+    fn respects_search_moves() {
+        let line = search(
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+            SearchLimits {
+                depth: Some(1),
+                search_moves: vec![(Square::G1, Square::F1, MoveType::QuietMove)],
+                ..Default::default()
+            },
+        );
+        assert_eq!((line[0].0, line[0].1), (Square::G1, Square::F1));
+    }
+
+    #[test]
+    fn stops_at_node_limit() {
+        let flag = Arc::new(AtomicBool::new(true));
+        root_negamax(
+            SearchLimits {
+                nodes: Some(500),
+                ..Default::default()
+            },
+            SearchSettings::default(),
+            GameManager::default(),
+            &MOVE_TABLE,
+            &TranspositionTable::new(1),
+            flag.clone(),
+            &|_| {},
+        );
+        assert!(!flag.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn reports_multiple_lines() {
+        let lines = root_negamax(
+            SearchLimits {
+                depth: Some(2),
+                ..Default::default()
+            },
+            SearchSettings {
+                multi_pv: 3,
+                ..Default::default()
+            },
+            GameManager::default(),
+            &MOVE_TABLE,
+            &TranspositionTable::new(1),
+            Arc::new(AtomicBool::new(true)),
+            &|_| {},
+        );
+        assert_eq!(lines.len(), 3);
+        assert!(lines.windows(2).all(|w| w[0].0 >= w[1].0));
+        assert_ne!(lines[0].1[0], lines[1].1[0]);
+        assert_ne!(lines[1].1[0], lines[2].1[0]);
+    }
+
+    #[test]
+    fn reports_principal_variation() {
+        // A full-width search yields one move per ply searched.
+        let line = search(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            SearchLimits {
+                depth: Some(2),
+                ..Default::default()
+            },
+        );
+        assert_eq!(line.len(), 2);
+    }
+}
@@ -0,0 +1,225 @@
+//! A transposition table shared by every search thread. Each slot holds two
+//! atomics, the key XORed with the data and the data itself, so a slot torn
+//! by concurrent writers simply fails to match on the next probe.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::types::{MoveType, Square};
+
+use super::search::{MATE_SCORE, MAX_DEPTH};
+
+/// The number of bytes taken by one slot of the table.
+const SLOT_BYTES: usize = 16;
+
+/// `MoveType`s in the order of their 4-bit encoding; see `MoveType::to_str()`.
+const MOVE_TYPES: [MoveType; 14] = [
+    MoveType::QuietMove,
+    MoveType::DoublePawnPush,
+    MoveType::KingCastle,
+    MoveType::QueenCastle,
+    MoveType::Capture,
+    MoveType::EPCapture,
+    MoveType::NPromotion,
+    MoveType::BPromotion,
+    MoveType::RPromotion,
+    MoveType::QPromotion,
+    MoveType::NPromoCapture,
+    MoveType::BPromoCapture,
+    MoveType::RPromoCapture,
+    MoveType::QPromoCapture,
+];
+
+/// How a stored score relates to the true value of the position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    /// The score is exact.
+    Exact,
+    /// The search failed high; the true score is at least this.
+    Lower,
+    /// The search failed low; the true score is at most this.
+    Upper,
+}
+
+/// One decoded entry of the table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entry {
+    pub depth: u16,
+    pub bound: Bound,
+    pub best_move: Option<(Square, Square, MoveType)>,
+    score: i32,
+}
+
+impl Entry {
+    /// Returns the stored score as seen from a node `ply` plies from the root.
+    pub fn score(&self, ply: u16) -> i32 {
+        // Mate scores are stored relative to the node, not the root.
+        if self.score >= MATE_SCORE - MAX_DEPTH as i32 {
+            self.score - ply as i32
+        } else if self.score <= -MATE_SCORE + MAX_DEPTH as i32 {
+            self.score + ply as i32
+        } else {
+            self.score
+        }
+    }
+}
+
+pub struct TranspositionTable {
+    slots: Vec<(AtomicU64, AtomicU64)>,
+}
+
+impl TranspositionTable {
+    /// Creates an empty table taking up about `megabytes` MiB.
+    pub fn new(megabytes: usize) -> Self {
+        let len = (megabytes.max(1) << 20) / SLOT_BYTES;
+        Self {
+            slots: (0..len)
+                .map(|_| (AtomicU64::new(0), AtomicU64::new(0)))
+                .collect(),
+        }
+    }
+
+    /// Forgets every stored entry.
+    pub fn clear(&self) {
+        for (check, data) in &self.slots {
+            check.store(0, Ordering::Relaxed);
+            data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn slot(&self, key: u64) -> &(AtomicU64, AtomicU64) {
+        &self.slots[(key % self.slots.len() as u64) as usize]
+    }
+
+    /// Looks up the position with Zobrist key `key`.
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        let (check, data) = self.slot(key);
+        let data = data.load(Ordering::Relaxed);
+        if data != 0 && check.load(Ordering::Relaxed) ^ data == key {
+            Some(decode(data))
+        } else {
+            None
+        }
+    }
+
+    /// Stores the result of searching the position with key `key` to `depth`
+    /// plies, `ply` plies from the root. Deeper results for the same
+    /// position are kept in preference to shallower ones.
+    pub fn store(
+        &self,
+        key: u64,
+        depth: u16,
+        ply: u16,
+        score: i32,
+        bound: Bound,
+        best_move: Option<(Square, Square, MoveType)>,
+    ) {
+        if self.probe(key).is_some_and(|old| old.depth > depth) {
+            return;
+        }
+
+        // Store mate scores relative to this node; see `Entry::score()`.
+        let score = if score >= MATE_SCORE - MAX_DEPTH as i32 {
+            score + ply as i32
+        } else if score <= -MATE_SCORE + MAX_DEPTH as i32 {
+            score - ply as i32
+        } else {
+            score
+        };
+
+        let data = encode(Entry {
+            depth,
+            bound,
+            best_move,
+            score,
+        });
+        let (check, slot) = self.slot(key);
+        check.store(key ^ data, Ordering::Relaxed);
+        slot.store(data, Ordering::Relaxed);
+    }
+}
+
+/// Packs an entry as: score in bits 0-31, depth in 32-39, bound in 40-41,
+/// and the move in 42-57 (from, to, then move type), with bit 58 set if
+/// there is a move. Bit 59 is always set, so no entry encodes to zero.
+fn encode(entry: Entry) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+    let mv = match entry.best_move {
+        Some((from, to, movetype)) => {
+            let kind = MOVE_TYPES.iter().position(|m| *m == movetype).unwrap() as u64;
+            1 << 16 | square_index(from) << 10 | square_index(to) << 4 | kind
+        }
+        None => 0,
+    };
+    (entry.score as u32 as u64)
+        | (entry.depth.min(255) as u64) << 32
+        | bound << 40
+        | mv << 42
+        | 1 << 59
+}
+
+fn decode(data: u64) -> Entry {
+    let bound = match (data >> 40) & 0b11 {
+        0 => Bound::Exact,
+        1 => Bound::Lower,
+        _ => Bound::Upper,
+    };
+    let mv = (data >> 42) & 0x1FFFF;
+    let best_move = if mv & 1 << 16 != 0 {
+        Some((
+            index_square(mv >> 10 & 0x3F),
+            index_square(mv >> 4 & 0x3F),
+            MOVE_TYPES[(mv & 0xF) as usize],
+        ))
+    } else {
+        None
+    };
+    Entry {
+        depth: ((data >> 32) & 0xFF) as u16,
+        bound,
+        best_move,
+        score: data as u32 as i32,
+    }
+}
+
+fn square_index(square: Square) -> u64 {
+    square.to_u64().trailing_zeros() as u64
+}
+
+fn index_square(index: u64) -> Square {
+    Square::from_u64(1 << index).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_and_probe() {
+        let tt = TranspositionTable::new(1);
+        let mv = Some((Square::E7, Square::E8, MoveType::QPromoCapture));
+        tt.store(0xDEADBEEF, 5, 0, -1234, Bound::Lower, mv);
+
+        let entry = tt.probe(0xDEADBEEF).unwrap();
+        assert_eq!(entry.depth, 5);
+        assert_eq!(entry.bound, Bound::Lower);
+        assert_eq!(entry.best_move, mv);
+        assert_eq!(entry.score(0), -1234);
+        assert!(tt.probe(0xDEADBEEE).is_none());
+
+        tt.clear();
+        assert!(tt.probe(0xDEADBEEF).is_none());
+    }
+
+    #[test]
+    fn mate_scores_are_node_relative() {
+        let tt = TranspositionTable::new(1);
+        // Mate in 3 plies from a node 2 plies deep is mate in 5 from the root...
+        tt.store(42, 3, 2, MATE_SCORE - 5, Bound::Exact, None);
+        // ...but mate in 4 from a node reached after a single ply.
+        assert_eq!(tt.probe(42).unwrap().score(1), MATE_SCORE - 4);
+    }
+}
@@ -35,7 +35,7 @@ impl GameManager {
                         bishops_white: (self.bitboard.bishops_white ^ from.to_u64()) | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
 
                     ..*self
@@ -53,7 +53,7 @@ impl GameManager {
                             queens_black: self.bitboard.queens_black & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
 
                         ..*self
@@ -187,7 +187,7 @@ impl GameManager {
                         knights_white: (self.bitboard.knights_white ^ from.to_u64()) | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -204,7 +204,7 @@ impl GameManager {
                             queens_black: self.bitboard.queens_black & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -219,7 +219,7 @@ impl GameManager {
                         bishops_white: self.bitboard.bishops_white | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -229,7 +229,7 @@ impl GameManager {
                         rooks_white: self.bitboard.rooks_white | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -239,7 +239,7 @@ impl GameManager {
                         knights_white: self.bitboard.knights_white | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -249,7 +249,7 @@ impl GameManager {
                         queens_white: self.bitboard.queens_white | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -268,7 +268,7 @@ impl GameManager {
                             queens_black: self.bitboard.queens_black & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -286,7 +286,7 @@ impl GameManager {
                             queens_black: self.bitboard.queens_black & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -304,7 +304,7 @@ impl GameManager {
                             queens_black: self.bitboard.queens_black & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -322,7 +322,7 @@ impl GameManager {
                             queens_black: self.bitboard.queens_black & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -351,7 +351,7 @@ impl GameManager {
                             pawns_black: self.bitboard.pawns_black & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -379,7 +379,7 @@ impl GameManager {
                             pawns_white: (self.bitboard.pawns_white ^ from.to_u64()) | to.to_u64(),
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: String::from(target_coord),
                         ..*self
                     }
@@ -389,7 +389,7 @@ impl GameManager {
                         pawns_white: (self.bitboard.pawns_white ^ from.to_u64()) | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -405,7 +405,7 @@ impl GameManager {
                             queens_black: self.bitboard.queens_black & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -421,7 +421,7 @@ impl GameManager {
                         queens_white: (self.bitboard.queens_white ^ from.to_u64()) | to.to_u64(),
                         ..self.bitboard
                     },
-                    castling_rights: self.castling_rights.clone(),
+                    castling_rights: self.castling_rights,
                     en_passant_target: self.en_passant_target.clone(),
                     ..*self
                 },
@@ -437,7 +437,7 @@ impl GameManager {
                             queens_black: self.bitboard.queens_black & !to_square,
                             ..self.bitboard
                         },
-                        castling_rights: self.castling_rights.clone(),
+                        castling_rights: self.castling_rights,
                         en_passant_target: self.en_passant_target.clone(),
                         ..*self
                     }
@@ -452,7 +452,7 @@ impl GameManager {
         assert!(
             retval.bitboard.king_black.is_power_of_two(),
             "{} {:?} {:?} {:#X} {:#X}\n",
-            retval.bitboard.to_string(),
+            retval.bitboard,
             retval.castling_rights.black,
             retval.castling_rights.white,
             retval.bitboard.king_black,
@@ -461,7 +461,7 @@ impl GameManager {
         assert!(
             retval.bitboard.king_white.is_power_of_two(),
             "{} {:?} {:?} {:#X} {:#X}\n",
-            retval.bitboard.to_string(),
+            retval.bitboard,
             retval.castling_rights.black,
             retval.castling_rights.white,
             retval.bitboard.king_black,
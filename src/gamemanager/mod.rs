@@ -1,9 +1,10 @@
 use crate::{
     bitboard,
-    movetable::{noarc::NoArc, MoveTable},
+    movetable::{MoveTable, MOVE_TABLE},
     types::{CastlingRecord, Color, MoveType, PieceType, Square},
 };
 use bitboard::BitBoard;
+use evaluation::{nnue::Accumulator, psqt::Psqt};
 use pseudolegal_moves::pseudolegal_moves;
 use regex::Regex;
 
@@ -36,6 +37,11 @@ pub struct GameManager {
     pub en_passant_target: String,
     pub halfmoves: u32,
     pub fullmoves: u32,
+    /// The NNUE's hidden layer for this position, kept up to date by
+    /// `legal_moves()` while the network is in use.
+    pub accumulator: Accumulator,
+    /// Material and piece-square scores, kept up to date by `legal_moves()`.
+    pub psqt: Psqt,
 }
 
 impl Default for GameManager {
@@ -48,31 +54,62 @@ impl Default for GameManager {
             en_passant_target: String::new(),
             halfmoves: 0,
             fullmoves: 1,
+            accumulator: Accumulator::default(),
+            psqt: Psqt::default(),
         }
     }
 }
 
 impl GameManager {
-    /// A utility method for generating a new `GameManager` from a FEN string\
+    /// A utility method for generating a new `GameManager` from a FEN string
+    /// that is known to be good, such as one written in a test.
     /// * `fen` - a `&str` representing a game's state in FEN
     /// * `returns` - a `GameManager` as generated from the FEN
     pub fn from_fen_str(fen: &str) -> Self {
-        if Self::is_valid_fen(fen) {
-            let tokens: Vec<String> = fen.split_whitespace().map(str::to_string).collect();
-            GameManager {
-                //board space validation implemented at higher level (is_valid_fen())
-                bitboard: BitBoard::from_fen_string(&tokens[0]),
-                white_to_move: tokens[1] == "w",
-                castling_rights: CastlingRecord::try_from(tokens[2].as_str())
-                    .expect("We expect FEN strings to be well-formed."),
-                en_passant_target: tokens[3].clone(),
-                halfmoves: tokens[4].parse().unwrap_or_default(),
-                fullmoves: tokens[5].parse().unwrap_or_default(),
-            } // TODO: Remove subscript element access to handle malformed FEN strings.
+        Self::try_from_fen_str(fen).expect("We expect FEN strings to be well-formed.")
+    }
+
+    /// Generates a new `GameManager` from a FEN string that may be malformed,
+    /// such as one sent by a GUI.
+    /// * `fen` - a `&str` representing a game's state in FEN
+    /// * `returns` - a `GameManager` as generated from the FEN, or `None` if the
+    ///   FEN is malformed or describes a position we can't play from
+    pub fn try_from_fen_str(fen: &str) -> Option<Self> {
+        if !Self::is_valid_fen(fen) {
+            return None;
+        }
+
+        let tokens: Vec<&str> = fen.split_whitespace().collect();
+        let gm = GameManager {
+            bitboard: BitBoard::from_fen_string(tokens[0]),
+            white_to_move: tokens[1].eq_ignore_ascii_case("w"),
+            castling_rights: CastlingRecord::try_from(tokens[2]).ok()?,
+            en_passant_target: tokens[3].to_ascii_lowercase(),
+            halfmoves: tokens[4].parse().ok()?,
+            fullmoves: tokens[5].parse().ok()?,
+            accumulator: Accumulator::default(),
+            psqt: Psqt::default(),
+        };
+
+        // Move generation relies on each side having exactly one king, on
+        // pawns never standing on the back ranks, and on the side that just
+        // moved not having left its king in check.
+        let bb = &gm.bitboard;
+        let back_ranks = 0xFF000000_000000FF;
+        let waiting = if gm.white_to_move {
+            (bb.king_black, Color::White)
         } else {
-            eprintln!("WARNING: Malformed FEN string; defaulting to startpos.");
-            GameManager::default()
+            (bb.king_white, Color::Black)
+        };
+        if bb.king_white.count_ones() != 1
+            || bb.king_black.count_ones() != 1
+            || (bb.pawns_white | bb.pawns_black) & back_ranks != 0
+            || waiting.0 & gm.attacked_by(&MOVE_TABLE, waiting.1) != 0
+        {
+            return None;
         }
+
+        Some(gm)
     }
 
     #[allow(dead_code)]
@@ -106,11 +143,11 @@ impl GameManager {
     /// A utility function validating FEN strings
     /// * `returns` - a `bool` indicating whether or not the string follows FEN guidelines
     fn is_valid_fen(fen: &str) -> bool {
-        let fen_regex_string = r"([PNBRQKpnbrqk1-8]{1,8}\/){7}[PNBRQKpnbrqk1-8]{1,8} [WBwb] ((K?Q?k?q)|(K?Q?kq?)|(K?Qk?q?)|(KQ?k?q?)|-) (([A-Ha-h][1-8])|-) \d+ \d+";
+        let fen_regex_string = r"^([PNBRQKpnbrqk1-8]{1,8}\/){7}[PNBRQKpnbrqk1-8]{1,8} [WBwb] ((K?Q?k?q)|(K?Q?kq?)|(K?Qk?q?)|(KQ?k?q?)|-) (([A-Ha-h][36])|-) \d+ \d+$";
         let reggae = Regex::new(fen_regex_string).unwrap();
         let tokens: Vec<String> = fen.split_whitespace().map(str::to_string).collect();
 
-        reggae.is_match(fen) && tokens.len() == 6 && {
+        reggae.is_match(fen.trim()) && tokens.len() == 6 && {
             let ranks: Vec<String> = tokens[0].split('/').map(str::to_string).collect();
             for rank in ranks {
                 let mut count: i32 = 8;
@@ -148,7 +185,7 @@ impl GameManager {
 
     /// Returns a bitmask of all the pieces attacked by the given color on this GameManager's state.
     /// TODO, BUG: Needs to be more careful of pawn moves. Pawns' forward moves cannot capture.
-    pub fn attacked_by(&self, tbl: &NoArc<MoveTable>, color: Color) -> u64 {
+    pub fn attacked_by(&self, tbl: &MoveTable, color: Color) -> u64 {
         let moves = pseudolegal_moves(
             color,
             self.bitboard,
@@ -162,23 +199,20 @@ impl GameManager {
         use crate::types::MoveType::*;
         use crate::types::PieceType::*;
 
-        let movefilter: &dyn for<'a, 'b> Fn(&'a &'b (PieceType, Square, Square, MoveType)) -> bool =
-            &|mv: &&(PieceType, Square, Square, MoveType)| {
-                if mv.3 == DoublePawnPush
-                    || mv.0 == Pawn
-                        && (mv.3 == QuietMove
-                            || mv.3 == BPromotion
-                            || mv.3 == RPromotion
-                            || mv.3 == NPromotion
-                            || mv.3 == QPromotion)
-                {
-                    return false; // Rule it out if it is a pawn push.
-                } else if mv.0 == King && (mv.3 == KingCastle || mv.3 == QueenCastle) {
-                    return false; // Rule it out if it is a castling move.
-                } else {
-                    return true;
-                }
-            };
+        let movefilter = |mv: &&(PieceType, Square, Square, MoveType)| {
+            if mv.3 == DoublePawnPush
+                || mv.0 == Pawn
+                    && matches!(
+                        mv.3,
+                        QuietMove | BPromotion | RPromotion | NPromotion | QPromotion
+                    )
+            {
+                false // Rule it out if it is a pawn push.
+            } else {
+                // Rule it out if it is a castling move.
+                !(mv.0 == King && (mv.3 == KingCastle || mv.3 == QueenCastle))
+            }
+        };
 
         moves
             .iter()
@@ -186,6 +220,63 @@ impl GameManager {
             .map(|(_, _, to, _)| to.to_u64())
             .fold(0_u64, |acc, v| acc | v)
     }
+
+    /// Returns the squares of the pieces giving check to the side to move.
+    pub fn checkers(&self, tbl: &MoveTable) -> Vec<Square> {
+        let (king, enemy) = if self.white_to_move {
+            (self.bitboard.king_white, Color::Black)
+        } else {
+            (self.bitboard.king_black, Color::White)
+        };
+        let mut checkers: Vec<Square> = pseudolegal_moves(
+            enemy,
+            self.bitboard,
+            self.castling_rights,
+            &self.en_passant_target,
+            self.halfmoves,
+            self.fullmoves,
+            tbl,
+        )
+        .into_iter()
+        .filter(|mv| mv.2.to_u64() == king)
+        .map(|mv| mv.1)
+        .collect();
+        checkers.dedup(); // Pawns promoting with capture reach the king four times.
+        checkers
+    }
+
+    /// Returns the same position with the board mirrored top to bottom and
+    /// the colors swapped, so it should be exactly as good for the side to
+    /// move as this one.
+    pub fn flipped(&self) -> Self {
+        let en_passant_target = match self.en_passant_target.as_bytes() {
+            [file, b'3'] => format!("{}6", *file as char),
+            [file, b'6'] => format!("{}3", *file as char),
+            _ => self.en_passant_target.clone(),
+        };
+        GameManager {
+            bitboard: self.bitboard.flipped(),
+            white_to_move: !self.white_to_move,
+            castling_rights: CastlingRecord {
+                white: self.castling_rights.black,
+                black: self.castling_rights.white,
+            },
+            en_passant_target,
+            halfmoves: self.halfmoves,
+            fullmoves: self.fullmoves,
+            accumulator: Accumulator::default(),
+            psqt: Psqt::default(),
+        }
+    }
+
+    /// Returns true if the side to move is in check.
+    pub fn in_check(&self, tbl: &MoveTable) -> bool {
+        if self.white_to_move {
+            self.bitboard.king_white & self.attacked_by(tbl, Color::Black) != 0
+        } else {
+            self.bitboard.king_black & self.attacked_by(tbl, Color::White) != 0
+        }
+    }
 }
 
 #[cfg(test)]
@@ -193,8 +284,8 @@ mod test {
     use super::GameManager;
     use crate::{
         gamemanager::pseudolegal_moves::*,
-        movetable::{noarc::NoArc, MoveTable},
-        types::Color,
+        movetable::MOVE_TABLE,
+        types::{Color, Square},
     };
 
     #[test]
@@ -207,13 +298,29 @@ mod test {
             &game_manager.en_passant_target,
             game_manager.halfmoves,
             game_manager.fullmoves,
-            &NoArc::new(MoveTable::default()),
+            &MOVE_TABLE,
         );
 
+        assert_eq!(moves.len(), 20 /* 20 valid moves at start of game. */);
+    }
+
+    #[test]
+    fn flipped_mirrors_position() {
+        let gm =
+            GameManager::from_fen_str("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b Kq e3 0 2");
         assert_eq!(
-            moves.iter().count(),
-            20 /* 20 valid moves at start of game. */
+            gm.flipped().to_fen_string(),
+            "rnbqkbnr/pppp1ppp/8/3Pp3/8/8/PPP1PPPP/RNBQKBNR w Qk e6 0 2"
         );
+        assert_eq!(gm.flipped().flipped().to_fen_string(), gm.to_fen_string());
+    }
+
+    #[test]
+    fn finds_checkers() {
+        let gm = GameManager::from_fen_str("4k3/8/8/1B6/8/8/8/4RK2 b - - 0 1");
+        let mut checkers = gm.checkers(&MOVE_TABLE);
+        checkers.sort_by_key(|sq| sq.to_str().to_string());
+        assert_eq!(checkers, [Square::B5, Square::E1]);
     }
 
     #[test]
@@ -226,12 +333,9 @@ mod test {
             &game_manager.en_passant_target,
             game_manager.halfmoves,
             game_manager.fullmoves,
-            &NoArc::new(MoveTable::default()),
+            &MOVE_TABLE,
         );
 
-        assert_eq!(
-            moves.iter().count(),
-            20 /* 20 valid moves at start of game. */
-        );
+        assert_eq!(moves.len(), 20 /* 20 valid moves at start of game. */);
     }
 }
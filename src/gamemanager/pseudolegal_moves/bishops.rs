@@ -1,7 +1,4 @@
-use crate::{
-    movetable::{noarc::NoArc, MoveTable},
-    types::*,
-};
+use crate::{movetable::MoveTable, types::*};
 
 /// Returns all pseudolegal moves the knights can make from their positions.
 /// ## Inputs
@@ -47,7 +44,7 @@ pub fn pseudolegal_bishop_moves(
     bishop_locations: Vec<u64>,
     friendly_pieces: u64,
     enemy_pieces: u64,
-    movetable: &NoArc<MoveTable>,
+    movetable: &MoveTable,
 ) -> Vec<(PieceType, Square, Square, MoveType)> {
     let mut bishop_pseudo_legal_moves = Vec::new();
 
@@ -91,11 +88,7 @@ pub fn pseudolegal_bishop_moves(
 
 #[cfg(test)]
 mod test {
-    use crate::{
-        gamemanager::pseudolegal_moves::bishops,
-        movetable::{noarc::NoArc, MoveTable},
-        types::*,
-    };
+    use crate::{gamemanager::pseudolegal_moves::bishops, movetable::MOVE_TABLE, types::*};
     use std::collections::HashSet;
 
     #[test]
@@ -105,10 +98,10 @@ mod test {
             vec![0x20000000_00000000],
             0xFFAF5000_00000000,
             0xFFFF,
-            &NoArc::new(MoveTable::default()),
+            &MOVE_TABLE,
         );
         let moves: HashSet<u64> = HashSet::from_iter(
-            vec![
+            [
                 0x00400000_00000000,
                 0x00100000_00000000,
                 0x00008000_00000000,
@@ -1,7 +1,4 @@
-use crate::{
-    movetable::{noarc::NoArc, MoveTable},
-    types::*,
-};
+use crate::{movetable::MoveTable, types::*};
 
 /// Returns all pseudolegal moves the kings can make from their positions.
 /// ## Inputs
@@ -51,7 +48,7 @@ pub fn pseudolegal_king_moves(
     friendly_rooks: u64,
     enemy_pieces: u64,
     castling_rights: CastlingRecord,
-    movetable: &NoArc<MoveTable>,
+    movetable: &MoveTable,
 ) -> Vec<Move> {
     let mut king_pseudo_legal_moves = Vec::new();
     assert_eq!(king_locations.len(), 1);
@@ -192,11 +189,7 @@ pub fn pseudolegal_king_moves(
 
 #[cfg(test)]
 mod tests {
-    use crate::{
-        gamemanager::pseudolegal_moves::kings,
-        movetable::{noarc::NoArc, MoveTable},
-        types::*,
-    };
+    use crate::{gamemanager::pseudolegal_moves::kings, movetable::MOVE_TABLE, types::*};
     use std::collections::HashSet;
     use Square::*;
 
@@ -212,10 +205,10 @@ mod tests {
                 black: CastlingRights::Neither,
                 white: CastlingRights::Neither,
             },
-            &NoArc::new(MoveTable::default()),
+            &MOVE_TABLE,
         );
         let moves: HashSet<u64> = HashSet::from_iter(
-            vec![
+            [
                 A6.to_u64(),
                 B6.to_u64(),
                 C6.to_u64(),
@@ -244,10 +237,10 @@ mod tests {
                 black: CastlingRights::Both,
                 white: CastlingRights::Neither,
             },
-            &NoArc::new(MoveTable::default()),
+            &MOVE_TABLE,
         );
         let moves: HashSet<u64> = HashSet::from_iter(
-            vec![
+            [
                 D8.to_u64(),
                 F8.to_u64(),
                 D7.to_u64(),
@@ -276,10 +269,10 @@ mod tests {
                 black: CastlingRights::Neither,
                 white: CastlingRights::Both,
             },
-            &NoArc::new(MoveTable::default()),
+            &MOVE_TABLE,
         );
         let moves: HashSet<u64> = HashSet::from_iter(
-            vec![
+            [
                 D1.to_u64(),
                 F1.to_u64(),
                 D2.to_u64(),
@@ -1,7 +1,4 @@
-use crate::{
-    movetable::{noarc::NoArc, MoveTable},
-    types::*,
-};
+use crate::{movetable::MoveTable, types::*};
 
 /// Returns all pseudolegal moves the knights can make from their positions.
 /// ## Inputs
@@ -35,7 +32,7 @@ pub fn pseudolegal_knight_moves(
     knight_locations: Vec<u64>,
     friendly_pieces: u64,
     enemy_pieces: u64,
-    movetable: &NoArc<MoveTable>,
+    movetable: &MoveTable,
 ) -> Vec<Move> {
     let mut knight_pseudo_legal_moves = Vec::new();
 
@@ -76,8 +73,7 @@ pub fn pseudolegal_knight_moves(
 #[cfg(test)]
 mod tests {
     use crate::gamemanager::pseudolegal_moves::knights;
-    use crate::movetable::noarc::NoArc;
-    use crate::{movetable::MoveTable, types::*};
+    use crate::{movetable::MOVE_TABLE, types::*};
     use std::collections::HashSet;
 
     #[test]
@@ -87,13 +83,10 @@ mod tests {
             vec![0x40000000_00000000],
             0xFFFF0000_00000000,
             0xFFFF,
-            &NoArc::new(MoveTable::default()),
-        );
-        let moves: HashSet<u64> = HashSet::from_iter(
-            vec![0x00008000_00000000, 0x00002000_00000000]
-                .iter()
-                .cloned(),
+            &MOVE_TABLE,
         );
+        let moves: HashSet<u64> =
+            HashSet::from_iter([0x00008000_00000000, 0x00002000_00000000].iter().cloned());
         assert!(pslnm.iter().all(|m| moves.contains(&m.2.to_u64())))
     }
 }
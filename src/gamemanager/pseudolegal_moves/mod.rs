@@ -13,7 +13,7 @@ pub mod rooks;
 use crate::{
     bitboard::BitBoard,
     gamemanager::GameManager,
-    movetable::{noarc::NoArc, MoveTable},
+    movetable::MoveTable,
     types::{CastlingRecord, Color, Move},
 };
 
@@ -27,7 +27,7 @@ pub fn pseudolegal_moves(
     en_passant_target: &str,
     _halfmoves: u32,
     _fullmoves: u32,
-    movetable: &NoArc<MoveTable>,
+    movetable: &MoveTable,
 ) -> Vec<Move> {
     let mut pseudolegal_moves: Vec<Move> = Vec::new();
 
@@ -71,7 +71,7 @@ pub fn pseudolegal_moves(
                 friendly_pieces,
                 enemy_pieces,
                 en_passant_target,
-                &movetable,
+                movetable,
             );
             pseudolegal_moves.append(&mut pawn_pseudo_legal_moves);
 
@@ -80,7 +80,7 @@ pub fn pseudolegal_moves(
                 knights,
                 friendly_pieces,
                 enemy_pieces,
-                &movetable,
+                movetable,
             );
             pseudolegal_moves.append(&mut knight_pseudo_legal_moves);
 
@@ -89,7 +89,7 @@ pub fn pseudolegal_moves(
                 bishops,
                 friendly_pieces,
                 enemy_pieces,
-                &movetable,
+                movetable,
             );
             pseudolegal_moves.append(&mut bishop_pseudo_legal_moves);
 
@@ -98,7 +98,7 @@ pub fn pseudolegal_moves(
                 rooks,
                 friendly_pieces,
                 enemy_pieces,
-                &movetable,
+                movetable,
             );
             pseudolegal_moves.append(&mut rook_pseudo_legal_moves);
 
@@ -107,7 +107,7 @@ pub fn pseudolegal_moves(
                 queens,
                 friendly_pieces,
                 enemy_pieces,
-                &movetable,
+                movetable,
             );
             pseudolegal_moves.append(&mut queen_pseudo_legal_moves);
 
@@ -118,7 +118,7 @@ pub fn pseudolegal_moves(
                 bitboard.rooks_black,
                 enemy_pieces,
                 castling_rights,
-                &movetable,
+                movetable,
             );
             pseudolegal_moves.append(&mut king_pseudo_legal_moves);
         }
@@ -151,7 +151,7 @@ pub fn pseudolegal_moves(
                 friendly_pieces,
                 enemy_pieces,
                 en_passant_target,
-                &movetable,
+                movetable,
             );
             pseudolegal_moves.append(&mut pawn_pseudo_legal_moves);
 
@@ -160,7 +160,7 @@ pub fn pseudolegal_moves(
                 knights,
                 friendly_pieces,
                 enemy_pieces,
-                &movetable,
+                movetable,
             );
             pseudolegal_moves.append(&mut knight_pseudo_legal_moves);
 
@@ -169,7 +169,7 @@ pub fn pseudolegal_moves(
                 bishops,
                 friendly_pieces,
                 enemy_pieces,
-                &movetable,
+                movetable,
             );
             pseudolegal_moves.append(&mut bishop_pseudo_legal_moves);
 
@@ -178,7 +178,7 @@ pub fn pseudolegal_moves(
                 rooks,
                 friendly_pieces,
                 enemy_pieces,
-                &movetable,
+                movetable,
             );
             pseudolegal_moves.append(&mut rook_pseudo_legal_moves);
 
@@ -187,7 +187,7 @@ pub fn pseudolegal_moves(
                 queens,
                 friendly_pieces,
                 enemy_pieces,
-                &movetable,
+                movetable,
             );
             pseudolegal_moves.append(&mut queen_pseudo_legal_moves);
 
@@ -198,7 +198,7 @@ pub fn pseudolegal_moves(
                 bitboard.rooks_white,
                 enemy_pieces,
                 castling_rights,
-                &movetable,
+                movetable,
             );
             pseudolegal_moves.append(&mut king_pseudo_legal_moves);
         }
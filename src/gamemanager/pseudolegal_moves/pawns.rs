@@ -1,7 +1,4 @@
-use crate::{
-    movetable::{noarc::NoArc, MoveTable},
-    types::*,
-};
+use crate::{movetable::MoveTable, types::*};
 
 /// A method returning a list of pseudo-legal pawn moves playable according to
 /// the information encoded in this instance of GameManager
@@ -10,15 +7,15 @@ use crate::{
 /// * `friendly_pieces` - a `u64` representing the current position of allied pieces
 /// * `enemy_pieces` - a `u64` representing the current position of enemy pieces
 /// * `returns` - a `Vec` of tuples representing playable pawn moves in the following form:\
-///     (the `PieceType` of the piece to move, the starting `Square`,
-///     the target `Square`, and the `MoveType`)
+///   (the `PieceType` of the piece to move, the starting `Square`,
+///   the target `Square`, and the `MoveType`)
 pub fn pseudolegal_pawn_moves(
     color: Color,
     pawn_locations: Vec<u64>,
     friendly_pieces: u64,
     enemy_pieces: u64,
     en_passant_target: &str,
-    movetable: &NoArc<MoveTable>,
+    movetable: &MoveTable,
 ) -> Vec<Move> {
     let mut pawn_pseudo_legal_moves = Vec::new();
 
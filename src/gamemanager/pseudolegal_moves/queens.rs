@@ -1,14 +1,11 @@
-use crate::{
-    movetable::{noarc::NoArc, MoveTable},
-    types::*,
-};
+use crate::{movetable::MoveTable, types::*};
 
 pub fn pseudolegal_queen_moves(
     color: Color,
     queen_locations: Vec<u64>,
     friendly_pieces: u64,
     enemy_pieces: u64,
-    movetable: &NoArc<MoveTable>,
+    movetable: &MoveTable,
 ) -> Vec<Move> {
     let mut queen_pseudo_legal_moves = Vec::new();
 
@@ -1,7 +1,4 @@
-use crate::{
-    movetable::{noarc::NoArc, MoveTable},
-    types::*,
-};
+use crate::{movetable::MoveTable, types::*};
 
 /// Returns all pseudolegal moves the rooks can make from their positions.
 /// ## Inputs
@@ -19,7 +16,7 @@ pub fn pseudolegal_rook_moves(
     rook_locations: Vec<u64>,
     friendly_pieces: u64,
     enemy_pieces: u64,
-    movetable: &NoArc<MoveTable>,
+    movetable: &MoveTable,
 ) -> Vec<Move> {
     let mut rook_pseudo_legal_moves = Vec::new();
 
@@ -90,21 +87,15 @@ pub fn pseudolegal_rook_moves(
 #[cfg(test)]
 mod tests {
     use crate::gamemanager::pseudolegal_moves::rooks;
-    use crate::movetable::noarc::NoArc;
-    use crate::{movetable::MoveTable, types::*};
+    use crate::{movetable::MOVE_TABLE, types::*};
     use std::collections::HashSet;
 
     #[test]
     fn check_rook_pslm() {
         use Square::*;
 
-        let pslm = rooks::pseudolegal_rook_moves(
-            Color::Black,
-            vec![B5.to_u64()],
-            0,
-            0,
-            &NoArc::new(MoveTable::default()),
-        );
+        let pslm =
+            rooks::pseudolegal_rook_moves(Color::Black, vec![B5.to_u64()], 0, 0, &MOVE_TABLE);
         let moves: HashSet<u64> = HashSet::from_iter(
             vec![
                 A5.to_u64(),
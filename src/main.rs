@@ -1,27 +1,51 @@
 #![allow(dead_code)]
 
 use std::{
-    sync::{atomic::AtomicBool, Arc, Mutex},
+    io,
+    sync::{Arc, Mutex},
     thread,
 };
 
 use enginemanager::Engine;
-use types::{MoveType, Square};
 
 mod bitboard;
+mod book;
+mod bookbuilder;
 mod enginemanager;
 mod gamemanager;
 mod movetable;
+mod notation;
+mod options;
+mod searchcontroller;
+mod syzygy;
+mod tuner;
 mod types;
 mod ucimanager;
+mod zobrist;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("tune") {
+        if let Err(err) = tuner::run(&args[2..]) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("book") {
+        if let Err(err) = bookbuilder::run(&args[2..]) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    gamemanager::evaluation::kpk::init();
     let e = Engine::default();
-    let search_flag = Arc::new(AtomicBool::new(false)); // Continue searching? Default to no.
-    let best_move = Arc::new(Mutex::new((Square::A1, Square::A1, MoveType::QuietMove)));
+    let out = Arc::new(Mutex::new(io::stdout()));
 
     let uci_handle = thread::spawn(move || {
-        ucimanager::communicate(e, search_flag, best_move);
+        ucimanager::communicate(e, io::stdin().lock(), out);
     });
 
     uci_handle
@@ -1,7 +1,9 @@
 use crate::types::{Color, PieceType};
 use dashmap::DashMap;
+use std::sync::LazyLock;
 
-pub mod noarc;
+/// The move table shared by every search thread, built the first time it's used.
+pub static MOVE_TABLE: LazyLock<MoveTable> = LazyLock::new(MoveTable::default);
 
 /// A HashMap of [`(Color, PieceType, u64)`] indexing [`Vec<Vec<u64>>`] where
 /// the index integer is a position on the board (must be a power of two) and
@@ -121,6 +123,8 @@ impl Default for MoveTable {
 /// be the top left of the board.
 /// * `square` - the xy coordinates of the piece
 /// * `returns` - a `Vec<u64>` containing each pseudo legal move possible from that coordinate
+// The board is walked by x-y coordinate, which the rays compare as well as index by.
+#[allow(clippy::needless_range_loop)]
 fn rook_move_rays(square: (usize, usize)) -> Vec<Vec<u64>> {
     // For square = (0, 0)...
     //   0 1 2 3 4 5 6 7 i
@@ -234,6 +238,8 @@ fn rook_move_rays(square: (usize, usize)) -> Vec<Vec<u64>> {
 /// be the top left of the board.
 /// * `square` - the xy coordinates of the piece
 /// * `returns` - a `Vec<u64>` containing each pseudo legal move possible from that coordinate
+// The board is walked by x-y coordinate, which the rays compare as well as index by.
+#[allow(clippy::needless_range_loop)]
 fn bishop_move_rays(square: (usize, usize)) -> Vec<Vec<u64>> {
     // For square = (1, 1)...
     //   0 1 2 3 4 5 6 7 i
@@ -351,6 +357,8 @@ fn bishop_move_rays(square: (usize, usize)) -> Vec<Vec<u64>> {
 /// Return the possible moves of a king on the given square, ignoring castling and other special moves.
 /// * `square` - the xy coordinates of the piece
 /// * `returns` - a [`Vec<Vec<u64>>`] containing each pseudo legal move possible from that coordinate
+// The board is walked by x-y coordinate, which the rays compare as well as index by.
+#[allow(clippy::needless_range_loop)]
 fn king_move_rays(square: (usize, usize)) -> Vec<Vec<u64>> {
     // For square = (1, 1)...
     //   0 1 2 3 4 5 6 7 i
@@ -587,9 +595,7 @@ mod test {
         pslm.insert(0x10000000000000);
 
         let all_are_members = rays.iter().all(|r| r.iter().all(|m| pslm.contains(m)));
-        let only_three = rays
-            .iter()
-            .fold(0, |acc, r: &Vec<u64>| acc + r.iter().count());
+        let only_three = rays.iter().fold(0, |acc, r: &Vec<u64>| acc + r.len());
 
         assert!(all_are_members);
         assert_eq!(only_three, 3);
@@ -614,7 +620,7 @@ mod test {
         let pslm: HashSet<u64> = HashSet::from_iter(squares.iter().cloned());
 
         let all_are_members = rays.iter().all(|r| r.iter().all(|m| pslm.contains(m)));
-        let only_eight = rays.iter().fold(0, |acc, r| acc + r.iter().count());
+        let only_eight = rays.iter().fold(0, |acc, r| acc + r.len());
 
         assert!(all_are_members);
         assert_eq!(only_eight, 8);
@@ -645,7 +651,7 @@ mod test {
         let pslm: HashSet<u64> = HashSet::from_iter(squares.iter().cloned());
 
         let all_are_members = rays.iter().all(|r| r.iter().all(|m| pslm.contains(m)));
-        let only_fourteen = rays.iter().fold(0, |acc, r| acc + r.iter().count());
+        let only_fourteen = rays.iter().fold(0, |acc, r| acc + r.len());
 
         assert!(all_are_members);
         assert_eq!(only_fourteen, 14);
@@ -669,7 +675,7 @@ mod test {
         let pslm: HashSet<u64> = HashSet::from_iter(squares.iter().cloned());
 
         let all_are_members = rays.iter().all(|r| r.iter().all(|m| pslm.contains(m)));
-        let only_seven = rays.iter().fold(0, |acc, r| acc + r.iter().count());
+        let only_seven = rays.iter().fold(0, |acc, r| acc + r.len());
         assert!(all_are_members);
         assert_eq!(only_seven, 7);
     }
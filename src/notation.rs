@@ -0,0 +1,250 @@
+//! Standard Algebraic Notation for moves, as written in PGN files and by people.
+
+use crate::{
+    gamemanager::GameManager,
+    movetable::MoveTable,
+    types::{MoveType, PieceType, Square},
+};
+
+/// A legal move as returned by `GameManager::legal_moves()`.
+pub type LegalMove = (PieceType, Square, Square, MoveType, GameManager);
+
+/// Writes `mv`, a legal move from `gm`, in Standard Algebraic Notation.
+pub fn san(gm: &GameManager, tbl: &MoveTable, mv: &LegalMove) -> String {
+    let legal_moves = gm.legal_moves(tbl);
+    let (piece, from, to, movetype, after) = mv;
+    use MoveType::*;
+
+    let mut s = match movetype {
+        KingCastle => String::from("O-O"),
+        QueenCastle => String::from("O-O-O"),
+        _ => {
+            let capture = matches!(
+                movetype,
+                Capture | EPCapture | NPromoCapture | BPromoCapture | RPromoCapture | QPromoCapture
+            );
+            let mut s = String::new();
+            if *piece == PieceType::Pawn {
+                if capture {
+                    s.push_str(&from.to_str()[..1]);
+                }
+            } else {
+                s.push(piece_letter(piece));
+                s.push_str(&disambiguation(&legal_moves, mv));
+            }
+            if capture {
+                s.push('x');
+            }
+            s.push_str(to.to_str());
+            if let Some(letter) = promotion_letter(movetype) {
+                s.push('=');
+                s.push(letter);
+            }
+            s
+        }
+    };
+
+    if after.in_check(tbl) {
+        s.push(if after.legal_moves(tbl).is_empty() {
+            '#'
+        } else {
+            '+'
+        });
+    }
+    s
+}
+
+/// The file, rank, or square that tells `mv` apart from other moves by the
+/// same kind of piece to the same square, if there are any.
+fn disambiguation(legal_moves: &[LegalMove], mv: &LegalMove) -> String {
+    let from = mv.1.to_str();
+    let rivals: Vec<&str> = legal_moves
+        .iter()
+        .filter(|other| other.0 == mv.0 && other.2 == mv.2 && other.1 != mv.1)
+        .map(|other| other.1.to_str())
+        .collect();
+
+    if rivals.is_empty() {
+        String::new()
+    } else if rivals.iter().all(|r| r[..1] != from[..1]) {
+        String::from(&from[..1])
+    } else if rivals.iter().all(|r| r[1..] != from[1..]) {
+        String::from(&from[1..])
+    } else {
+        String::from(from)
+    }
+}
+
+/// Reads `text`, a move in Standard Algebraic Notation, as one of the legal
+/// moves from `gm`. Check and mate marks and annotations like `!?` may be
+/// left off or added, captures needn't be marked, and castling may be written
+/// with zeroes and promotions without `=`.
+/// * `returns` - the move, or `None` if it isn't legal or could be more than one
+pub fn parse_san(gm: &GameManager, tbl: &MoveTable, text: &str) -> Option<LegalMove> {
+    let text = text.trim().trim_end_matches(['+', '#', '!', '?']);
+    let mut candidates = gm.legal_moves(tbl).into_iter();
+    match text {
+        "O-O" | "0-0" => return candidates.find(|mv| mv.3 == MoveType::KingCastle),
+        "O-O-O" | "0-0-0" => return candidates.find(|mv| mv.3 == MoveType::QueenCastle),
+        _ => {}
+    }
+
+    // Split off the piece letter and any promotion, leaving the origin's
+    // file or rank, a capture mark and the destination.
+    let (piece, rest) = match text.chars().next()? {
+        'K' => (PieceType::King, &text[1..]),
+        'Q' => (PieceType::Queen, &text[1..]),
+        'R' => (PieceType::Rook, &text[1..]),
+        'B' => (PieceType::Bishop, &text[1..]),
+        'N' => (PieceType::Knight, &text[1..]),
+        _ => (PieceType::Pawn, text),
+    };
+    let (rest, promotion) = match rest.char_indices().last()? {
+        (i, c @ ('Q' | 'R' | 'B' | 'N')) if piece == PieceType::Pawn => {
+            (rest[..i].trim_end_matches('='), Some(c))
+        }
+        _ => (rest, None),
+    };
+    if rest.len() < 2 || !rest.is_char_boundary(rest.len() - 2) {
+        return None;
+    }
+    let (origin, to) = rest.split_at(rest.len() - 2);
+    let to = Square::from_str(to)?;
+    let origin = origin.strip_suffix('x').unwrap_or(origin);
+    if origin.len() > 2 || !origin.chars().all(|c| matches!(c, 'a'..='h' | '1'..='8')) {
+        return None;
+    }
+
+    let mut matching = candidates.filter(|mv| {
+        let from = mv.1.to_str();
+        mv.0 == piece
+            && mv.2 == to
+            && origin.chars().all(|c| from.contains(c))
+            && promotion_letter(&mv.3) == promotion
+    });
+    let mv = matching.next()?;
+    matching.next().is_none().then_some(mv)
+}
+
+/// The letter of the piece `movetype` promotes to, if it's a promotion.
+fn promotion_letter(movetype: &MoveType) -> Option<char> {
+    use MoveType::*;
+    match movetype {
+        NPromotion | NPromoCapture => Some('N'),
+        BPromotion | BPromoCapture => Some('B'),
+        RPromotion | RPromoCapture => Some('R'),
+        QPromotion | QPromoCapture => Some('Q'),
+        _ => None,
+    }
+}
+
+fn piece_letter(piece: &PieceType) -> char {
+    match piece {
+        PieceType::King => 'K',
+        PieceType::Queen => 'Q',
+        PieceType::Rook => 'R',
+        PieceType::Bishop => 'B',
+        PieceType::Knight => 'N',
+        PieceType::Pawn | PieceType::Super => ' ',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movetable::MOVE_TABLE;
+
+    /// Returns the SAN of every legal move from `fen`.
+    fn all_san(fen: &str) -> Vec<String> {
+        let gm = GameManager::from_fen_str(fen);
+        gm.legal_moves(&MOVE_TABLE)
+            .iter()
+            .map(|mv| san(&gm, &MOVE_TABLE, mv))
+            .collect()
+    }
+
+    #[test]
+    fn writes_common_moves() {
+        let moves = all_san("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        for expected in ["O-O", "O-O-O", "bxa8=Q+", "b8=N", "Kd2", "Rxa8+"] {
+            assert!(
+                moves.iter().any(|m| m == expected),
+                "{expected} in {moves:?}"
+            );
+        }
+        assert!(all_san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").contains(&String::from("Ra8#")));
+    }
+
+    #[test]
+    fn disambiguates_moves() {
+        // Two knights reach d2, told apart by their files.
+        let moves = all_san("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1");
+        assert!(moves.contains(&String::from("Nbd2")));
+        assert!(moves.contains(&String::from("Nfd2")));
+        assert!(moves.contains(&String::from("Nc3")));
+        // Two rooks on the a-file reach a3, told apart by their ranks.
+        let moves = all_san("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1");
+        assert!(moves.contains(&String::from("R1a3")));
+        assert!(moves.contains(&String::from("R5a3")));
+        assert!(moves.contains(&String::from("Rb5")));
+        // Three queens reach e1; one needs its whole square.
+        let moves = all_san("2k5/8/8/8/4Q2Q/8/8/K6Q w - - 0 1");
+        for expected in ["Qh4e1", "Qee1", "Q1e1"] {
+            assert!(
+                moves.contains(&String::from(expected)),
+                "{expected} in {moves:?}"
+            );
+        }
+    }
+
+    /// Parses `text` in the position `fen` and writes it back in UCI.
+    fn parse(fen: &str, text: &str) -> Option<String> {
+        let gm = GameManager::from_fen_str(fen);
+        parse_san(&gm, &MOVE_TABLE, text).map(|mv| crate::types::uci_move_string(mv.1, mv.2, mv.3))
+    }
+
+    #[test]
+    fn reads_moves() {
+        let knights = "4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1";
+        assert_eq!(parse(knights, "Nbd2").as_deref(), Some("b1d2"));
+        assert_eq!(parse(knights, "Nfd2!?").as_deref(), Some("f3d2"));
+        assert_eq!(parse(knights, "N3d2").as_deref(), Some("f3d2"));
+        assert_eq!(parse(knights, "Nd2"), None); // Either knight.
+        assert_eq!(parse(knights, "Nd3"), None); // Neither.
+        let rooks = "4k3/8/8/R7/8/8/8/R3K3 w Q - 0 1";
+        assert_eq!(parse(rooks, "R1a3").as_deref(), Some("a1a3"));
+        assert_eq!(parse(rooks, "R5a3").as_deref(), Some("a5a3"));
+        assert_eq!(parse(rooks, "Ra5a3").as_deref(), Some("a5a3"));
+        assert_eq!(parse(rooks, "Ra3"), None);
+        assert_eq!(parse(rooks, "Rxa8+").as_deref(), Some("a5a8"));
+        assert_eq!(parse(rooks, "O-O-O").as_deref(), Some("e1c1"));
+        assert_eq!(parse(rooks, "0-0-0").as_deref(), Some("e1c1"));
+        assert_eq!(parse(rooks, "O-O"), None);
+
+        let pawns = "r3k3/1P6/8/3pP3/8/8/8/4K3 w q d6 0 1";
+        assert_eq!(parse(pawns, "exd6").as_deref(), Some("e5d6"));
+        assert_eq!(parse(pawns, "e6").as_deref(), Some("e5e6"));
+        assert_eq!(parse(pawns, "bxa8=Q+").as_deref(), Some("b7a8q"));
+        assert_eq!(parse(pawns, "b8N").as_deref(), Some("b7b8n"));
+        assert_eq!(parse(pawns, "b8"), None);
+        assert_eq!(parse(pawns, "Pe6"), None);
+        assert_eq!(parse(pawns, ""), None);
+        assert_eq!(parse(pawns, "é6"), None);
+    }
+
+    #[test]
+    fn reads_what_it_writes() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "2k5/8/8/8/4Q2Q/8/8/K6Q w - - 0 1",
+            "8/P1k5/K7/8/8/8/8/8 w - - 0 1",
+        ] {
+            let gm = GameManager::from_fen_str(fen);
+            for mv in gm.legal_moves(&MOVE_TABLE) {
+                let text = san(&gm, &MOVE_TABLE, &mv);
+                let read = parse_san(&gm, &MOVE_TABLE, &text).expect(&text);
+                assert_eq!((read.1, read.2, read.3), (mv.1, mv.2, mv.3), "{text}");
+            }
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! The UCI options this engine declares, and their current values.
+
+use vampirc_uci::UciOptionConfig;
+
+pub const HASH: &str = "Hash";
+pub const THREADS: &str = "Threads";
+pub const MULTI_PV: &str = "MultiPV";
+pub const MOVE_OVERHEAD: &str = "Move Overhead";
+pub const CLEAR_HASH: &str = "Clear Hash";
+pub const PONDER: &str = "Ponder";
+pub const SHOW_WDL: &str = "UCI_ShowWDL";
+/// How much the engine dislikes draws, in centipawns.
+pub const CONTEMPT: &str = "Contempt";
+/// The fraction of the remaining clock spent on one move is 1 / this, when
+/// `go` gives no `movestogo`.
+pub const TIME_DIVISOR: &str = "Time Divisor";
+/// The depth searched by a bare `go` when `Bare Go` is `Depth`.
+pub const DEFAULT_DEPTH: &str = "Default Depth";
+/// Whether a bare `go` searches to `Default Depth`, or until `stop`.
+pub const BARE_GO: &str = "Bare Go";
+/// A file to which every command received is appended.
+pub const DEBUG_LOG_FILE: &str = "Debug Log File";
+/// A file of evaluation parameters to use instead of the built-in ones.
+pub const EVAL_FILE: &str = "EvalFile";
+/// Whether positions are scored by the handcrafted evaluation or the NNUE.
+pub const EVAL_BACKEND: &str = "Eval Backend";
+/// The NNUE's network file.
+pub const NNUE_FILE: &str = "NNUE File";
+/// Directories holding Syzygy tablebases, separated as in `PATH`.
+pub const SYZYGY_PATH: &str = "SyzygyPath";
+/// The most pieces a position may have to be looked up in the tablebases.
+pub const SYZYGY_PROBE_LIMIT: &str = "SyzygyProbeLimit";
+/// Whether to play moves from the `BookFile` rather than search.
+pub const OWN_BOOK: &str = "OwnBook";
+/// A Polyglot opening book.
+pub const BOOK_FILE: &str = "BookFile";
+/// The book is only consulted up to this move of the game.
+pub const BOOK_DEPTH: &str = "BookDepth";
+/// Whether to always play the book's favourite move, rather than pick one
+/// at random in proportion to their weights.
+pub const BEST_BOOK_MOVE: &str = "Best Book Move";
+
+/// The current value of one option.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionValue {
+    Check(bool),
+    Spin(i64),
+    Combo(String),
+    Button,
+    String(String),
+}
+
+/// A registry of the declared options and their values.
+pub struct Options {
+    entries: Vec<(UciOptionConfig, OptionValue)>,
+}
+
+impl Default for Options {
+    /// Declares every option, set to its default value.
+    fn default() -> Self {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get()) as i64;
+        let declarations = vec![
+            spin(HASH, 16, 1, 4096),
+            spin(THREADS, threads, 1, 256),
+            spin(MULTI_PV, 1, 1, 256),
+            spin(MOVE_OVERHEAD, 10, 0, 5000),
+            UciOptionConfig::Button {
+                name: String::from(CLEAR_HASH),
+            },
+            check(PONDER, false),
+            check(SHOW_WDL, false),
+            spin(CONTEMPT, 0, -100, 100),
+            spin(TIME_DIVISOR, 20, 1, 100),
+            spin(DEFAULT_DEPTH, 4, 1, 64),
+            UciOptionConfig::Combo {
+                name: String::from(BARE_GO),
+                default: Some(String::from("Depth")),
+                var: vec![String::from("Depth"), String::from("Infinite")],
+            },
+            UciOptionConfig::String {
+                name: String::from(DEBUG_LOG_FILE),
+                default: Some(String::from("<empty>")),
+            },
+            UciOptionConfig::String {
+                name: String::from(EVAL_FILE),
+                default: Some(String::from("<empty>")),
+            },
+            UciOptionConfig::Combo {
+                name: String::from(EVAL_BACKEND),
+                default: Some(String::from("Classical")),
+                var: vec![String::from("Classical"), String::from("NNUE")],
+            },
+            UciOptionConfig::String {
+                name: String::from(NNUE_FILE),
+                default: Some(String::from("<empty>")),
+            },
+            UciOptionConfig::String {
+                name: String::from(SYZYGY_PATH),
+                default: Some(String::from("<empty>")),
+            },
+            spin(SYZYGY_PROBE_LIMIT, 7, 0, 7),
+            check(OWN_BOOK, false),
+            UciOptionConfig::String {
+                name: String::from(BOOK_FILE),
+                default: Some(String::from("<empty>")),
+            },
+            spin(BOOK_DEPTH, 20, 1, 255),
+            check(BEST_BOOK_MOVE, false),
+        ];
+
+        Self {
+            entries: declarations
+                .into_iter()
+                .map(|config| {
+                    let value = default_value(&config);
+                    (config, value)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Options {
+    /// Returns the declaration of every option, in the order they're printed on `uci`.
+    pub fn configs(&self) -> impl Iterator<Item = &UciOptionConfig> {
+        self.entries.iter().map(|(config, _)| config)
+    }
+
+    /// Validates and applies a `setoption` command. Option names are matched
+    /// case-insensitively, as the UCI protocol asks.
+    /// * `returns` - the declared name of the option set, or a description of what was wrong
+    pub fn set(&mut self, name: &str, value: Option<&str>) -> Result<String, String> {
+        let (config, current) = self
+            .entries
+            .iter_mut()
+            .find(|(config, _)| config.get_name().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| format!("No such option: {}", name.trim()))?;
+        let value = value.map(str::trim);
+
+        *current = match config {
+            UciOptionConfig::Button { .. } => OptionValue::Button,
+            UciOptionConfig::Check { name, .. } => match value {
+                Some(v) if v.eq_ignore_ascii_case("true") => OptionValue::Check(true),
+                Some(v) if v.eq_ignore_ascii_case("false") => OptionValue::Check(false),
+                _ => return Err(format!("{name} must be true or false")),
+            },
+            UciOptionConfig::Spin { name, min, max, .. } => {
+                let min = min.unwrap_or(i64::MIN);
+                let max = max.unwrap_or(i64::MAX);
+                match value.and_then(|v| v.parse::<i64>().ok()) {
+                    Some(v) if (min..=max).contains(&v) => OptionValue::Spin(v),
+                    _ => return Err(format!("{name} must be an integer from {min} to {max}")),
+                }
+            }
+            UciOptionConfig::Combo { name, var, .. } => {
+                match var
+                    .iter()
+                    .find(|v| value.is_some_and(|value| v.eq_ignore_ascii_case(value)))
+                {
+                    Some(v) => OptionValue::Combo(v.clone()),
+                    None => return Err(format!("{name} must be one of: {}", var.join(", "))),
+                }
+            }
+            UciOptionConfig::String { .. } => match value {
+                None | Some("<empty>") => OptionValue::String(String::new()),
+                Some(v) => OptionValue::String(String::from(v)),
+            },
+        };
+
+        Ok(String::from(config.get_name()))
+    }
+
+    fn value(&self, name: &str) -> &OptionValue {
+        self.entries
+            .iter()
+            .find(|(config, _)| config.get_name() == name)
+            .map(|(_, value)| value)
+            .unwrap_or_else(|| panic!("Option {name} was never declared."))
+    }
+
+    /// The value of a `check` option.
+    pub fn check(&self, name: &str) -> bool {
+        match self.value(name) {
+            OptionValue::Check(v) => *v,
+            other => panic!("Option {name} is not a check option: {other:?}"),
+        }
+    }
+
+    /// The value of a `spin` option.
+    pub fn spin(&self, name: &str) -> i64 {
+        match self.value(name) {
+            OptionValue::Spin(v) => *v,
+            other => panic!("Option {name} is not a spin option: {other:?}"),
+        }
+    }
+
+    /// The value of a `combo` or `string` option.
+    pub fn string(&self, name: &str) -> &str {
+        match self.value(name) {
+            OptionValue::Combo(v) | OptionValue::String(v) => v,
+            other => panic!("Option {name} is not a combo or string option: {other:?}"),
+        }
+    }
+}
+
+fn spin(name: &str, default: i64, min: i64, max: i64) -> UciOptionConfig {
+    UciOptionConfig::Spin {
+        name: String::from(name),
+        default: Some(default),
+        min: Some(min),
+        max: Some(max),
+    }
+}
+
+fn check(name: &str, default: bool) -> UciOptionConfig {
+    UciOptionConfig::Check {
+        name: String::from(name),
+        default: Some(default),
+    }
+}
+
+fn default_value(config: &UciOptionConfig) -> OptionValue {
+    match config {
+        UciOptionConfig::Check { default, .. } => OptionValue::Check(default.unwrap_or(false)),
+        UciOptionConfig::Spin { default, .. } => OptionValue::Spin(default.unwrap_or(0)),
+        UciOptionConfig::Combo { default, .. } => {
+            OptionValue::Combo(default.clone().unwrap_or_default())
+        }
+        UciOptionConfig::Button { .. } => OptionValue::Button,
+        UciOptionConfig::String { default, .. } => match default.as_deref() {
+            None | Some("<empty>") => OptionValue::String(String::new()),
+            Some(v) => OptionValue::String(String::from(v)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_validates_values() {
+        let mut options = Options::default();
+
+        assert_eq!(options.set("hash", Some("64")), Ok(String::from(HASH)));
+        assert_eq!(options.spin(HASH), 64);
+        assert!(options.set(HASH, Some("0")).is_err());
+        assert!(options.set(HASH, Some("lots")).is_err());
+        assert_eq!(options.spin(HASH), 64);
+
+        assert!(options.set(PONDER, Some("true")).is_ok());
+        assert!(options.check(PONDER));
+        assert!(options.set(PONDER, Some("yes")).is_err());
+
+        assert!(options.set(BARE_GO, Some("infinite")).is_ok());
+        assert_eq!(options.string(BARE_GO), "Infinite");
+        assert!(options.set(BARE_GO, Some("forever")).is_err());
+
+        assert!(options.set(CLEAR_HASH, None).is_ok());
+        assert!(options.set("No Such Option", Some("1")).is_err());
+    }
+
+    #[test]
+    fn declarations_print_as_uci() {
+        let options = Options::default();
+        let lines: Vec<String> = options.configs().map(|c| c.to_string()).collect();
+        assert!(lines.contains(&String::from(
+            "option name Hash type spin default 16 min 1 max 4096"
+        )));
+        assert!(lines.contains(&String::from("option name Clear Hash type button")));
+    }
+}
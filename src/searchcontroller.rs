@@ -0,0 +1,205 @@
+//! Owns the thread running a search, and makes sure every `go` is answered
+//! by exactly one `bestmove`.
+
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::gamemanager::legal_moves::{
+    search::{root_negamax, Line, SearchLimits, SearchSettings},
+    transposition::TranspositionTable,
+};
+use crate::gamemanager::GameManager;
+use crate::movetable::MoveTable;
+use crate::types::uci_move_string;
+
+/// Where UCI output is written: stdout for the engine, a buffer in tests.
+pub type Output = Arc<Mutex<dyn Write + Send>>;
+
+/// Writes one line to `out`, flushing it so the GUI sees it at once.
+pub fn send(out: &Output, line: &str) {
+    let mut out = out.lock().unwrap();
+    let _ = writeln!(out, "{}", line);
+    let _ = out.flush();
+}
+
+/// Everything a worker needs to run one search.
+pub struct SearchJob {
+    pub limits: SearchLimits,
+    pub settings: SearchSettings,
+    pub gm: GameManager,
+    pub tbl: &'static MoveTable,
+    pub tt: Arc<TranspositionTable>,
+    pub threads: usize,
+    /// Hold the best move back until `stop`, even once the limits are reached.
+    pub infinite: bool,
+    /// Search on the opponent's time, holding the best move back until
+    /// `ponderhit` or `stop`.
+    pub ponder: bool,
+    /// How long the search may run. A ponder search's clock only starts on `ponderhit`.
+    pub budget: Option<Duration>,
+    /// Whether to name the expected reply in the `bestmove` line.
+    pub report_ponder: bool,
+}
+
+/// Clears a search flag once its budget has elapsed, unless cancelled first.
+struct Timer {
+    cancel: Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl Timer {
+    fn start(flag: Arc<AtomicBool>, budget: Duration) -> Self {
+        let (cancel, cancelled) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = cancelled.recv_timeout(budget) {
+                flag.store(false, Ordering::Relaxed);
+            }
+        });
+        Self { cancel, handle }
+    }
+
+    fn cancel(self) {
+        let _ = self.cancel.send(());
+        let _ = self.handle.join();
+    }
+}
+
+/// Runs at most one search at a time. The worker thread is the only thing
+/// that ever writes `bestmove`, and it does so exactly once per search.
+pub struct SearchController {
+    out: Output,
+    /// Cleared to make the running search stop.
+    flag: Arc<AtomicBool>,
+    /// While set, the worker must not report its best move, even if it's done.
+    holding: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+    timer: Option<Timer>,
+    /// When a ponder search started, and the time it may use after a `ponderhit`.
+    ponder_clock: Option<(Instant, Option<Duration>)>,
+}
+
+impl SearchController {
+    pub fn new(out: Output) -> Self {
+        Self {
+            out,
+            flag: Arc::new(AtomicBool::new(false)),
+            holding: Arc::new(AtomicBool::new(false)),
+            worker: None,
+            timer: None,
+            ponder_clock: None,
+        }
+    }
+
+    /// Starts a search, first finishing any search still running.
+    pub fn go(&mut self, job: SearchJob) {
+        self.stop();
+
+        self.flag.store(true, Ordering::Relaxed);
+        self.holding
+            .store(job.infinite || job.ponder, Ordering::Relaxed);
+        if job.ponder {
+            self.ponder_clock = Some((Instant::now(), job.budget));
+        } else if let Some(budget) = job.budget {
+            self.timer = Some(Timer::start(self.flag.clone(), budget));
+        }
+
+        let out = self.out.clone();
+        let flag = self.flag.clone();
+        let holding = self.holding.clone();
+        self.worker = Some(thread::spawn(move || {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(job.threads)
+                .build()
+                .expect("Failed to build the search thread pool.");
+            let report = |info: &str| send(&out, info);
+            let lines = pool.install(|| {
+                root_negamax(
+                    job.limits,
+                    job.settings,
+                    job.gm,
+                    job.tbl,
+                    &job.tt,
+                    flag.clone(),
+                    &report,
+                )
+            });
+
+            while holding.load(Ordering::Relaxed) && flag.load(Ordering::Relaxed) {
+                thread::park();
+            }
+            let best = lines.into_iter().next().map(|(_, line)| line);
+            send(
+                &out,
+                &bestmove_string(&best.unwrap_or_default(), job.report_ponder),
+            );
+        }));
+    }
+
+    /// The expected move was played: keep searching, now on our own clock.
+    pub fn ponderhit(&mut self) {
+        if let Some((start, budget)) = self.ponder_clock.take() {
+            self.holding.store(false, Ordering::Relaxed);
+            // Without a clock, the search's own limits end it.
+            if let Some(budget) = budget {
+                self.timer = Some(Timer::start(
+                    self.flag.clone(),
+                    budget.saturating_sub(start.elapsed()),
+                ));
+            }
+            if let Some(worker) = &self.worker {
+                worker.thread().unpark();
+            }
+        }
+    }
+
+    /// Stops the running search, if any, and waits for it to report its best move.
+    pub fn stop(&mut self) {
+        self.ponder_clock = None;
+        self.holding.store(false, Ordering::Relaxed);
+        self.flag.store(false, Ordering::Relaxed);
+        if let Some(worker) = self.worker.take() {
+            worker.thread().unpark();
+            worker.join().expect("The search thread panicked.");
+        }
+        if let Some(timer) = self.timer.take() {
+            timer.cancel();
+        }
+    }
+}
+
+/// Formats the head of a principal variation as a `bestmove` line, adding the
+/// expected reply as the ponder move if asked. An empty line, from a mated or
+/// stalemated position, is written as the null move `0000`.
+fn bestmove_string(line: &Line, with_ponder: bool) -> String {
+    match (line.first(), line.get(1)) {
+        (None, _) => String::from("bestmove 0000"),
+        (Some(best), Some(reply)) if with_ponder => format!(
+            "bestmove {} ponder {}",
+            uci_move_string(best.0, best.1, best.2),
+            uci_move_string(reply.0, reply.1, reply.2)
+        ),
+        (Some(best), _) => format!("bestmove {}", uci_move_string(best.0, best.1, best.2)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{MoveType, Square};
+
+    #[test]
+    fn bestmove_with_ponder() {
+        let line = vec![
+            (Square::E2, Square::E4, MoveType::DoublePawnPush),
+            (Square::E7, Square::E5, MoveType::DoublePawnPush),
+        ];
+        assert_eq!(bestmove_string(&line, false), "bestmove e2e4");
+        assert_eq!(bestmove_string(&line, true), "bestmove e2e4 ponder e7e5");
+        assert_eq!(bestmove_string(&line[..1].to_vec(), true), "bestmove e2e4");
+        assert_eq!(bestmove_string(&Vec::new(), true), "bestmove 0000");
+    }
+}
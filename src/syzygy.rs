@@ -0,0 +1,1421 @@
+//! Syzygy endgame tablebases: win/draw/loss (WDL) probing for the search,
+//! and distance-to-zero (DTZ) probing to pick moves at the root, read from
+//! the `.rtbw` and `.rtbz` files themselves.
+//!
+//! The files hold, for every arrangement of the pieces of one material
+//! signature, a value compressed by recursive pairing and a canonical
+//! Huffman code. Arrangements are turned into indices by folding the board's
+//! symmetries away and numbering the remaining pieces group by group, and the
+//! values are looked up by finding the block holding the index through a
+//! sparse index. The layout followed here is the one the original probing
+//! code reads.
+//!
+//! Squares are numbered from a1 = 0 along each rank to h8 = 63 here, unlike
+//! the bitboards, where bit 0 is h1. Pieces are numbered 1 to 6 for white's
+//! pawn, knight, bishop, rook, queen and king, and 9 to 14 for black's.
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs,
+    ops::Neg,
+    path::PathBuf,
+    sync::{Arc, LazyLock, OnceLock, RwLock},
+};
+
+use crate::{
+    bitboard::BitBoard,
+    gamemanager::GameManager,
+    movetable::MoveTable,
+    types::{MoveType, PieceType, Square},
+};
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+/// The most pieces a table can hold, kings included.
+pub const MAX_PIECES: usize = 7;
+
+/// Root moves are ranked by how soon they win, up to this.
+const MAX_DTZ: i32 = 1 << 18;
+
+/// Flags stored with each table, or with each of its files for pawns.
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+/// A position's result with best play, for the side to move. Cursed wins
+/// and blessed losses are wins and losses the fifty-move rule turns into
+/// draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Option<Self> {
+        Some(match value {
+            -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            2 => Wdl::Win,
+            _ => return None,
+        })
+    }
+
+    fn signum(self) -> i32 {
+        (self as i32).signum()
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Wdl::from_value(-(self as i32)).unwrap_or(Wdl::Draw)
+    }
+}
+
+/// Tables mapping pieces' squares to their part of a table index, shared
+/// by every table.
+struct Encoding {
+    /// Squares below the a1-h8 diagonal, numbered 0 to 27.
+    map_b1h1h7: [usize; 64],
+    /// Squares in the a1-d1-d4 triangle, numbered 0 to 9, diagonal last.
+    map_a1d1d4: [usize; 64],
+    /// The 462 ways to place two kings with the first in the triangle.
+    map_kk: [[usize; 64]; 10],
+    /// `binomial[k][n]` is the number of ways to choose `k` of `n` things.
+    binomial: [[u64; 64]; MAX_PIECES],
+    /// Pawn squares numbered so that the leading pawn, nearest the edge and
+    /// then lowest, has the highest number.
+    map_pawns: [usize; 64],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+static ENCODING: LazyLock<Encoding> = LazyLock::new(Encoding::new);
+
+impl Encoding {
+    fn new() -> Self {
+        let mut e = Encoding {
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; MAX_PIECES],
+            map_pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        let mut code = 0;
+        for s in 0..64 {
+            if off_a1h8(s) < 0 {
+                e.map_b1h1h7[s] = code;
+                code += 1;
+            }
+        }
+
+        let mut code = 0;
+        let mut diagonal = Vec::new();
+        for s in 0..28 {
+            if off_a1h8(s) < 0 && s % 8 <= 3 {
+                e.map_a1d1d4[s] = code;
+                code += 1;
+            } else if off_a1h8(s) == 0 && s % 8 <= 3 {
+                diagonal.push(s);
+            }
+        }
+        for s in diagonal {
+            e.map_a1d1d4[s] = code;
+            code += 1;
+        }
+
+        let mut code = 0;
+        let mut both_on_diagonal = Vec::new();
+        for idx in 0..10 {
+            // Only b1 is numbered 0 in the triangle.
+            for s1 in (0..28).filter(|&s1| e.map_a1d1d4[s1] == idx && (idx != 0 || s1 == 1)) {
+                for s2 in 0..64 {
+                    if distance(s1, s2) <= 1 || (off_a1h8(s1) == 0 && off_a1h8(s2) > 0) {
+                        continue;
+                    }
+                    if off_a1h8(s1) == 0 && off_a1h8(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        e.map_kk[idx][s2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            e.map_kk[idx][s2] = code;
+            code += 1;
+        }
+
+        e.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES.min(n + 1) {
+                e.binomial[k][n] = if k > 0 { e.binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { e.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        let mut available = 47;
+        for lead_pawns in 1..=5 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..=6 {
+                    let sq = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        e.map_pawns[sq] = available;
+                        e.map_pawns[sq ^ 7] = available.saturating_sub(1);
+                        available = available.saturating_sub(2);
+                    }
+                    e.lead_pawn_idx[lead_pawns][sq] = idx;
+                    idx += e.binomial[lead_pawns - 1][e.map_pawns[sq]];
+                }
+                e.lead_pawns_size[lead_pawns][file] = idx;
+            }
+        }
+        e
+    }
+}
+
+/// How far a square is above the a1-h8 diagonal; negative below it.
+fn off_a1h8(s: usize) -> i32 {
+    (s / 8) as i32 - (s % 8) as i32
+}
+
+/// The number of king moves between two squares.
+fn distance(a: usize, b: usize) -> usize {
+    (a / 8).abs_diff(b / 8).max((a % 8).abs_diff(b % 8))
+}
+
+fn u16_le(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_le(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u32_be(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_be(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// A symbol's two halves in the pairing tree, at `btree`.
+fn children(bytes: &[u8], btree: usize, sym: usize) -> Option<(usize, usize)> {
+    let lr = bytes.get(btree + 3 * sym..btree + 3 * sym + 3)?;
+    Some((
+        ((lr[1] as usize & 0xF) << 8) | lr[0] as usize,
+        ((lr[2] as usize) << 4) | (lr[1] as usize >> 4),
+    ))
+}
+
+/// How one side's positions, or one file's for pawns, are encoded and
+/// compressed. Positions are offsets into the table's bytes.
+#[derive(Default)]
+struct PairsData {
+    flags: u8,
+    /// The pieces, in the order the encoding takes them.
+    pieces: [u8; MAX_PIECES],
+    /// The sizes of the groups pieces are numbered in, ending with zero,
+    /// and what each group's number is multiplied by.
+    group_len: [usize; MAX_PIECES + 1],
+    group_idx: [u64; MAX_PIECES + 1],
+    size_of_block: usize,
+    span: u64,
+    sparse_index_size: usize,
+    blocks_num: usize,
+    block_length_size: usize,
+    min_sym_len: u8,
+    lowest_sym: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    btree: usize,
+    sparse_index: usize,
+    block_length: usize,
+    data: usize,
+    /// Where the DTZ value maps for each result start.
+    map_idx: [usize; 4],
+}
+
+impl PairsData {
+    /// Works out the groups from the pieces and the order they're numbered in.
+    fn new(
+        info: &TableInfo,
+        pieces: [u8; MAX_PIECES],
+        order: [u8; 2],
+        file: usize,
+    ) -> Option<Self> {
+        let e = &*ENCODING;
+        let mut d = PairsData {
+            pieces,
+            ..Default::default()
+        };
+
+        let mut n = 0;
+        let mut first_len: i32 = if info.has_pawns {
+            0
+        } else if info.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        d.group_len[0] = 1;
+        for i in 1..info.piece_count {
+            first_len -= 1;
+            if first_len > 0 || pieces[i] == pieces[i - 1] {
+                d.group_len[n] += 1;
+            } else {
+                n += 1;
+                d.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        d.group_len[n] = 0;
+
+        let both_pawns = info.has_pawns && info.pawn_count[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - d.group_len[0] - if both_pawns { d.group_len[1] } else { 0 };
+        let mut idx: u64 = 1;
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                d.group_idx[0] = idx;
+                idx *= if info.has_pawns {
+                    *e.lead_pawns_size.get(d.group_len[0])?.get(file)?
+                } else if info.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                d.group_idx[1] = idx;
+                idx *= e.binomial.get(d.group_len[1])?[48usize.checked_sub(d.group_len[0])?];
+            } else {
+                d.group_idx[next] = idx;
+                idx *= e.binomial.get(d.group_len[next])?[free_squares];
+                free_squares = free_squares.checked_sub(d.group_len[next])?;
+                next += 1;
+            }
+            k += 1;
+            if k > 16 {
+                return None;
+            }
+        }
+        d.group_idx[n] = idx;
+        Some(d)
+    }
+
+    /// Reads the compression parameters at `at`, returning where they end.
+    fn set_sizes(&mut self, bytes: &[u8], mut at: usize) -> Option<usize> {
+        self.flags = *bytes.get(at)?;
+        at += 1;
+        if self.flags & SINGLE_VALUE != 0 {
+            // Every position has the same value, stored here.
+            self.min_sym_len = *bytes.get(at)?;
+            return Some(at + 1);
+        }
+
+        let groups = self.group_len.iter().position(|&len| len == 0)?;
+        let tb_size = self.group_idx[groups];
+        self.size_of_block = 1usize.checked_shl(*bytes.get(at)? as u32)?;
+        self.span = 1u64.checked_shl(*bytes.get(at + 1)? as u32)?;
+        self.sparse_index_size = tb_size.div_ceil(self.span) as usize;
+        let padding = *bytes.get(at + 2)? as usize;
+        self.blocks_num = u32_le(bytes, at + 3)? as usize;
+        self.block_length_size = self.blocks_num + padding;
+        let max_sym_len = *bytes.get(at + 7)?;
+        self.min_sym_len = *bytes.get(at + 8)?;
+        at += 9;
+        if max_sym_len < self.min_sym_len || self.min_sym_len == 0 {
+            return None;
+        }
+
+        // The canonical code gives longer symbols lower values, so working
+        // up from the longest gives, for each length, the lowest value a
+        // symbol of that length can start with, padded to 64 bits.
+        self.lowest_sym = at;
+        let lengths = (max_sym_len - self.min_sym_len) as usize + 1;
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = u16_le(bytes, at + 2 * i)? as u64;
+            let next = u16_le(bytes, at + 2 * (i + 1))? as u64;
+            self.base64[i] = (self.base64[i + 1] + lowest).wrapping_sub(next) / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            *base = base
+                .checked_shl(64 - i as u32 - self.min_sym_len as u32)
+                .unwrap_or(0);
+        }
+        at += 2 * lengths;
+
+        let symbols = u16_le(bytes, at)? as usize;
+        at += 2;
+        self.btree = at;
+        bytes.get(at..at + 3 * symbols)?;
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                self.symlen[sym] = self.set_symlen(bytes, sym, &mut visited)?;
+            }
+        }
+        Some(at + 3 * symbols + (symbols & 1))
+    }
+
+    /// The number of values a symbol stands for, less one.
+    fn set_symlen(&mut self, bytes: &[u8], sym: usize, visited: &mut [bool]) -> Option<u8> {
+        visited[sym] = true;
+        let (left, right) = children(bytes, self.btree, sym)?;
+        if right == 0xFFF {
+            return Some(0);
+        }
+        for child in [left, right] {
+            if !*visited.get(child)? {
+                self.symlen[child] = self.set_symlen(bytes, child, visited)?;
+            }
+        }
+        Some(
+            self.symlen[left]
+                .wrapping_add(self.symlen[right])
+                .wrapping_add(1),
+        )
+    }
+
+    /// The value stored for the position with index `idx`.
+    fn decompress(&self, bytes: &[u8], idx: u64) -> Option<u16> {
+        if self.flags & SINGLE_VALUE != 0 {
+            return Some(self.min_sym_len as u16);
+        }
+
+        // The sparse index tells the block holding the index in the middle
+        // of each span, and where in the block it is; walk from there.
+        let k = (idx / self.span) as usize;
+        if k >= self.sparse_index_size {
+            return None;
+        }
+        let entry = self.sparse_index + 6 * k;
+        let mut block = u32_le(bytes, entry)? as usize;
+        let mut offset = u16_le(bytes, entry + 4)? as i64;
+        offset += (idx % self.span) as i64 - (self.span / 2) as i64;
+
+        let block_length = |block: usize| -> Option<i64> {
+            if block >= self.block_length_size {
+                return None;
+            }
+            Some(u16_le(bytes, self.block_length + 2 * block)? as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        // Read symbols from the start of the block until we reach the one
+        // standing for our value.
+        let mut ptr = self.data + block * self.size_of_block;
+        let mut buf = u64_be(bytes, ptr)?;
+        ptr += 8;
+        let mut buf_size = 64;
+        let min_len = self.min_sym_len as usize;
+        let mut sym = loop {
+            let mut len = 0;
+            while buf < *self.base64.get(len)? {
+                len += 1;
+            }
+            let code = (buf - self.base64[len]).checked_shr((64 - len - min_len) as u32)?;
+            let sym =
+                (code as u16).wrapping_add(u16_le(bytes, self.lowest_sym + 2 * len)?) as usize;
+            let sym_len = *self.symlen.get(sym)? as i64;
+            if offset < sym_len + 1 {
+                break sym;
+            }
+            offset -= sym_len + 1;
+            let len = len + min_len;
+            buf = buf.checked_shl(len as u32).unwrap_or(0);
+            buf_size -= len as i32;
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= (u32_be(bytes, ptr)? as u64) << (64 - buf_size);
+                ptr += 4;
+            }
+        };
+
+        // Then walk down the pairs the symbol expands into.
+        while self.symlen[sym] != 0 {
+            let (left, right) = children(bytes, self.btree, sym)?;
+            let left_len = *self.symlen.get(left)? as i64;
+            if offset < left_len + 1 {
+                sym = left;
+            } else {
+                offset -= left_len + 1;
+                sym = right;
+            }
+            self.symlen.get(sym)?;
+        }
+        Some(children(bytes, self.btree, sym)?.0 as u16)
+    }
+}
+
+/// What a table's name says about it.
+struct TableInfo {
+    /// The name, as in `KRPvKR`, with the stronger side first.
+    name: String,
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    /// The pawns of the side leading the encoding, then the other side's.
+    pawn_count: [usize; 2],
+    /// Whether both sides have the same pieces.
+    symmetric: bool,
+}
+
+impl TableInfo {
+    /// Reads a name like `KRPvKR`.
+    fn parse(name: &str) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        let counts = [count_pieces(white)?, count_pieces(black)?];
+        if counts[0][5] != 1 || counts[1][5] != 1 {
+            return None;
+        }
+        let piece_count: usize = counts.iter().flatten().sum();
+        if piece_count > MAX_PIECES {
+            return None;
+        }
+        let (white_pawns, black_pawns) = (counts[0][0], counts[1][0]);
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        Some(Self {
+            name: String::from(name),
+            piece_count,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: counts.iter().any(|c| c[..5].contains(&1)),
+            pawn_count: if white_leads {
+                [white_pawns, black_pawns]
+            } else {
+                [black_pawns, white_pawns]
+            },
+            symmetric: white == black,
+        })
+    }
+}
+
+/// Counts the pawns, knights, bishops, rooks, queens and kings in one side
+/// of a table name.
+fn count_pieces(side: &str) -> Option<[usize; 6]> {
+    let mut counts = [0; 6];
+    for c in side.chars() {
+        counts["PNBRQK".find(c)?] += 1;
+    }
+    Some(counts)
+}
+
+/// One side's pieces as they appear in a table name.
+fn side_name(counts: [u32; 6]) -> String {
+    let mut name = String::from("K");
+    for (letter, piece) in [('Q', 4), ('R', 3), ('B', 2), ('N', 1), ('P', 0)] {
+        for _ in 0..counts[piece] {
+            name.push(letter);
+        }
+    }
+    name
+}
+
+/// The pieces on `board`, by square, with their numbers.
+fn board_pieces(board: &BitBoard) -> Vec<(usize, u8)> {
+    let sets = [
+        (board.pawns_white, 1),
+        (board.knights_white, 2),
+        (board.bishops_white, 3),
+        (board.rooks_white, 4),
+        (board.queens_white, 5),
+        (board.king_white, 6),
+        (board.pawns_black, 9),
+        (board.knights_black, 10),
+        (board.bishops_black, 11),
+        (board.rooks_black, 12),
+        (board.queens_black, 13),
+        (board.king_black, 14),
+    ];
+    let mut pieces = Vec::with_capacity(MAX_PIECES);
+    for (mut bits, piece) in sets {
+        while bits != 0 {
+            pieces.push(((bits.trailing_zeros() ^ 7) as usize, piece));
+            bits &= bits - 1;
+        }
+    }
+    pieces.sort_unstable();
+    pieces
+}
+
+/// The number of pieces on `board`, kings included.
+pub fn piece_count(board: &BitBoard) -> usize {
+    (board.pawns_white
+        | board.knights_white
+        | board.bishops_white
+        | board.rooks_white
+        | board.queens_white
+        | board.king_white
+        | board.pawns_black
+        | board.knights_black
+        | board.bishops_black
+        | board.rooks_black
+        | board.queens_black
+        | board.king_black)
+        .count_ones() as usize
+}
+
+/// The name of the table `board` belongs in, white's pieces first.
+fn material_name(board: &BitBoard) -> String {
+    let count = |bits: u64| bits.count_ones();
+    let white = [
+        count(board.pawns_white),
+        count(board.knights_white),
+        count(board.bishops_white),
+        count(board.rooks_white),
+        count(board.queens_white),
+        1,
+    ];
+    let black = [
+        count(board.pawns_black),
+        count(board.knights_black),
+        count(board.bishops_black),
+        count(board.rooks_black),
+        count(board.queens_black),
+        1,
+    ];
+    format!("{}v{}", side_name(white), side_name(black))
+}
+
+/// The result of looking a position up in a DTZ table.
+enum Dtz {
+    Value(i32),
+    /// The table only holds positions with the other side to move.
+    ChangeStm,
+}
+
+/// One WDL or DTZ file, read into memory.
+struct Table {
+    bytes: Vec<u8>,
+    /// The encodings by side to move and, for pawns, by the leading pawn's
+    /// file. DTZ tables and tables with the same pieces on both sides only
+    /// have one side.
+    items: [Vec<PairsData>; 2],
+    /// Where the DTZ value maps start.
+    map: usize,
+}
+
+impl Table {
+    /// Parses a table's file.
+    fn parse(bytes: Vec<u8>, info: &TableInfo, dtz: bool) -> Option<Self> {
+        if bytes.get(..4)? != if dtz { DTZ_MAGIC } else { WDL_MAGIC } {
+            return None;
+        }
+        if (bytes.get(4)? & 2 != 0) != info.has_pawns {
+            return None;
+        }
+        let mut at = 5;
+        let sides = if dtz || info.symmetric { 1 } else { 2 };
+        let files = if info.has_pawns { 4 } else { 1 };
+        let both_pawns = info.has_pawns && info.pawn_count[1] > 0;
+
+        let mut items: [Vec<PairsData>; 2] = Default::default();
+        for file in 0..files {
+            let first = *bytes.get(at)?;
+            let second = if both_pawns {
+                *bytes.get(at + 1)?
+            } else {
+                0xFF
+            };
+            let order = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            at += 1 + both_pawns as usize;
+
+            let mut pieces = [[0; MAX_PIECES]; 2];
+            for (k, &byte) in bytes.get(at..at + info.piece_count)?.iter().enumerate() {
+                pieces[0][k] = byte & 0xF;
+                pieces[1][k] = byte >> 4;
+            }
+            at += info.piece_count;
+            for side in 0..sides {
+                items[side].push(PairsData::new(info, pieces[side], order[side], file)?);
+            }
+        }
+        at += at & 1;
+
+        for file in 0..files {
+            for side in items.iter_mut().take(sides) {
+                at = side[file].set_sizes(&bytes, at)?;
+            }
+        }
+
+        let map = at;
+        if dtz {
+            for d in items[0].iter_mut().filter(|d| d.flags & MAPPED != 0) {
+                if d.flags & WIDE != 0 {
+                    at += at & 1;
+                    for i in 0..4 {
+                        d.map_idx[i] = (at - map) / 2 + 1;
+                        at += 2 * u16_le(&bytes, at)? as usize + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        d.map_idx[i] = at - map + 1;
+                        at += *bytes.get(at)? as usize + 1;
+                    }
+                }
+            }
+            at += at & 1;
+        }
+
+        for file in 0..files {
+            for side in items.iter_mut().take(sides) {
+                side[file].sparse_index = at;
+                at += 6 * side[file].sparse_index_size;
+            }
+        }
+        for file in 0..files {
+            for side in items.iter_mut().take(sides) {
+                side[file].block_length = at;
+                at += 2 * side[file].block_length_size;
+            }
+        }
+        for file in 0..files {
+            for side in items.iter_mut().take(sides) {
+                at = (at + 0x3F) & !0x3F;
+                side[file].data = at;
+                at += side[file].blocks_num * side[file].size_of_block;
+            }
+        }
+        if at > bytes.len() {
+            return None;
+        }
+        Some(Self { bytes, items, map })
+    }
+
+    /// Works out which encoding holds `gm` and its index there, or that the
+    /// DTZ table only holds the other side to move.
+    fn index(
+        &self,
+        info: &TableInfo,
+        gm: &GameManager,
+        dtz: bool,
+    ) -> Option<Result<(usize, usize, u64), ()>> {
+        let e = &*ENCODING;
+        let board = board_pieces(&gm.bitboard);
+
+        // Tables hold the stronger side as white, and when both sides have
+        // the same pieces, only white to move; otherwise swap the colors.
+        let black_stronger = material_name(&gm.bitboard) != info.name;
+        let flip = black_stronger || (info.symmetric && !gm.white_to_move);
+        let (flip_color, flip_squares) = if flip { (8, 56) } else { (0, 0) };
+        let stm = flip as usize ^ !gm.white_to_move as usize;
+
+        let mut squares = Vec::with_capacity(MAX_PIECES);
+        let mut pieces = Vec::with_capacity(MAX_PIECES);
+        let mut lead_pawn = 0;
+        let mut file = 0;
+        if info.has_pawns {
+            // The leading pawns come first, and the one most towards the edge
+            // and then lowest picks which of the four files' tables to use.
+            lead_pawn = self.items[0].first()?.pieces[0] ^ flip_color;
+            for &(sq, piece) in board.iter().filter(|(_, piece)| *piece == lead_pawn) {
+                squares.push(sq ^ flip_squares);
+                pieces.push(piece ^ flip_color);
+            }
+            let lead = (0..squares.len()).max_by_key(|&i| (e.map_pawns[squares[i]], Reverse(i)))?;
+            squares.swap(0, lead);
+            file = (squares[0] % 8).min(7 - squares[0] % 8);
+        }
+        let lead_pawns = squares.len();
+
+        if dtz {
+            let flags = self.items[0].get(file)?.flags;
+            let either_side = info.symmetric && !info.has_pawns;
+            if (flags & STM) as usize != stm && !either_side {
+                return Some(Err(()));
+            }
+        }
+
+        for &(sq, piece) in &board {
+            if !info.has_pawns || piece != lead_pawn {
+                squares.push(sq ^ flip_squares);
+                pieces.push(piece ^ flip_color);
+            }
+        }
+        if squares.len() != info.piece_count {
+            return None;
+        }
+        let side = if dtz { 0 } else { stm };
+        let d = self.items[side].get(file)?;
+
+        // Put the pieces in the order the table numbers them in.
+        for i in lead_pawns..squares.len().saturating_sub(1) {
+            if let Some(j) = (i + 1..squares.len()).find(|&j| d.pieces[i] == pieces[j]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+
+        // Mirror the leading piece onto files a to d.
+        if squares[0] % 8 > 3 {
+            for sq in squares.iter_mut() {
+                *sq ^= 7;
+            }
+        }
+
+        let mut idx;
+        if info.has_pawns {
+            idx = e.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&sq| e.map_pawns[sq]);
+            for (i, &sq) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                idx += e.binomial[i][e.map_pawns[sq]];
+            }
+        } else {
+            // Without pawns, mirror it onto the a1-d1-d4 triangle too, and
+            // the first of the leading group off the diagonal below it.
+            if squares[0] / 8 > 3 {
+                for sq in squares.iter_mut() {
+                    *sq ^= 56;
+                }
+            }
+            for i in 0..d.group_len[0] {
+                if off_a1h8(squares[i]) == 0 {
+                    continue;
+                }
+                if off_a1h8(squares[i]) > 0 {
+                    for sq in squares[i..].iter_mut() {
+                        *sq = ((*sq >> 3) | (*sq << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            idx = if info.has_unique_pieces {
+                let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+                let adjust1 = (s1 > s0) as usize;
+                let adjust2 = (s2 > s0) as usize + (s2 > s1) as usize;
+                let rank = |s: usize| s / 8;
+                (if off_a1h8(s0) != 0 {
+                    (e.map_a1d1d4[s0] * 63 + (s1 - adjust1)) * 62 + s2 - adjust2
+                } else if off_a1h8(s1) != 0 {
+                    (6 * 63 + rank(s0) * 28 + e.map_b1h1h7[s1]) * 62 + s2 - adjust2
+                } else if off_a1h8(s2) != 0 {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank(s0) * 7 * 28
+                        + (rank(s1) - adjust1) * 28
+                        + e.map_b1h1h7[s2]
+                } else {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + rank(s0) * 7 * 6
+                        + (rank(s1) - adjust1) * 6
+                        + (rank(s2) - adjust2)
+                }) as u64
+            } else {
+                e.map_kk[e.map_a1d1d4[squares[0]]][squares[1]] as u64
+            };
+        }
+
+        // Number the remaining groups, each by the squares the earlier
+        // groups left free.
+        idx *= d.group_idx[0];
+        let mut start = d.group_len[0];
+        let mut remaining_pawns = info.has_pawns && info.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            squares.get_mut(start..start + len)?.sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let sq = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&s| sq > s).count();
+                let free = sq
+                    .checked_sub(adjust)?
+                    .checked_sub(8 * remaining_pawns as usize)?;
+                n += e.binomial.get(i + 1)?[free];
+            }
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            start += len;
+            next += 1;
+        }
+        Some(Ok((side, file, idx)))
+    }
+
+    fn probe_wdl(&self, info: &TableInfo, gm: &GameManager) -> Option<Wdl> {
+        let (side, file, idx) = self.index(info, gm, false)?.ok()?;
+        let value = self.items[side][file].decompress(&self.bytes, idx)?;
+        Wdl::from_value(value as i32 - 2)
+    }
+
+    fn probe_dtz(&self, info: &TableInfo, gm: &GameManager, wdl: Wdl) -> Option<Dtz> {
+        let Ok((_, file, idx)) = self.index(info, gm, true)? else {
+            return Some(Dtz::ChangeStm);
+        };
+        let d = &self.items[0][file];
+        let mut value = d.decompress(&self.bytes, idx)? as usize;
+
+        if d.flags & MAPPED != 0 {
+            const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+            let at = d.map_idx[WDL_MAP[(wdl as i32 + 2) as usize]] + value;
+            value = if d.flags & WIDE != 0 {
+                u16_le(&self.bytes, self.map + 2 * at)? as usize
+            } else {
+                *self.bytes.get(self.map + at)? as usize
+            };
+        }
+
+        // Tables store moves rather than plies where that loses nothing.
+        let mut dtz = value as i32;
+        if (wdl == Wdl::Win && d.flags & WIN_PLIES == 0)
+            || (wdl == Wdl::Loss && d.flags & LOSS_PLIES == 0)
+            || wdl == Wdl::CursedWin
+            || wdl == Wdl::BlessedLoss
+        {
+            dtz *= 2;
+        }
+        Some(Dtz::Value(dtz + 1))
+    }
+}
+
+/// One material signature's files, read the first time they're needed.
+struct Entry {
+    info: TableInfo,
+    wdl_path: Option<PathBuf>,
+    dtz_path: Option<PathBuf>,
+    wdl: OnceLock<Option<Table>>,
+    dtz: OnceLock<Option<Table>>,
+}
+
+impl Entry {
+    fn table(&self, dtz: bool) -> Option<&Table> {
+        let (cell, path) = if dtz {
+            (&self.dtz, &self.dtz_path)
+        } else {
+            (&self.wdl, &self.wdl_path)
+        };
+        cell.get_or_init(|| {
+            let bytes = fs::read(path.as_ref()?).ok()?;
+            Table::parse(bytes, &self.info, dtz)
+        })
+        .as_ref()
+    }
+}
+
+/// The tables found in a set of directories.
+#[derive(Default)]
+pub struct Tablebases {
+    /// Entries by the names of both their material signatures, `KRvK` and `KvKR`.
+    entries: HashMap<String, Arc<Entry>>,
+    /// The most pieces in any table found.
+    largest: usize,
+}
+
+impl Tablebases {
+    /// Finds the tables in `paths`, directories separated as in the `PATH`
+    /// environment variable.
+    pub fn open(paths: &str) -> Self {
+        let mut found: HashMap<String, (Option<PathBuf>, Option<PathBuf>)> = HashMap::new();
+        for dir in std::env::split_paths(paths) {
+            let Ok(listing) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for file in listing.flatten() {
+                let path = file.path();
+                let (Some(stem), Some(extension)) = (
+                    path.file_stem().and_then(|s| s.to_str()),
+                    path.extension().and_then(|s| s.to_str()),
+                ) else {
+                    continue;
+                };
+                let slot = found.entry(String::from(stem)).or_default();
+                match extension {
+                    "rtbw" => slot.0.get_or_insert(path),
+                    "rtbz" => slot.1.get_or_insert(path),
+                    _ => continue,
+                };
+            }
+        }
+
+        let mut tablebases = Self::default();
+        for (name, (wdl_path, dtz_path)) in found {
+            let Some(info) = TableInfo::parse(&name) else {
+                continue;
+            };
+            if wdl_path.is_none() {
+                continue;
+            }
+            tablebases.largest = tablebases.largest.max(info.piece_count);
+            let (white, black) = name.split_once('v').unwrap_or_default();
+            let mirrored = format!("{black}v{white}");
+            let entry = Arc::new(Entry {
+                info,
+                wdl_path,
+                dtz_path,
+                wdl: OnceLock::new(),
+                dtz: OnceLock::new(),
+            });
+            tablebases.entries.insert(mirrored, entry.clone());
+            tablebases.entries.insert(name, entry);
+        }
+        tablebases
+    }
+
+    /// The number of tables found, counting each material signature once.
+    pub fn len(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|(name, entry)| **name == entry.info.name)
+            .count()
+    }
+
+    /// The most pieces, kings included, in any table found.
+    pub fn largest(&self) -> usize {
+        self.largest
+    }
+
+    /// Whether `gm` can be looked up: few enough pieces, and no castling.
+    pub fn covers(&self, gm: &GameManager) -> bool {
+        gm.castling_rights.are_none() && piece_count(&gm.bitboard) <= self.largest
+    }
+
+    fn entry(&self, gm: &GameManager) -> Option<&Entry> {
+        self.entries
+            .get(&material_name(&gm.bitboard))
+            .map(|entry| &**entry)
+    }
+
+    fn wdl_table(&self, gm: &GameManager) -> Option<Wdl> {
+        if piece_count(&gm.bitboard) == 2 {
+            return Some(Wdl::Draw);
+        }
+        let entry = self.entry(gm)?;
+        entry.table(false)?.probe_wdl(&entry.info, gm)
+    }
+
+    fn dtz_table(&self, gm: &GameManager, wdl: Wdl) -> Option<Dtz> {
+        let entry = self.entry(gm)?;
+        entry.table(true)?.probe_dtz(&entry.info, gm, wdl)
+    }
+
+    /// Looks `gm` up in the WDL tables. Captures are tried first, as the
+    /// tables don't account for en passant, and a capture may be the only
+    /// way to the best result; with `zeroing`, pawn moves are too. Returns
+    /// the result, and whether a capture or pawn move achieves it.
+    fn search(&self, gm: &GameManager, tbl: &MoveTable, zeroing: bool) -> Option<(Wdl, bool)> {
+        let moves = gm.legal_moves(tbl);
+        let mut best = Wdl::Loss;
+        let mut tried = 0;
+        for mv in &moves {
+            let pawn_move = zeroing && mv.0 == PieceType::Pawn;
+            if !is_capture(mv.3) && !pawn_move {
+                continue;
+            }
+            tried += 1;
+            let value = -self.search(&mv.4, tbl, false)?.0;
+            if value > best {
+                best = value;
+                if value >= Wdl::Win {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        // Having tried every move, the table isn't needed, and might be
+        // wrong about a position with en passant possible.
+        let all_tried = tried > 0 && tried == moves.len();
+        let value = if all_tried { best } else { self.wdl_table(gm)? };
+        if best >= value {
+            Some((best, best > Wdl::Draw || all_tried))
+        } else {
+            Some((value, false))
+        }
+    }
+
+    /// The result of `gm` with best play, if it's in the tables.
+    pub fn probe_wdl(&self, gm: &GameManager, tbl: &MoveTable) -> Option<Wdl> {
+        if !self.covers(gm) {
+            return None;
+        }
+        Some(self.search(gm, tbl, false)?.0)
+    }
+
+    /// The number of plies to the next capture or pawn move with best play,
+    /// positive when winning and negative when losing, and zero for a draw;
+    /// one more than 100 for wins and losses the fifty-move rule spoils.
+    pub fn probe_dtz(&self, gm: &GameManager, tbl: &MoveTable) -> Option<i32> {
+        if !self.covers(gm) {
+            return None;
+        }
+        let (wdl, zeroing) = self.search(gm, tbl, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing {
+            return Some(dtz_before_zeroing(wdl));
+        }
+        match self.dtz_table(gm, wdl)? {
+            Dtz::Value(dtz) => {
+                let cursed = wdl == Wdl::CursedWin || wdl == Wdl::BlessedLoss;
+                Some((dtz + if cursed { 100 } else { 0 }) * wdl.signum())
+            }
+            Dtz::ChangeStm => {
+                // The table only holds the other side to move, so look one
+                // move ahead for the move that wins soonest.
+                let mut min_dtz = 0xFFFF;
+                for mv in gm.legal_moves(tbl) {
+                    let zeroing = is_capture(mv.3) || mv.0 == PieceType::Pawn;
+                    let mut dtz = if zeroing {
+                        -dtz_before_zeroing(self.search(&mv.4, tbl, false)?.0)
+                    } else {
+                        -self.probe_dtz(&mv.4, tbl)?
+                    };
+                    if dtz == 1 && is_mate(&mv.4, tbl) {
+                        min_dtz = 1;
+                    }
+                    if !zeroing {
+                        dtz += dtz.signum();
+                    }
+                    if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                        min_dtz = dtz;
+                    }
+                }
+                // With no legal moves, the position is mate.
+                Some(if min_dtz == 0xFFFF { -1 } else { min_dtz })
+            }
+        }
+    }
+
+    /// Ranks each of `moves`, the legal moves in `gm`, by the tables: the
+    /// higher, the better, with certain wins ranked alike and losses ranked
+    /// alike unless the fifty-move rule might save them. `None` if any of
+    /// them couldn't be looked up.
+    pub fn rank_root_moves(
+        &self,
+        gm: &GameManager,
+        moves: &[(PieceType, Square, Square, MoveType, GameManager)],
+        tbl: &MoveTable,
+    ) -> Option<Vec<i32>> {
+        if !self.covers(gm) {
+            return None;
+        }
+        let halfmoves = gm.halfmoves as i32;
+        moves
+            .iter()
+            .map(|mv| {
+                let child = &mv.4;
+                let mut dtz = if child.halfmoves == 0 {
+                    dtz_before_zeroing(-self.probe_wdl(child, tbl)?)
+                } else {
+                    let dtz = -self.probe_dtz(child, tbl)?;
+                    dtz + dtz.signum()
+                };
+                if dtz == 2 && is_mate(child, tbl) {
+                    dtz = 1;
+                }
+                Some(if dtz > 0 {
+                    if dtz + halfmoves <= 99 {
+                        MAX_DTZ
+                    } else {
+                        MAX_DTZ - (dtz + halfmoves)
+                    }
+                } else if dtz < 0 {
+                    if -dtz * 2 + halfmoves < 100 {
+                        -MAX_DTZ
+                    } else {
+                        -MAX_DTZ + (-dtz + halfmoves)
+                    }
+                } else {
+                    0
+                })
+            })
+            .collect()
+    }
+}
+
+/// The DTZ of a position whose best move is a capture or pawn move.
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+        Wdl::Draw => 0,
+    }
+}
+
+fn is_capture(move_type: MoveType) -> bool {
+    use MoveType::*;
+    matches!(
+        move_type,
+        Capture | NPromoCapture | BPromoCapture | RPromoCapture | QPromoCapture | EPCapture
+    )
+}
+
+fn is_mate(gm: &GameManager, tbl: &MoveTable) -> bool {
+    gm.in_check(tbl) && gm.legal_moves(tbl).is_empty()
+}
+
+/// The tables in use, replaced by `set_tablebases()`.
+static TABLEBASES: LazyLock<RwLock<Arc<Tablebases>>> = LazyLock::new(Default::default);
+
+/// Makes the search use `tablebases`.
+pub fn set_tablebases(tablebases: Tablebases) {
+    *TABLEBASES.write().unwrap() = Arc::new(tablebases);
+}
+
+/// The tables in use.
+pub fn tablebases() -> Arc<Tablebases> {
+    TABLEBASES.read().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movetable::MOVE_TABLE;
+
+    #[test]
+    fn encoding_tables() {
+        let e = &*ENCODING;
+        // Every legal placement of two kings, less the mirror images.
+        let kings = (0..64)
+            .flat_map(|s1| (0..64).map(move |s2| (s1, s2)))
+            .filter(|&(s1, s2)| {
+                s1 % 8 <= 3
+                    && s1 / 8 <= s1 % 8
+                    && distance(s1, s2) > 1
+                    && (off_a1h8(s1) != 0 || off_a1h8(s2) <= 0)
+            })
+            .map(|(s1, s2)| e.map_kk[e.map_a1d1d4[s1]][s2])
+            .max();
+        assert_eq!(kings, Some(461));
+        assert_eq!(e.binomial[3][10], 120);
+        assert_eq!(e.map_pawns[8], 47); // a2 leads the most
+    }
+
+    #[test]
+    fn reads_table_names() {
+        let info = TableInfo::parse("KRPvKR").unwrap();
+        assert_eq!(info.piece_count, 5);
+        assert!(info.has_pawns && info.has_unique_pieces && !info.symmetric);
+        assert_eq!(info.pawn_count, [1, 0]);
+        assert!(TableInfo::parse("KRvKR").unwrap().symmetric);
+        assert!(TableInfo::parse("KRvR").is_none());
+
+        let gm = GameManager::from_fen_str("7k/8/8/8/8/8/1r6/KQ6 w - - 0 1");
+        assert_eq!(material_name(&gm.bitboard), "KQvKR");
+    }
+
+    /// The tables `examples/syzygy_fixtures.rs` writes: KQvK, KRvK, KPvK,
+    /// KBvK, KNvK, KNNvK and KBNvK.
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy");
+
+    fn fixture(name: &str, dtz: bool) -> (TableInfo, Table) {
+        let info = TableInfo::parse(name).unwrap();
+        let extension = if dtz { "rtbz" } else { "rtbw" };
+        let bytes = fs::read(format!("{FIXTURES}/{name}.{extension}")).unwrap();
+        let table = Table::parse(bytes, &info, dtz).unwrap();
+        (info, table)
+    }
+
+    /// Every legal position of the kings and one more white piece, which
+    /// `place` puts on the board, with `white_to_move`.
+    fn placements(
+        place: fn(&mut BitBoard, u64),
+        white_to_move: bool,
+    ) -> impl Iterator<Item = GameManager> {
+        let kings = GameManager::from_fen_str("4k3/8/8/8/8/8/8/4K3 w - - 0 1").bitboard;
+        (0..64 * 64 * 64).filter_map(move |i| {
+            let (wk, piece, bk) = (i % 64, i / 64 % 64, i / 4096);
+            if wk == piece || bk == piece || distance(wk, bk) <= 1 {
+                return None;
+            }
+            let mut bitboard = BitBoard {
+                king_white: 1 << wk,
+                king_black: 1 << bk,
+                ..kings
+            };
+            place(&mut bitboard, 1 << piece);
+            let waiting = GameManager {
+                bitboard,
+                white_to_move: !white_to_move,
+                ..Default::default()
+            };
+            if waiting.in_check(&MOVE_TABLE) {
+                return None;
+            }
+            Some(GameManager {
+                white_to_move,
+                ..waiting
+            })
+        })
+    }
+
+    #[test]
+    fn indexes_every_position_in_range() {
+        let (info, table) = fixture("KQvK", false);
+        let size = table.items[0][0].group_idx[1];
+        assert_eq!(size, 31332);
+        for (wk, wq, bk) in [
+            (0, 1, 63),
+            (7, 9, 40),
+            (27, 0, 45),
+            (36, 18, 54),
+            (63, 62, 0),
+        ] {
+            let board = BitBoard {
+                king_white: 1 << wk,
+                queens_white: 1 << wq,
+                king_black: 1 << bk,
+                ..GameManager::from_fen_str("4k3/8/8/8/8/8/8/4K3 w - - 0 1").bitboard
+            };
+            let gm = GameManager {
+                bitboard: board,
+                ..Default::default()
+            };
+            let (_, _, idx) = table.index(&info, &gm, false).unwrap().unwrap();
+            assert!(idx < size);
+            // The same position mirrored left to right has the same index.
+            let mirrored = GameManager {
+                bitboard: BitBoard {
+                    king_white: (1u64 << wk).reverse_bits().swap_bytes(),
+                    queens_white: (1u64 << wq).reverse_bits().swap_bytes(),
+                    king_black: (1u64 << bk).reverse_bits().swap_bytes(),
+                    ..board
+                },
+                ..Default::default()
+            };
+            assert_eq!(
+                table.index(&info, &mirrored, false).unwrap().unwrap().2,
+                idx
+            );
+        }
+    }
+
+    #[test]
+    fn longest_distances_match_known_mates() {
+        // KQvK is mated in at most 10 moves, white's tenth move mating.
+        let (info, dtz) = fixture("KQvK", true);
+        let longest = placements(|b, bits| b.queens_white = bits, true)
+            .map(|gm| match dtz.probe_dtz(&info, &gm, Wdl::Win) {
+                Some(Dtz::Value(dtz)) => dtz,
+                _ => panic!("{}", gm.bitboard),
+            })
+            .max();
+        assert_eq!(longest, Some(19));
+
+        // KRvK in at most 16, here with black to move first.
+        let (info, wdl) = fixture("KRvK", false);
+        let (_, dtz) = fixture("KRvK", true);
+        let longest = placements(|b, bits| b.rooks_white = bits, false)
+            .filter(|gm| wdl.probe_wdl(&info, gm) == Some(Wdl::Loss))
+            .map(|gm| match dtz.probe_dtz(&info, &gm, Wdl::Loss) {
+                Some(Dtz::Value(dtz)) => dtz,
+                _ => panic!("{}", gm.bitboard),
+            })
+            .max();
+        assert_eq!(longest, Some(32));
+    }
+
+    #[test]
+    fn probes_wdl() {
+        let tb = Tablebases::open(FIXTURES);
+        assert_eq!((tb.len(), tb.largest()), (7, 4));
+        let probe = |fen: &str| tb.probe_wdl(&GameManager::from_fen_str(fen), &MOVE_TABLE);
+        assert_eq!(probe("7k/8/8/8/8/8/8/KQ6 w - - 0 1"), Some(Wdl::Win));
+        assert_eq!(probe("7k/8/8/8/8/8/8/KQ6 b - - 0 1"), Some(Wdl::Loss));
+        // With the colors swapped.
+        assert_eq!(probe("kq6/8/8/8/8/8/8/7K b - - 0 1"), Some(Wdl::Win));
+        // Taking the queen leaves a bare king.
+        assert_eq!(probe("7k/8/8/8/8/8/8/Kq6 w - - 0 1"), Some(Wdl::Draw));
+        assert_eq!(probe("8/8/8/8/8/2k5/1Q6/7K b - - 0 1"), Some(Wdl::Draw));
+        // Stalemate.
+        assert_eq!(probe("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), Some(Wdl::Draw));
+        assert_eq!(probe("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), Some(Wdl::Draw));
+        // With white to move, the king steps aside and the pawn queens.
+        assert_eq!(probe("4k3/4P3/4K3/8/8/8/8/8 w - - 0 1"), Some(Wdl::Win));
+        // The pawn outruns the king, but a rook's pawn can't get past it.
+        assert_eq!(probe("7k/8/8/8/8/8/P7/K7 b - - 0 1"), Some(Wdl::Loss));
+        assert_eq!(probe("k7/8/8/8/8/8/P7/K7 w - - 0 1"), Some(Wdl::Draw));
+        assert_eq!(probe("8/8/8/8/8/8/1p6/2k4K b - - 0 1"), Some(Wdl::Win));
+        assert_eq!(probe("7k/8/8/8/8/8/8/KB6 w - - 0 1"), Some(Wdl::Draw));
+        // Two knights can't force mate, but can give it.
+        assert_eq!(probe("8/8/8/3k4/8/8/8/NN2K3 w - - 0 1"), Some(Wdl::Draw));
+        assert_eq!(probe("7k/5K2/5N2/4N3/8/8/8/8 w - - 0 1"), Some(Wdl::Win));
+        // Too many pieces, no table, or castling still possible.
+        assert_eq!(probe("7k/8/8/8/8/8/8/KQ3qrr w - - 0 1"), None);
+        assert_eq!(probe("7k/8/8/8/8/8/r7/1R5K w - - 0 1"), None);
+        assert_eq!(probe("4k3/8/8/8/8/8/8/4K2R w K - 0 1"), None);
+    }
+
+    #[test]
+    fn probes_dtz() {
+        let tb = Tablebases::open(FIXTURES);
+        let dtz = |fen: &str| tb.probe_dtz(&GameManager::from_fen_str(fen), &MOVE_TABLE);
+        // Mate in one, from a table holding white to move.
+        assert_eq!(dtz("7k/8/6K1/8/8/8/Q7/8 w - - 0 1"), Some(1));
+        assert_eq!(dtz("7k/8/8/8/8/8/8/Kq6 w - - 0 1"), Some(0));
+        // From one holding black to move: white's positions are worked out
+        // from black's, one move on.
+        assert_eq!(dtz("k7/8/1K6/8/8/8/8/7R w - - 0 1"), Some(1));
+        assert_eq!(dtz("k7/8/1K6/8/8/8/8/7R b - - 0 1"), Some(-2));
+        // Kd6 Kf7 Kd7 and e8=Q.
+        assert_eq!(dtz("4k3/4P3/4K3/8/8/8/8/8 w - - 0 1"), Some(5));
+        assert_eq!(dtz("7k/5K2/5N2/4N3/8/8/8/8 w - - 0 1"), Some(1));
+        assert_eq!(dtz("7k/5K2/5NN1/8/8/8/8/8 b - - 0 1"), Some(-1));
+
+        let gm = GameManager::from_fen_str("7k/8/8/8/8/8/8/KQ6 w - - 0 1");
+        let moves = gm.legal_moves(&MOVE_TABLE);
+        let ranks = tb.rank_root_moves(&gm, &moves, &MOVE_TABLE).unwrap();
+        // Everything wins but Qh7, which gives the queen away, and Qg6,
+        // which stalemates.
+        for (mv, rank) in moves.iter().zip(ranks) {
+            let draws = mv.0 == PieceType::Queen && (mv.2 == Square::H7 || mv.2 == Square::G6);
+            assert_eq!(rank, if draws { 0 } else { MAX_DTZ });
+        }
+    }
+
+    #[test]
+    fn matches_published_values() {
+        // The values python-chess's documentation gives for this position,
+        // probed from the official tables. They keep losses in KBNvK to the
+        // move, so the mate 54 plies away reads as 53.
+        let tb = Tablebases::open(FIXTURES);
+        let gm = GameManager::from_fen_str("8/2K5/4B3/3N4/8/8/4k3/8 b - - 0 1");
+        assert_eq!(tb.probe_wdl(&gm, &MOVE_TABLE), Some(Wdl::Loss));
+        assert_eq!(tb.probe_dtz(&gm, &MOVE_TABLE), Some(-53));
+    }
+
+    #[test]
+    fn opens_nothing_without_tables() {
+        let tb = Tablebases::open("/nonexistent");
+        assert_eq!((tb.len(), tb.largest()), (0, 0));
+        assert_eq!(
+            tb.probe_wdl(
+                &GameManager::from_fen_str("7k/8/8/8/8/8/8/KQ6 w - - 0 1"),
+                &MOVE_TABLE
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,338 @@
+//! Texel tuning: adjusts the evaluation parameters to best predict the
+//! results of games from positions played in them.
+//!
+//! Run as `swordfish tune <dataset> [output] [passes]`. Each line of the
+//! dataset is a FEN followed by the game's result for white, written as
+//! `1-0`, `0-1` or `1/2-1/2`, or as a number from 0 to 1, with any brackets,
+//! quotes or separators around it. Every position is first resolved to a
+//! quiet one by a capture search, and the tuner then minimises the mean
+//! squared difference between the results and the evaluations of the quiet
+//! positions, mapped to expected scores by a sigmoid whose scale `K` is fitted
+//! to the dataset first. The parameters are changed one step at a time, for as
+//! long as that keeps lowering the error, and saved after every pass.
+
+use std::fs;
+
+use rayon::prelude::*;
+
+use crate::{
+    gamemanager::{
+        evaluation::{
+            endgames::{self, Verdict},
+            params::EvalParams,
+        },
+        GameManager,
+    },
+    movetable::{MoveTable, MOVE_TABLE},
+    types::MoveType,
+};
+
+/// Parameters left alone, as they set the scale of other parameters rather
+/// than weigh anything themselves.
+const FIXED: [&str; 5] = [
+    "knight_phase",
+    "bishop_phase",
+    "rook_phase",
+    "queen_phase",
+    "min_attackers",
+];
+
+/// One training position.
+struct Sample {
+    /// The position, resolved to a quiet one.
+    gm: GameManager,
+    /// The result of the game it came from, for white: 1 for a win, 0.5 for
+    /// a draw and 0 for a loss.
+    result: f64,
+}
+
+/// Runs the tuner on the command line arguments that follow `tune`.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let dataset = args
+        .first()
+        .ok_or("Usage: swordfish tune <dataset> [output] [passes]")?;
+    let output = args.get(1).map_or("tuned.txt", String::as_str);
+    let passes = match args.get(2) {
+        Some(passes) => passes
+            .parse()
+            .map_err(|_| format!("Not a number of passes: {passes}"))?,
+        None => 100,
+    };
+
+    let text = fs::read_to_string(dataset).map_err(|err| format!("{dataset}: {err}"))?;
+    let samples = load_samples(&text, &MOVE_TABLE);
+    if samples.is_empty() {
+        return Err(format!("{dataset}: no usable positions"));
+    }
+    println!("Loaded {} positions", samples.len());
+
+    let mut params = EvalParams::default();
+    let k = fit_k(&samples, &params);
+    println!("K = {k:.4}, error = {:.6}", error(&samples, &params, k));
+
+    let names: Vec<&str> = EvalParams::default()
+        .params()
+        .into_iter()
+        .map(|(name, _)| name)
+        .filter(|name| !FIXED.contains(name))
+        .collect();
+    let mut saved = Ok(());
+    tune(
+        &samples,
+        &mut params,
+        k,
+        &names,
+        passes,
+        |params, pass, err| {
+            println!("Pass {pass}: error = {err:.6}");
+            saved = params.save(output);
+        },
+    );
+    saved?;
+    println!("Saved to {output}");
+    Ok(())
+}
+
+/// Parses every usable line of a dataset, resolving each position to a quiet
+/// one. Positions whose score the endgame knowledge settles, whatever the
+/// parameters, are left out.
+fn load_samples(text: &str, tbl: &MoveTable) -> Vec<Sample> {
+    let lines: Vec<&str> = text.lines().collect();
+    let params = EvalParams::default();
+    lines
+        .par_iter()
+        .filter_map(|line| parse_line(line))
+        .filter_map(|(fen, result)| {
+            let gm = GameManager::try_from_fen_str(&fen)?;
+            Some(Sample {
+                gm: quiet(&gm, tbl, -i32::MAX, i32::MAX).1,
+                result,
+            })
+        })
+        .filter(|sample| {
+            let endgame = endgames::probe(&params, &sample.gm);
+            !matches!(endgame, Some(e) if matches!(e.verdict, Verdict::Exact(_)))
+        })
+        .collect()
+}
+
+/// Splits a dataset line into a six-field FEN and the result for white.
+fn parse_line(line: &str) -> Option<(String, f64)> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 5 {
+        return None;
+    }
+    // The move counters are optional, as in EPD.
+    let counters = tokens[4..]
+        .iter()
+        .take(2)
+        .take_while(|t| t.parse::<u32>().is_ok())
+        .count();
+    let fen = if counters == 2 {
+        tokens[..6].join(" ")
+    } else {
+        format!("{} 0 1", tokens[..4].join(" "))
+    };
+
+    let label = tokens[4 + counters..].join(" ");
+    let result = if label.contains("1/2-1/2") {
+        0.5
+    } else if label.contains("1-0") {
+        1.0
+    } else if label.contains("0-1") {
+        0.0
+    } else {
+        let number = label
+            .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .find(|t| !t.is_empty())?;
+        number.parse().ok().filter(|r| (0.0..=1.0).contains(r))?
+    };
+    Some((fen, result))
+}
+
+/// Follows the best captures from `gm` until the position is quiet, as the
+/// search's capture search does, and returns the score and the quiet position.
+fn quiet(gm: &GameManager, tbl: &MoveTable, mut alpha: i32, beta: i32) -> (i32, GameManager) {
+    let stand_pat = gm.evaluate();
+    let mut best = (stand_pat, gm.clone());
+    if stand_pat >= beta {
+        return best;
+    }
+    alpha = alpha.max(stand_pat);
+
+    use MoveType::*;
+    for capture in gm.legal_moves(tbl).into_iter().filter(|m| {
+        matches!(
+            m.3,
+            Capture | NPromoCapture | BPromoCapture | RPromoCapture | QPromoCapture | EPCapture
+        )
+    }) {
+        let (score, leaf) = quiet(&capture.4, tbl, -beta, -alpha);
+        if -score > alpha {
+            alpha = -score;
+            best = (-score, leaf);
+            if alpha >= beta {
+                break;
+            }
+        }
+    }
+    best
+}
+
+/// Maps an evaluation for white to an expected score for white.
+fn sigmoid(eval: i32, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * eval as f64 / 400.0))
+}
+
+/// The mean squared difference between the results and what the evaluation
+/// with `params` expects them to be.
+fn error(samples: &[Sample], params: &EvalParams, k: f64) -> f64 {
+    let sum: f64 = samples
+        .par_iter()
+        .map(|sample| {
+            let eval = sample.gm.evaluate_with(params).eval;
+            let white_eval = if sample.gm.white_to_move { eval } else { -eval };
+            (sample.result - sigmoid(white_eval, k)).powi(2)
+        })
+        .sum();
+    sum / samples.len() as f64
+}
+
+/// Finds the sigmoid scale that makes the untuned evaluation fit the results best.
+fn fit_k(samples: &[Sample], params: &EvalParams) -> f64 {
+    let (mut low, mut high) = (0.0, 3.0);
+    for _ in 0..50 {
+        let a = low + (high - low) / 3.0;
+        let b = high - (high - low) / 3.0;
+        if error(samples, params, a) < error(samples, params, b) {
+            high = b;
+        } else {
+            low = a;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Nudges each value of the parameters called `names` up or down by one,
+/// keeping any change that lowers the error, for up to `passes` passes or
+/// until a pass changes nothing. `on_pass` is called after every pass with
+/// the parameters so far, the pass number and the error.
+fn tune(
+    samples: &[Sample],
+    params: &mut EvalParams,
+    k: f64,
+    names: &[&str],
+    passes: usize,
+    mut on_pass: impl FnMut(&EvalParams, usize, f64),
+) {
+    let mut best = error(samples, params, k);
+    for pass in 1..=passes {
+        let mut improved = false;
+        for name in names {
+            for i in 0..values(params, name).len() {
+                for delta in [1, -1] {
+                    let mut candidate = params.clone();
+                    let mut changed = values(&mut candidate, name);
+                    changed[i] += delta;
+                    set_values(&mut candidate, name, &changed);
+                    if candidate.check().is_err() {
+                        continue;
+                    }
+                    let err = error(samples, &candidate, k);
+                    if err < best {
+                        *params = candidate;
+                        best = err;
+                        improved = true;
+                        break;
+                    }
+                }
+            }
+        }
+        on_pass(params, pass, best);
+        if !improved {
+            break;
+        }
+    }
+}
+
+/// The values of the parameter called `name`.
+fn values(params: &mut EvalParams, name: &str) -> Vec<i32> {
+    params
+        .params()
+        .into_iter()
+        .find(|(n, _)| n == &name)
+        .map(|(_, param)| param.values())
+        .unwrap_or_default()
+}
+
+/// Replaces the values of the parameter called `name`.
+fn set_values(params: &mut EvalParams, name: &str, values: &[i32]) {
+    if let Some((_, mut param)) = params.params().into_iter().find(|(n, _)| n == &name) {
+        // The values came from the same parameter, so they're the right length.
+        let _ = param.set(values);
+    }
+    params.mirror_heatmaps();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_dataset_lines() {
+        let fen = "4k3/8/8/8/8/8/8/3QK3 w - - 0 1";
+        assert_eq!(
+            parse_line(&format!("{fen} [1.0]")),
+            Some((String::from(fen), 1.0))
+        );
+        assert_eq!(
+            parse_line(&format!("{fen} | 0.5")),
+            Some((String::from(fen), 0.5))
+        );
+        assert_eq!(
+            parse_line("4k3/8/8/8/8/8/8/3QK3 w - - c9 \"0-1\";"),
+            Some((String::from(fen), 0.0))
+        );
+        assert_eq!(
+            parse_line(&format!("{fen}; 1/2-1/2")),
+            Some((String::from(fen), 0.5))
+        );
+        assert_eq!(parse_line(&format!("{fen} 7")), None);
+        assert_eq!(parse_line("garbage"), None);
+    }
+
+    #[test]
+    fn resolves_captures() {
+        // White's queen takes the loose rook before the position is scored.
+        let gm = GameManager::from_fen_str("4k3/8/8/8/3r4/8/8/3QK3 w - - 0 1");
+        let (_, leaf) = quiet(&gm, &MOVE_TABLE, -i32::MAX, i32::MAX);
+        assert_eq!(leaf.bitboard.rooks_black, 0);
+    }
+
+    #[test]
+    fn tuning_lowers_the_error() {
+        let samples = load_samples(
+            "4k3/8/8/8/8/8/4P3/3QK3 w - - 0 1 [1.0]\n4k3/8/8/8/8/8/4P3/3QK3 b - - 0 1 [1.0]\n\
+             4k3/8/8/8/8/8/8/3QK3 w - - 0 1 [1.0]\n",
+            &MOVE_TABLE,
+        );
+        // The last position is a known win, whatever the parameters.
+        assert_eq!(samples.len(), 2);
+
+        let mut params = EvalParams::default();
+        let before = error(&samples, &params, 1.0);
+        let mut passes = 0;
+        tune(
+            &samples,
+            &mut params,
+            1.0,
+            &["queen_value"],
+            3,
+            |_, _, _| passes += 1,
+        );
+        assert_eq!(passes, 3);
+        assert!(error(&samples, &params, 1.0) < before);
+        // With only a queen and a pawn on the board this is nearly an endgame.
+        assert!(params.queen_value.eg() > EvalParams::default().queen_value.eg());
+    }
+}
@@ -1,15 +1,15 @@
 use std::fmt::Display;
 
 /// An `enum` to represent which type the piece is. This provides indexing for our hash table of moves.
-/// It also carries the evaluation weights of the pieces, which influence our eval. fn.
+/// The pieces' worth is an evaluation parameter; see `EvalParams`.
 #[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub enum PieceType {
     King,
-    Queen = 1000,
-    Rook = 500,
-    Bishop = 320,
-    Knight = 300,
-    Pawn = 100,
+    Queen,
+    Rook,
+    Bishop,
+    Knight,
+    Pawn,
     Super,
 }
 
@@ -174,8 +174,8 @@ impl Square {
 
     /// A function that generates the `u64` representation of a `Square`.\
     /// * `returns` - a `u64` indicating the position given by the `Square`
-    pub fn to_u64(&self) -> u64 {
-        match &self {
+    pub fn to_u64(self) -> u64 {
+        match self {
             Square::A8 => 0x80000000_00000000,
             Square::B8 => 0x40000000_00000000,
             Square::C8 => 0x20000000_00000000,
@@ -332,7 +332,7 @@ impl Square {
 
     /// A function that generates the `str` representation of a `Square`.\
     /// * `returns` - a `&str` in the format [A-H]{1}[1-8]{1} indicating the position given by the `Square`
-    pub fn to_str(&self) -> &str {
+    pub fn to_str(self) -> &'static str {
         match self {
             Square::A8 => "a8",
             Square::B8 => "b8",
@@ -430,12 +430,12 @@ pub enum MoveType {
 impl MoveType {
     /// A function that generates the `str` representation of a `MoveType`.\
     /// * `returns` - a `&str` in the following 4-bit format:
-    /// 1st bit: promotion
-    /// 2nd bit: capture
-    /// 3rd bit: special 1
-    /// 4th bit: special 0
+    ///   1st bit: promotion
+    ///   2nd bit: capture
+    ///   3rd bit: special 1
+    ///   4th bit: special 0
     #[allow(dead_code)]
-    pub fn to_str(&self) -> &str {
+    pub fn to_str(self) -> &'static str {
         match self {
             MoveType::QuietMove => "0000",
             MoveType::DoublePawnPush => "0001",
@@ -455,6 +455,19 @@ impl MoveType {
     }
 }
 
+/// Formats a move in the long algebraic notation used by UCI, e.g. `e7e8q`.
+pub fn uci_move_string(from: Square, to: Square, movetype: MoveType) -> String {
+    use MoveType::*;
+    let promo = match movetype {
+        QPromotion | QPromoCapture => "q",
+        RPromotion | RPromoCapture => "r",
+        BPromotion | BPromoCapture => "b",
+        NPromotion | NPromoCapture => "n",
+        _ => "",
+    };
+    format!("{}{}{}", from.to_str(), to.to_str(), promo)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CastlingRights {
     Kingside,
@@ -496,41 +509,11 @@ impl CastlingRecord {
     /// - if the input is not a single character string in ["K", "Q", "k", "q"].
     pub fn contains(&self, s: &str) -> bool {
         match s {
-            "K" => {
-                if self.white == CastlingRights::Both || self.white == CastlingRights::Kingside {
-                    true
-                } else {
-                    false
-                }
-            }
-            "Q" => {
-                if self.white == CastlingRights::Both || self.white == CastlingRights::Queenside {
-                    true
-                } else {
-                    false
-                }
-            }
-            "k" => {
-                if self.black == CastlingRights::Both || self.black == CastlingRights::Kingside {
-                    true
-                } else {
-                    false
-                }
-            }
-            "q" => {
-                if self.black == CastlingRights::Both || self.black == CastlingRights::Queenside {
-                    true
-                } else {
-                    false
-                }
-            }
-            "-" => {
-                if self.black == CastlingRights::Neither && self.white == CastlingRights::Neither {
-                    true
-                } else {
-                    false
-                }
-            }
+            "K" => self.white == CastlingRights::Both || self.white == CastlingRights::Kingside,
+            "Q" => self.white == CastlingRights::Both || self.white == CastlingRights::Queenside,
+            "k" => self.black == CastlingRights::Both || self.black == CastlingRights::Kingside,
+            "q" => self.black == CastlingRights::Both || self.black == CastlingRights::Queenside,
+            "-" => self.black == CastlingRights::Neither && self.white == CastlingRights::Neither,
             other => {
                 eprintln!("Caught str {other}");
                 unreachable!("We will never have malformed FEN input at this point.");
@@ -551,9 +534,9 @@ impl Display for CastlingRecord {
     }
 }
 
-impl Into<String> for CastlingRecord {
-    fn into(self) -> String {
-        format!("{self}")
+impl From<CastlingRecord> for String {
+    fn from(val: CastlingRecord) -> Self {
+        format!("{val}")
     }
 }
 
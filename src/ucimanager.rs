@@ -1,239 +1,933 @@
-use crate::gamemanager::legal_moves::search::root_negamax;
-use crate::types::{MoveType, Square};
-use crate::{
-    enginemanager::Engine,
-    gamemanager::GameManager,
-    movetable::{noarc::NoArc, MoveTable},
+use crate::book::{self, Book};
+use crate::gamemanager::evaluation::nnue::{self, Network};
+use crate::gamemanager::evaluation::params::{set_params, with_params, EvalParams};
+use crate::gamemanager::legal_moves::{
+    search::{SearchLimits, SearchSettings},
+    transposition::TranspositionTable,
 };
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use crate::notation::san;
+use crate::options;
+use crate::searchcontroller::{send, Output, SearchController, SearchJob};
+use crate::syzygy::{self, Tablebases};
+use crate::types::{uci_move_string, MoveType, PieceType, Square};
+use crate::{enginemanager::Engine, gamemanager::GameManager, movetable::MoveTable};
+use std::fmt::Display;
+use std::fs::OpenOptions;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
 use std::time::Duration;
-use std::{io, thread};
-use vampirc_uci::{UciMessage, UciMove, UciPiece};
-
-pub fn communicate(
-    mut e: Engine,
-    search_flag: Arc<AtomicBool>,
-    best_move: Arc<Mutex<(Square, Square, MoveType)>>,
-) {
-    loop {
-        let mut text = String::new();
+use vampirc_uci::{UciFen, UciMessage, UciMove, UciPiece, UciSearchControl, UciTimeControl};
+
+/// Why a command couldn't be carried out. Each is reported to the GUI as an
+/// `info string`, and leaves the engine as it was before the command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UciError {
+    /// The line isn't a command we understand.
+    UnknownCommand(String),
+    /// `position fen` was given a malformed FEN, or one we can't play from.
+    InvalidFen(String),
+    /// A move in a command isn't legal where it's played.
+    IllegalMove(String),
+    /// `setoption` named an unknown option or gave it a bad value.
+    InvalidOption(String),
+    /// A file couldn't be read or written.
+    Io(String),
+}
+
+impl Display for UciError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UciError::UnknownCommand(command) => write!(f, "Unknown command: {}", command),
+            UciError::InvalidFen(fen) => write!(f, "Invalid FEN: {}", fen),
+            UciError::IllegalMove(mv) => write!(f, "Illegal move: {}", mv),
+            UciError::InvalidOption(reason) => write!(f, "Invalid option: {}", reason),
+            UciError::Io(reason) => write!(f, "I/O error: {}", reason),
+        }
+    }
+}
 
-        io::stdin()
-            .read_line(&mut text)
-            .expect("Failed to read line");
+/// Runs the UCI loop, reading commands from `input` until `quit` or the end
+/// of input, and writing responses to `out`.
+pub fn communicate(mut e: Engine, mut input: impl BufRead, out: Output) {
+    let mut search = SearchController::new(out.clone());
+
+    loop {
+        // Read raw bytes, so that a line of garbage can't stop the loop.
+        let mut bytes = Vec::new();
+        match input.read_until(b'\n', &mut bytes) {
+            Ok(0) | Err(_) => {
+                search.stop(); // The GUI went away; treat it as "quit".
+                break;
+            }
+            Ok(_) => {}
+        }
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+        if text.trim().is_empty() {
+            continue;
+        }
+        log_command(&e, &text);
         let msg = vampirc_uci::parse_one(&text);
 
-        match msg {
+        let result = match msg {
             UciMessage::Uci => {
-                println!("id name Swordfish");
-                println!("id author Emilio Zuniga, Ethan Barry, Eric Oliver, Grace Kizer, & Zachary Wilson");
-                println!("uciok");
+                send(&out, "id name Swordfish");
+                send(&out, "id author Emilio Zuniga, Ethan Barry, Eric Oliver, Grace Kizer, & Zachary Wilson");
+                for config in e.options.configs() {
+                    send(&out, &config.to_string());
+                }
+                send(&out, "uciok");
+                Ok(())
             }
             UciMessage::IsReady => {
-                println!("readyok")
+                send(&out, "readyok");
+                Ok(())
             }
+            UciMessage::Debug(_) | UciMessage::Register { .. } => Ok(()),
             UciMessage::UciNewGame => {
-                //e.set_new_game = true;
+                search.stop();
+                e.new_game();
+                Ok(())
             }
             UciMessage::Position {
                 startpos,
                 fen,
                 moves,
-            } => {
-                // For now, we'll reinitalize the engine's data
-                // (minus movetable) each time we receive a
-                // 'position' command.
-                if startpos {
-                    e.board = GameManager::default();
-                } else {
-                    e.board = GameManager::from_fen_str(fen.unwrap().as_str());
-                }
-                e.move_history = moves.clone();
-
-                for m in moves {
-                    e.board = make_move(&e.board, &e.tbl, m);
-                }
-
-                //e.set_new_game = false;
-            }
+            } => set_position(&mut e, if startpos { None } else { fen }, moves),
             UciMessage::Go {
                 time_control,
-                search_control: _,
-            } => {
-                search_flag.store(true, Ordering::Relaxed);
-                {
-                    let mut lock = best_move.lock().unwrap();
-                    *lock = (Square::A1, Square::A1, MoveType::QuietMove); // Reinitialize best_move.
-                } // Lock dropped here.
-                let flag = search_flag.clone();
-                let best_move = best_move.clone();
-                let table = NoArc::new(MoveTable::default()); // TODO: Really hurts to create a whole new table...
-                let gm = e.board.clone();
-
-                if let Some(timectl) = time_control {
-                    match timectl {
-                        vampirc_uci::UciTimeControl::Infinite => {
-                            thread::spawn(move || {
-                                root_negamax(15, gm, &table, flag, best_move);
-                            });
-                        }
-                        vampirc_uci::UciTimeControl::MoveTime(t) => {
-                            let flag_clone = flag.clone();
-                            let best_move_clone = best_move.clone();
-                            thread::spawn(move || {
-                                root_negamax(10, gm, &table, flag, best_move);
-                                {
-                                    let lock = best_move_clone.lock().unwrap();
-                                    use MoveType::*;
-                                    let promo = match lock.2 {
-                                        QPromotion | QPromoCapture => "q",
-                                        RPromotion | RPromoCapture => "r",
-                                        BPromotion | BPromoCapture => "b",
-                                        NPromotion | NPromoCapture => "n",
-                                        _ => "",
-                                    };
-                                    let outstr = format!(
-                                        "bestmove {}{}{}",
-                                        lock.0.to_str(),
-                                        lock.1.to_str(),
-                                        promo
-                                    );
-                                    println!("{}", outstr);
-                                }
-                            });
-                            thread::spawn(move || {
-                                thread::sleep(Duration::from_millis(t.num_milliseconds() as u64));
-                                flag_clone.store(false, Ordering::Relaxed);
-                            });
-                        }
-                        vampirc_uci::UciTimeControl::Ponder => unimplemented!(),
-                        vampirc_uci::UciTimeControl::TimeLeft {
-                            white_time,
-                            black_time,
-                            white_increment: _,
-                            black_increment: _,
-                            moves_to_go: _,
-                        } => {
-                            let flag_clone = flag.clone();
-                            let best_move_clone = best_move.clone();
-                            thread::spawn(move || {
-                                root_negamax(10, gm, &table, flag, best_move);
-                                {
-                                    let lock = best_move_clone.lock().unwrap();
-                                    use MoveType::*;
-                                    let promo = match lock.2 {
-                                        QPromotion | QPromoCapture => "q",
-                                        RPromotion | RPromoCapture => "r",
-                                        BPromotion | BPromoCapture => "b",
-                                        NPromotion | NPromoCapture => "n",
-                                        _ => "",
-                                    };
-                                    let outstr = format!(
-                                        "bestmove {}{}{}",
-                                        lock.0.to_str(),
-                                        lock.1.to_str(),
-                                        promo
-                                    );
-                                    println!("{}", outstr);
-                                }
-                            });
-                            let time_remaining = if e.board.white_to_move {
-                                let time = white_time.unwrap().num_milliseconds().abs();
-                                Duration::from_millis(time as u64 / 20)
-                            } else {
-                                let time = black_time.unwrap().num_milliseconds().abs();
-                                Duration::from_millis(time as u64 / 20)
-                            };
-                            thread::spawn(move || {
-                                thread::sleep(time_remaining);
-                                flag_clone.store(false, Ordering::Relaxed);
-                            });
-                        }
-                    }
-                }
+                search_control,
+            } => go(&e, &mut search, &out, &text, time_control, search_control),
+            UciMessage::PonderHit => {
+                search.ponderhit();
+                Ok(())
             }
             UciMessage::Stop => {
-                search_flag.store(false, Ordering::Relaxed);
-                {
-                    let lock = best_move.lock().unwrap();
-                    use MoveType::*;
-                    let promo = match lock.2 {
-                        QPromotion | QPromoCapture => "q",
-                        RPromotion | RPromoCapture => "r",
-                        BPromotion | BPromoCapture => "b",
-                        NPromotion | NPromoCapture => "n",
-                        _ => "",
-                    };
-                    let outstr =
-                        format!("bestmove {}{}{}", lock.0.to_str(), lock.1.to_str(), promo);
-                    println!("{}", outstr);
-                }
+                search.stop();
+                Ok(())
             }
+            UciMessage::SetOption { name, value } => match e.options.set(&name, value.as_deref()) {
+                Ok(name) if name == options::HASH => {
+                    e.tt = Arc::new(TranspositionTable::new(
+                        e.options.spin(options::HASH) as usize
+                    ));
+                    Ok(())
+                }
+                Ok(name) if name == options::CLEAR_HASH => {
+                    e.tt.clear();
+                    Ok(())
+                }
+                Ok(name) if name == options::EVAL_FILE => load_eval_file(&mut e),
+                Ok(name) if name == options::NNUE_FILE => load_nnue_file(&mut e),
+                Ok(name) if name == options::EVAL_BACKEND => switch_backend(&mut e),
+                Ok(name) if name == options::BOOK_FILE => load_book(&mut e),
+                Ok(name) if name == options::SYZYGY_PATH => {
+                    load_tablebases(&e, &out);
+                    Ok(())
+                }
+                Ok(_) => Ok(()),
+                Err(err) => Err(UciError::InvalidOption(err)),
+            },
             UciMessage::Quit => {
-                search_flag.store(false, Ordering::Relaxed);
+                search.stop();
                 break;
             }
-            _ => {
-                println!("Some other message was received.");
-            }
+            UciMessage::Unknown(..) => debug_command(&mut e, &out, text.trim()),
+            _ => Err(UciError::UnknownCommand(String::from(text.trim()))),
+        };
+
+        if let Err(err) = result {
+            send(&out, &format!("info string {}", err));
         }
     } // End of the input loop. UCI terminates.
 }
 
-fn make_move(board: &GameManager, tbl: &NoArc<MoveTable>, m: UciMove) -> GameManager {
-    let h_from = Square::from_str(&m.from.to_string()).unwrap();
-    let h_to = Square::from_str(&m.to.to_string()).unwrap();
-    let legal_moves = board.legal_moves(&tbl);
-    let updated_data = legal_moves
-        .iter()
-        .find(|data| {
-            data.1 == h_from
-                && data.2 == h_to
-                && match m.promotion {
-                    Some(p) => match p {
-                        UciPiece::Knight => {
-                            if m.from.file != m.to.file {
-                                //if the files are not the same
-                                //then this was a promoting pawn capture
-                                data.3 == MoveType::NPromoCapture
-                            } else {
-                                data.3 == MoveType::NPromotion
-                            }
+/// Starts searching the current position as a `go` command asks.
+/// * `command` - the text of the command, as the parser loses some of it
+fn go(
+    e: &Engine,
+    search: &mut SearchController,
+    out: &Output,
+    command: &str,
+    mut time_control: Option<UciTimeControl>,
+    mut search_control: Option<UciSearchControl>,
+) -> Result<(), UciError> {
+    // The parser keeps only one of "ponder" and the limits that share the
+    // command with it, so look for it ourselves and parse the rest without it.
+    let ponder = command
+        .split_whitespace()
+        .any(|t| t.eq_ignore_ascii_case("ponder"));
+    if ponder {
+        let rest: Vec<&str> = command
+            .split_whitespace()
+            .filter(|t| !t.eq_ignore_ascii_case("ponder"))
+            .collect();
+        if let UciMessage::Go {
+            time_control: t,
+            search_control: s,
+        } = vampirc_uci::parse_one(&rest.join(" "))
+        {
+            (time_control, search_control) = (t, s);
+        }
+    }
+    let mut limits = search_limits(&e.board, e.tbl, out, search_control);
+    let bare_go_infinite = e.options.string(options::BARE_GO) == "Infinite";
+    let overhead = Duration::from_millis(e.options.spin(options::MOVE_OVERHEAD) as u64);
+    let divisor = e.options.spin(options::TIME_DIVISOR) as u64;
+    let budget = match time_control {
+        Some(UciTimeControl::MoveTime(t)) => {
+            Some(Duration::from_millis(t.num_milliseconds().max(0) as u64))
+                .map(|t| t.saturating_sub(overhead))
+        }
+        Some(UciTimeControl::TimeLeft {
+            white_time,
+            black_time,
+            white_increment,
+            black_increment,
+            moves_to_go,
+        }) => {
+            let (time, increment) = if e.board.white_to_move {
+                (white_time, white_increment)
+            } else {
+                (black_time, black_increment)
+            };
+            time.map(|t| {
+                clock_budget(
+                    t.num_milliseconds(),
+                    increment.map_or(0, |inc| inc.num_milliseconds()),
+                    moves_to_go,
+                    divisor,
+                    overhead,
+                )
+            })
+        }
+        Some(UciTimeControl::Ponder) | Some(UciTimeControl::Infinite) | None => None,
+    };
+
+    // With nothing at all to stop the search, a bare "go" or "go ponder"
+    // either searches until "stop", or to a fixed depth so that it still
+    // produces a bestmove.
+    let bare = time_control.is_none()
+        && limits.depth.is_none()
+        && limits.nodes.is_none()
+        && limits.mate.is_none();
+    let infinite =
+        matches!(time_control, Some(UciTimeControl::Infinite)) || bare && bare_go_infinite;
+
+    // A search that's asked to keep going, or to look at certain moves,
+    // wants more than the book can tell it.
+    if !infinite && !ponder && limits.search_moves.is_empty() && limits.mate.is_none() {
+        if let Some(mv) = book_move(e) {
+            search.stop();
+            send(
+                out,
+                &format!("bestmove {}", uci_move_string(mv.0, mv.1, mv.2)),
+            );
+            return Ok(());
+        }
+    }
+    if !infinite
+        && budget.is_none()
+        && limits.depth.is_none()
+        && limits.nodes.is_none()
+        && limits.mate.is_none()
+    {
+        limits.depth = Some(e.options.spin(options::DEFAULT_DEPTH) as u16);
+    }
+
+    search.go(SearchJob {
+        limits,
+        settings: SearchSettings {
+            contempt: e.options.spin(options::CONTEMPT) as i32,
+            show_wdl: e.options.check(options::SHOW_WDL),
+            multi_pv: e.options.spin(options::MULTI_PV) as usize,
+            syzygy_probe_limit: e.options.spin(options::SYZYGY_PROBE_LIMIT) as usize,
+        },
+        gm: e.board.clone(),
+        tbl: e.tbl,
+        tt: e.tt.clone(),
+        threads: e.options.spin(options::THREADS) as usize,
+        infinite,
+        ponder,
+        budget,
+        report_ponder: e.options.check(options::PONDER),
+    });
+    Ok(())
+}
+
+/// How long to think about one move with `time` milliseconds left, gaining
+/// `increment` after it. The clock is shared out over `moves_to_go` moves if
+/// the GUI says how many remain until the next time control, or over
+/// `divisor` otherwise, and most of the increment is spent as it comes. The
+/// answer never reaches past the remaining time, less the `overhead`, and a
+/// negative clock counts as none left.
+fn clock_budget(
+    time: i64,
+    increment: i64,
+    moves_to_go: Option<u8>,
+    divisor: u64,
+    overhead: Duration,
+) -> Duration {
+    let time = time.max(0) as u64;
+    let increment = increment.max(0) as u64;
+    let moves = moves_to_go.filter(|&n| n > 0).map_or(divisor, u64::from);
+    let share = time / moves + increment * 3 / 4;
+    Duration::from_millis(share.min(time)).saturating_sub(overhead)
+}
+
+/// The move the opening book has for the current position, if `OwnBook` is
+/// on and we're no further into the game than the `BookDepth`.
+fn book_move(e: &Engine) -> Option<(Square, Square, MoveType)> {
+    let book = e.book.as_ref()?;
+    if !e.options.check(options::OWN_BOOK)
+        || e.board.fullmoves as i64 > e.options.spin(options::BOOK_DEPTH)
+    {
+        return None;
+    }
+    book.choose(
+        &e.board,
+        e.tbl,
+        e.options.check(options::BEST_BOOK_MOVE),
+        book::random(),
+    )
+}
+
+/// Loads the opening book in the `BookFile`, or unloads the one in use if
+/// it's empty.
+fn load_book(e: &mut Engine) -> Result<(), UciError> {
+    let path = e.options.string(options::BOOK_FILE);
+    e.book = if path.is_empty() {
+        None
+    } else {
+        Some(
+            Book::load(path)
+                .map_err(|err| UciError::InvalidOption(format!("{}: {err}", options::BOOK_FILE)))?,
+        )
+    };
+    Ok(())
+}
+
+/// Switches the evaluation to the parameters in the `EvalFile`, or back to
+/// the built-in ones if it's empty. Scores in the hash table were worked
+/// out with the old parameters, so it's cleared too.
+fn load_eval_file(e: &mut Engine) -> Result<(), UciError> {
+    let path = e.options.string(options::EVAL_FILE);
+    let params = if path.is_empty() {
+        EvalParams::default()
+    } else {
+        EvalParams::load(path)
+            .map_err(|err| UciError::InvalidOption(format!("{}: {err}", options::EVAL_FILE)))?
+    };
+    set_params(params);
+    e.tt.clear();
+    Ok(())
+}
+
+/// Loads the network in the `NNUE File`, or unloads the one in use if it's
+/// empty, falling back to the handcrafted evaluation.
+fn load_nnue_file(e: &mut Engine) -> Result<(), UciError> {
+    let path = e.options.string(options::NNUE_FILE);
+    let network = if path.is_empty() {
+        None
+    } else {
+        Some(
+            Network::load(path)
+                .map_err(|err| UciError::InvalidOption(format!("{}: {err}", options::NNUE_FILE)))?,
+        )
+    };
+    nnue::set_network(network);
+    switch_backend(e)
+}
+
+/// Finds the tablebases in the `SyzygyPath` directories, and says how many
+/// there are. Directories that can't be read are skipped.
+fn load_tablebases(e: &Engine, out: &Output) {
+    let tablebases = Tablebases::open(e.options.string(options::SYZYGY_PATH));
+    send(
+        out,
+        &format!(
+            "info string found {} tablebases of up to {} pieces",
+            tablebases.len(),
+            tablebases.largest()
+        ),
+    );
+    syzygy::set_tablebases(tablebases);
+    e.tt.clear();
+}
+
+/// Scores positions with the evaluation the `Eval Backend` names. The NNUE
+/// needs a network to be loaded first.
+fn switch_backend(e: &mut Engine) -> Result<(), UciError> {
+    let use_nnue = e.options.string(options::EVAL_BACKEND) == "NNUE";
+    let ready = !use_nnue || nnue::evaluate(&e.board).is_some();
+    nnue::set_enabled(use_nnue && ready);
+    e.tt.clear();
+    if ready {
+        Ok(())
+    } else {
+        Err(UciError::InvalidOption(format!(
+            "{}: no network loaded; set the {} first",
+            options::EVAL_BACKEND,
+            options::NNUE_FILE
+        )))
+    }
+}
+
+/// Runs one of the non-standard commands used when debugging from a terminal:
+/// `d` shows the board, `eval` the evaluation, `flip` mirrors the position,
+/// `moves` lists the legal moves, and `evalsave <file>` writes the evaluation
+/// parameters in use to a file, to edit and load as the `EvalFile`.
+fn debug_command(e: &mut Engine, out: &Output, command: &str) -> Result<(), UciError> {
+    if let Some(path) = command.strip_prefix("evalsave ") {
+        return with_params(|p| p.save(path.trim())).map_err(UciError::Io);
+    }
+    match command {
+        "d" => send(out, &board_display(&e.board, e.tbl)),
+        "eval" => {
+            send(out, &e.board.evaluate_traced().to_string());
+            if let Some(eval) = nnue::evaluate(&e.board) {
+                send(
+                    out,
+                    &format!("NNUE evaluation: {eval} (for the side to move)"),
+                );
+            }
+        }
+        "flip" => {
+            let board = e.board.flipped();
+            e.start_fen = Some(board.to_fen_string());
+            e.move_history.clear();
+            e.board = board;
+        }
+        "moves" => {
+            for mv in e.board.legal_moves(e.tbl) {
+                send(
+                    out,
+                    &format!(
+                        "{} {}",
+                        uci_move_string(mv.1, mv.2, mv.3),
+                        san(&e.board, e.tbl, &mv)
+                    ),
+                );
+            }
+        }
+        _ => return Err(UciError::UnknownCommand(String::from(command))),
+    }
+    Ok(())
+}
+
+/// Draws `board` as a grid, followed by its FEN, hash key and checkers.
+fn board_display(board: &GameManager, tbl: &MoveTable) -> String {
+    let separator = " +---+---+---+---+---+---+---+---+\n";
+    let mut s = String::from(separator);
+    for (rank, row) in board.bitboard.to_string().lines().enumerate() {
+        for c in row.chars() {
+            s.push_str(&format!(" | {}", if c == '.' { ' ' } else { c }));
+        }
+        s.push_str(&format!(" | {}\n", 8 - rank));
+        s.push_str(separator);
+    }
+    s.push_str("   a   b   c   d   e   f   g   h\n\n");
+    s.push_str(&format!("Fen: {}\n", board.to_fen_string()));
+    s.push_str(&format!("Key: {:016X}\n", board.zobrist_key()));
+    s.push_str("Checkers:");
+    for square in board.checkers(tbl) {
+        s.push(' ');
+        s.push_str(square.to_str());
+    }
+    s
+}
+
+/// Appends a received command to the `Debug Log File`, if one is set.
+fn log_command(e: &Engine, text: &str) {
+    let path = e.options.string(options::DEBUG_LOG_FILE);
+    if !path.is_empty() {
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+            let _ = write!(file, ">> {}", text);
+        }
+    }
+}
+
+/// Converts the `go` command's search controls into `SearchLimits`,
+/// resolving any `searchmoves` against the legal moves of `board`. Illegal
+/// ones are reported and left out; if none are legal, every move is searched.
+fn search_limits(
+    board: &GameManager,
+    tbl: &MoveTable,
+    out: &Output,
+    search_control: Option<UciSearchControl>,
+) -> SearchLimits {
+    match search_control {
+        Some(sc) => SearchLimits {
+            depth: sc.depth.map(u16::from),
+            nodes: sc.nodes,
+            mate: sc.mate.map(u16::from),
+            search_moves: sc
+                .search_moves
+                .into_iter()
+                .filter_map(|m| {
+                    let found = find_legal_move(board, tbl, m);
+                    if found.is_none() {
+                        let err = UciError::IllegalMove(m.to_string());
+                        send(out, &format!("info string {}", err));
+                    }
+                    found.map(|data| (data.1, data.2, data.3))
+                })
+                .collect(),
+        },
+        None => SearchLimits::default(),
+    }
+}
+
+/// Sets up the position reached by playing `moves` from `fen`, or from the
+/// start position if that's `None`. When this continues the game we already
+/// have, only the new moves are played. If any move is illegal, the engine
+/// keeps its previous position.
+fn set_position(e: &mut Engine, fen: Option<UciFen>, moves: Vec<UciMove>) -> Result<(), UciError> {
+    let fen = fen.map(|f| f.0);
+    let continues = e.start_fen == fen && moves.starts_with(&e.move_history);
+    let (mut board, played) = if continues {
+        (e.board.clone(), e.move_history.len())
+    } else {
+        let board = match &fen {
+            Some(fen) => {
+                GameManager::try_from_fen_str(fen).ok_or(UciError::InvalidFen(fen.clone()))?
+            }
+            None => GameManager::default(),
+        };
+        (board, 0)
+    };
+
+    for m in &moves[played..] {
+        board = make_move(&board, e.tbl, *m)?;
+    }
+
+    e.board = board;
+    e.start_fen = fen;
+    e.move_history = moves;
+    Ok(())
+}
+
+fn make_move(board: &GameManager, tbl: &MoveTable, m: UciMove) -> Result<GameManager, UciError> {
+    find_legal_move(board, tbl, m)
+        .map(|data| data.4)
+        .ok_or(UciError::IllegalMove(m.to_string()))
+}
+
+/// Finds the legal move from `board` matching the UCI move `m`, if there is one.
+fn find_legal_move(
+    board: &GameManager,
+    tbl: &MoveTable,
+    m: UciMove,
+) -> Option<(PieceType, Square, Square, MoveType, GameManager)> {
+    let h_from = Square::from_str(&m.from.to_string())?;
+    let h_to = Square::from_str(&m.to.to_string())?;
+    let legal_moves = board.legal_moves(tbl);
+    legal_moves.into_iter().find(|data| {
+        data.1 == h_from
+            && data.2 == h_to
+            && match m.promotion {
+                Some(p) => match p {
+                    UciPiece::Knight => {
+                        if m.from.file != m.to.file {
+                            //if the files are not the same
+                            //then this was a promoting pawn capture
+                            data.3 == MoveType::NPromoCapture
+                        } else {
+                            data.3 == MoveType::NPromotion
                         }
-                        UciPiece::Bishop => {
-                            if m.from.file != m.to.file {
-                                //if the files are not the same
-                                //then this was a promoting pawn capture
-                                data.3 == MoveType::BPromoCapture
-                            } else {
-                                data.3 == MoveType::BPromotion
-                            }
+                    }
+                    UciPiece::Bishop => {
+                        if m.from.file != m.to.file {
+                            //if the files are not the same
+                            //then this was a promoting pawn capture
+                            data.3 == MoveType::BPromoCapture
+                        } else {
+                            data.3 == MoveType::BPromotion
                         }
-                        UciPiece::Rook => {
-                            if m.from.file != m.to.file {
-                                //if the files are not the same
-                                //then this was a promoting pawn capture
-                                data.3 == MoveType::RPromoCapture
-                            } else {
-                                data.3 == MoveType::RPromotion
-                            }
+                    }
+                    UciPiece::Rook => {
+                        if m.from.file != m.to.file {
+                            //if the files are not the same
+                            //then this was a promoting pawn capture
+                            data.3 == MoveType::RPromoCapture
+                        } else {
+                            data.3 == MoveType::RPromotion
                         }
-                        UciPiece::Queen => {
-                            if m.from.file != m.to.file {
-                                //if the files are not the same
-                                //then this was a promoting pawn capture
-                                data.3 == MoveType::QPromoCapture
-                            } else {
-                                data.3 == MoveType::QPromotion
-                            }
+                    }
+                    UciPiece::Queen => {
+                        if m.from.file != m.to.file {
+                            //if the files are not the same
+                            //then this was a promoting pawn capture
+                            data.3 == MoveType::QPromoCapture
+                        } else {
+                            data.3 == MoveType::QPromotion
                         }
-                        _ => panic!("We should never promote to a Pawn or King"),
-                    },
-                    None => true,
-                }
-        })
-        .unwrap();
+                    }
+                    _ => false, // We should never promote to a Pawn or King.
+                },
+                None => !matches!(
+                    data.3,
+                    MoveType::NPromotion
+                        | MoveType::BPromotion
+                        | MoveType::RPromotion
+                        | MoveType::QPromotion
+                        | MoveType::NPromoCapture
+                        | MoveType::BPromoCapture
+                        | MoveType::RPromoCapture
+                        | MoveType::QPromoCapture
+                ),
+            }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Feeds `script` to the UCI loop, returning everything it wrote except
+    /// the `info` lines reporting search progress.
+    fn run(script: &str) -> Vec<String> {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        communicate(Engine::default(), script.as_bytes(), buffer.clone());
+        let output = String::from_utf8(buffer.lock().unwrap().clone()).unwrap();
+        output
+            .lines()
+            .filter(|line| !line.starts_with("info") || line.starts_with("info string"))
+            .map(String::from)
+            .collect()
+    }
+
+    const MATE_IN_ONE: &str = "position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\n";
+
+    #[test]
+    fn clock_budgets() {
+        let overhead = Duration::from_millis(10);
+        let budget = |time, inc, mtg| clock_budget(time, inc, mtg, 20, overhead).as_millis();
+        assert_eq!(budget(60_000, 0, None), 2_990);
+        assert_eq!(budget(60_000, 2_000, None), 4_490);
+        assert_eq!(budget(60_000, 0, Some(10)), 5_990);
+        assert_eq!(budget(60_000, 0, Some(0)), 2_990);
+        // The last move before the time control may use the whole clock, but
+        // an increment never takes the budget past what's left.
+        assert_eq!(budget(1_000, 0, Some(1)), 990);
+        assert_eq!(budget(1_000, 5_000, None), 990);
+        assert_eq!(budget(-500, 1_000, None), 0);
+    }
+
+    #[test]
+    fn handshake() {
+        let output = run("uci\nisready\nquit\n");
+        assert_eq!(output[0], "id name Swordfish");
+        assert_eq!(output[output.len() - 2..], ["uciok", "readyok"]);
+    }
+
+    #[test]
+    fn stop_reports_once() {
+        let output = run(&format!(
+            "stop\n{MATE_IN_ONE}go infinite\nstop\nstop\ngo depth 1\nquit\n"
+        ));
+        assert_eq!(output, ["bestmove a1a8", "bestmove a1a8"]);
+    }
+
+    #[test]
+    fn new_go_finishes_old_search() {
+        let output = run(&format!("{MATE_IN_ONE}go infinite\ngo depth 1\n"));
+        assert_eq!(output, ["bestmove a1a8", "bestmove a1a8"]);
+    }
+
+    #[test]
+    fn isready_during_search() {
+        let output = run(&format!("{MATE_IN_ONE}go infinite\nisready\nstop\n"));
+        assert_eq!(output, ["readyok", "bestmove a1a8"]);
+    }
+
+    fn position(e: &mut Engine, command: &str) {
+        match vampirc_uci::parse_one(command) {
+            UciMessage::Position { fen, moves, .. } => set_position(e, fen, moves).unwrap(),
+            msg => panic!("Not a position command: {msg}"),
+        }
+    }
+
+    #[test]
+    fn position_continues_game() {
+        let mut e = Engine::default();
+        position(&mut e, "position startpos moves e2e4 e7e5");
+        position(&mut e, "position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(
+            e.board.to_fen_string(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+
+        // A different game is set up from scratch.
+        position(&mut e, "position startpos moves d2d4");
+        assert_eq!(e.move_history.len(), 1);
+        assert_eq!(
+            e.board.to_fen_string(),
+            "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq d3 0 1"
+        );
+    }
+
+    #[test]
+    fn illegal_position_keeps_board() {
+        let output = run(&format!(
+            "{MATE_IN_ONE}position startpos moves e2e4 e7e4\nposition fen 8/8/8/8/8/8/8/8 w - - 0 1\ngo depth 1\n"
+        ));
+        assert_eq!(
+            output,
+            [
+                "info string Illegal move: e7e4",
+                "info string Invalid FEN: 8/8/8/8/8/8/8/8 w - - 0 1",
+                "bestmove a1a8"
+            ]
+        );
+    }
+
+    #[test]
+    fn illegal_searchmoves_still_search() {
+        let output = run(&format!("{MATE_IN_ONE}go depth 2 searchmoves g1f1 a1h8\n"));
+        assert_eq!(output, ["info string Illegal move: a1h8", "bestmove g1f1"]);
+
+        // With no legal move left to search, every move is searched.
+        let output = run(&format!("{MATE_IN_ONE}go depth 1 searchmoves e2e4 a8a1\n"));
+        assert_eq!(
+            output,
+            [
+                "info string Illegal move: e2e4",
+                "info string Illegal move: a8a1",
+                "bestmove a1a8"
+            ]
+        );
+    }
+
+    #[test]
+    fn garbage_never_panics() {
+        let mut script = String::from(
+            "position fen garbage\n\
+             position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0\n\
+             position fen Prnbqkbn/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1\n\
+             position startpos moves e2e5\n\
+             position startpos moves e7e8q\n\
+             position startpos moves\n\
+             position\n\
+             go depth\n\
+             go searchmoves a1a1\n\
+             go wtime -5 btime x\n\
+             setoption name Hash value -1\n\
+             setoption name\n\
+             setoption\n\
+             ponderhit\n\
+             bestmove e2e4\n\
+             \u{1F41F}\n",
+        );
+        // Lines of pseudo-random printable bytes.
+        let mut seed = 0x2545F4914F6CDD1D_u64;
+        for _ in 0..200 {
+            for _ in 0..(seed % 40) {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                script.push((b' ' + (seed % 95) as u8) as char);
+            }
+            script.push('\n');
+        }
+        script.push_str("isready\n");
+
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        communicate(
+            Engine::default(),
+            &[script.as_bytes(), b"\xFF\xFE\n"].concat()[..],
+            buffer.clone(),
+        );
+        let output = String::from_utf8(buffer.lock().unwrap().clone()).unwrap();
+        assert!(output.lines().any(|line| line == "readyok"));
+        assert!(output.lines().all(|line| line.starts_with("info")
+            || line.starts_with("bestmove")
+            || line == "readyok"));
+    }
+
+    #[test]
+    fn debug_commands() {
+        let output = run("position startpos moves e2e4\nflip\nd\nmoves\neval\n");
+        assert!(output.contains(&String::from(
+            "Fen: rnbqkbnr/pppp1ppp/8/4p3/8/8/PPPPPPPP/RNBQKBNR w KQkq e6 0 1"
+        )));
+        assert!(output.contains(&String::from("Checkers:")));
+        assert!(output.contains(&String::from("g1f3 Nf3")));
+        assert!(output.iter().any(|l| l.starts_with("Evaluation: ")));
+
+        // Flipping the start position hands black the move.
+        let mut moves = run("position startpos\nflip\nmoves\n");
+        moves.sort();
+        let mut expected = [
+            "a7a5 a5", "a7a6 a6", "b7b5 b5", "b7b6 b6", "c7c5 c5", "c7c6 c6", "d7d5 d5", "d7d6 d6",
+            "e7e5 e5", "e7e6 e6", "f7f5 f5", "f7f6 f6", "g7g5 g5", "g7g6 g6", "h7h5 h5", "h7h6 h6",
+            "b8a6 Na6", "b8c6 Nc6", "g8f6 Nf6", "g8h6 Nh6",
+        ];
+        expected.sort();
+        assert_eq!(moves, expected);
+    }
+
+    #[test]
+    fn eval_files() {
+        let path = std::env::temp_dir().join(format!("swordfish-eval-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let output = run(&format!(
+            "evalsave {path}\nsetoption name EvalFile value {path}.missing\n"
+        ));
+        assert_eq!(output.len(), 1);
+        assert!(output[0].starts_with("info string Invalid option: EvalFile: "));
+
+        let saved = EvalParams::load(path).unwrap();
+        let _ = std::fs::remove_file(path);
+        assert_eq!(saved.to_text(), EvalParams::default().to_text());
+    }
+
+    #[test]
+    fn extreme_eval_files_search() {
+        // The parameters are shared by every test, so change them in a process of our own.
+        let child = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "ucimanager::tests::search_with_extreme_eval_file",
+                "--ignored",
+            ])
+            .output()
+            .unwrap();
+        assert!(
+            child.status.success(),
+            "{}",
+            String::from_utf8_lossy(&child.stdout)
+        );
+    }
+
+    /// Searches with the largest piece values, either way, that `EvalFile`
+    /// accepts. Run by `extreme_eval_files_search`, as it changes the
+    /// parameters for every test in the process.
+    #[test]
+    #[ignore]
+    fn search_with_extreme_eval_file() {
+        use crate::gamemanager::evaluation::score::MAX_VALUE;
+
+        let path =
+            std::env::temp_dir().join(format!("swordfish-extreme-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        for sign in [1, -1] {
+            let text = |v: i32| {
+                ["pawn", "knight", "bishop", "rook", "queen"]
+                    .map(|piece| format!("{piece}_value {} {}\n", sign * v, sign * v))
+                    .concat()
+            };
+            let v = (1..=MAX_VALUE)
+                .rev()
+                .find(|&v| EvalParams::from_text(&text(v)).is_ok())
+                .unwrap();
+            std::fs::write(path, text(v)).unwrap();
+            let output = run(&format!(
+                "setoption name EvalFile value {path}\n\
+                 position startpos\ngo depth 2\n\
+                 position fen qqqqkqqq/pppppppp/8/8/8/8/PPPPPPPP/QQQQKQQQ w - - 0 1\ngo depth 2\n"
+            ));
+            assert_eq!(output.len(), 2, "{output:?} with values of {}", sign * v);
+            assert!(output.iter().all(|line| line.starts_with("bestmove")));
+        }
+        let _ = std::fs::remove_file(path);
+        set_params(EvalParams::default());
+    }
+
+    #[test]
+    fn nnue_needs_a_network() {
+        let output = run(
+            "setoption name NNUE File value /nonexistent.nnue\nsetoption name Eval Backend value NNUE\n",
+        );
+        assert_eq!(output.len(), 2);
+        assert!(output[0].starts_with("info string Invalid option: NNUE File: "));
+        assert!(output[1].contains("no network loaded"));
+        assert!(!nnue::enabled());
+    }
+
+    #[test]
+    fn plays_book_moves() {
+        let path = std::env::temp_dir().join(format!("swordfish-book-{}.bin", std::process::id()));
+        let gm = GameManager::from_fen_str(
+            "rnbqkb1r/pppppppp/5n2/8/8/5N2/PPPPPPPP/RNBQKB1R w KQkq - 2 2",
+        );
+        let entry = book::BookEntry {
+            key: gm.zobrist_key(),
+            mv: book::encode_move(
+                &PieceType::Pawn,
+                Square::A2,
+                Square::A3,
+                MoveType::QuietMove,
+            ),
+            weight: 1,
+            learn: 0,
+        };
+        Book::new(vec![entry]).save(path.to_str().unwrap()).unwrap();
+
+        let output = run(&format!(
+            "setoption name BookFile value /nonexistent.bin\n\
+             setoption name BookFile value {}\nposition startpos moves g1f3 g8f6\ngo depth 1\n\
+             setoption name OwnBook value true\ngo depth 1\n\
+             setoption name BookDepth value 1\ngo depth 1\n",
+            path.display()
+        ));
+        let _ = std::fs::remove_file(&path);
+        assert!(output[0].starts_with("info string Invalid option: BookFile: "));
+        // The book is only used when asked, and up to its depth.
+        assert_ne!(output[1], "bestmove a2a3");
+        assert_eq!(output[2], "bestmove a2a3");
+        assert_ne!(output[3], "bestmove a2a3");
+    }
+
+    #[test]
+    fn reports_tablebases_found() {
+        let output = run("setoption name SyzygyPath value /nonexistent\n");
+        assert_eq!(output, ["info string found 0 tablebases of up to 0 pieces"]);
+    }
+
+    #[test]
+    fn ponderhit_reports_once() {
+        let output = run(&format!(
+            "setoption name Ponder value true\n{MATE_IN_ONE}go ponder\nponderhit\nstop\n"
+        ));
+        assert_eq!(output, ["bestmove a1a8"]);
+    }
+
+    #[test]
+    fn ponderhit_keeps_searching_to_the_depth() {
+        // Keep the input open until the bestmove is out, as its end would stop the search.
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let out = buffer.clone();
+        let uci = std::thread::spawn(move || {
+            communicate(Engine::default(), std::io::BufReader::new(reader), out)
+        });
+        let script = format!(
+            "setoption name Ponder value true\n{MATE_IN_ONE}go ponder depth 4\nponderhit\n"
+        );
+        writer.write_all(script.as_bytes()).unwrap();
+        let output = || String::from_utf8(buffer.lock().unwrap().clone()).unwrap();
+        let start = std::time::Instant::now();
+        while !output().contains("bestmove") && start.elapsed() < Duration::from_secs(60) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        drop(writer);
+        uci.join().unwrap();
 
-    updated_data.4.clone()
+        let output = output();
+        let lines: Vec<&str> = output.lines().collect();
+        let reached = lines
+            .iter()
+            .position(|line| line.starts_with("info depth 4 "))
+            .expect("The search stopped before depth 4.");
+        let bestmove = lines
+            .iter()
+            .position(|line| line.starts_with("bestmove"))
+            .unwrap();
+        assert!(reached < bestmove);
+        assert_eq!(
+            lines.iter().filter(|l| l.starts_with("bestmove")).count(),
+            1
+        );
+    }
 }